@@ -599,9 +599,14 @@ class AsyncBashlet:
             return result.stdout.strip()
 
     def _parse_session_list(self, result: CommandResult) -> list[Session]:
-        """Parse session list result."""
+        """Parse session list result.
+
+        Current CLIs wrap the list as ``{"schema_version": 1, "sessions": [...]}``;
+        older ones print a bare array.
+        """
         try:
-            items = json.loads(result.stdout)
+            parsed = json.loads(result.stdout)
+            items = parsed["sessions"] if isinstance(parsed, dict) else parsed
             return [
                 Session(
                     id=item["id"],
@@ -622,5 +627,5 @@ class AsyncBashlet:
                 )
                 for item in items
             ]
-        except (json.JSONDecodeError, KeyError):
+        except (json.JSONDecodeError, KeyError, TypeError):
             return []
@@ -271,7 +271,7 @@ class TestAsyncBashletSession:
             mock_process = AsyncMock()
             mock_process.communicate.return_value = (
                 json.dumps({
-                    "stdout": json.dumps(session_data),
+                    "stdout": json.dumps({"schema_version": 1, "sessions": session_data}),
                     "stderr": "",
                     "exit_code": 0,
                 }).encode(),
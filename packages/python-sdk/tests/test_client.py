@@ -467,7 +467,7 @@ class TestBashletSession:
         }]
         mock_run.return_value = MagicMock(
             stdout=json.dumps({
-                "stdout": json.dumps(session_data),
+                "stdout": json.dumps({"schema_version": 1, "sessions": session_data}),
                 "stderr": "",
                 "exit_code": 0,
             }),
@@ -488,7 +488,7 @@ class TestBashletSession:
     def test_list_sessions_empty(self, mock_run: MagicMock) -> None:
         mock_run.return_value = MagicMock(
             stdout=json.dumps({
-                "stdout": json.dumps([]),
+                "stdout": json.dumps({"schema_version": 1, "sessions": []}),
                 "stderr": "",
                 "exit_code": 0,
             }),
@@ -501,6 +501,30 @@ class TestBashletSession:
 
         assert sessions == []
 
+    @patch("subprocess.run")
+    def test_list_sessions_legacy_array(self, mock_run: MagicMock) -> None:
+        session_data = [{
+            "id": "session-1",
+            "created_at": 1704067200,
+            "last_activity": 1704067300,
+            "expired": False,
+            "workdir": "/workspace",
+        }]
+        mock_run.return_value = MagicMock(
+            stdout=json.dumps({
+                "stdout": json.dumps(session_data),
+                "stderr": "",
+                "exit_code": 0,
+            }),
+            stderr="",
+            returncode=0,
+        )
+
+        bashlet = Bashlet()
+        sessions = bashlet.list_sessions()
+
+        assert [s.id for s in sessions] == ["session-1"]
+
     @patch("subprocess.run")
     def test_list_sessions_parse_error(self, mock_run: MagicMock) -> None:
         mock_run.return_value = MagicMock(
@@ -537,7 +537,7 @@ describe("Bashlet", () => {
       ];
       mockedExeca.mockResolvedValueOnce({
         stdout: JSON.stringify({
-          stdout: JSON.stringify(sessionData),
+          stdout: JSON.stringify({ schema_version: 1, sessions: sessionData }),
           stderr: "",
           exit_code: 0,
         }),
@@ -562,6 +562,33 @@ describe("Bashlet", () => {
       expect(sessions[0].workdir).toBe("/workspace");
     });
 
+    it("should accept the bare array printed by older CLIs", async () => {
+      mockedExeca.mockResolvedValueOnce({
+        stdout: JSON.stringify({
+          stdout: JSON.stringify([
+            {
+              id: "session-1",
+              created_at: 1704067200,
+              last_activity: 1704067300,
+              expired: false,
+              mounts: [],
+              workdir: "/workspace",
+            },
+          ]),
+          stderr: "",
+          exit_code: 0,
+        }),
+        stderr: "",
+        exitCode: 0,
+        timedOut: false,
+      } as never);
+
+      const bashlet = new Bashlet();
+      const sessions = await bashlet.listSessions();
+
+      expect(sessions.map((s) => s.id)).toEqual(["session-1"]);
+    });
+
     it("should return empty array on parse error", async () => {
       mockedExeca.mockResolvedValueOnce({
         stdout: "invalid json",
@@ -579,7 +606,7 @@ describe("Bashlet", () => {
     it("should handle empty session list", async () => {
       mockedExeca.mockResolvedValueOnce({
         stdout: JSON.stringify({
-          stdout: JSON.stringify([]),
+          stdout: JSON.stringify({ schema_version: 1, sessions: [] }),
           stderr: "",
           exit_code: 0,
         }),
@@ -8,6 +8,7 @@ import type {
   Session,
   BashletJsonOutput,
   SessionListItem,
+  SessionListOutput,
 } from "./types.js";
 import {
   BashletError,
@@ -496,7 +497,11 @@ export class Bashlet {
 
   private parseSessionList(result: CommandResult): Session[] {
     try {
-      const items = JSON.parse(result.stdout) as SessionListItem[];
+      // Older CLIs print a bare array instead of { schema_version, sessions }
+      const parsed = JSON.parse(result.stdout) as
+        | SessionListOutput
+        | SessionListItem[];
+      const items = Array.isArray(parsed) ? parsed : parsed.sessions;
       return items.map((item) => ({
         id: item.id,
         name: item.name,
@@ -16,6 +16,7 @@ export type {
   ToolDefinition,
   BashletJsonOutput,
   SessionListItem,
+  SessionListOutput,
 } from "./types.js";
 
 // Error exports
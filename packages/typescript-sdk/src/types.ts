@@ -166,6 +166,14 @@ export interface BashletJsonOutput {
   error?: string;
 }
 
+/**
+ * Output of `bashlet list --format json`
+ */
+export interface SessionListOutput {
+  schema_version: number;
+  sessions: SessionListItem[];
+}
+
 /**
  * Session list item from bashlet CLI JSON output
  */
@@ -1,7 +1,9 @@
 use clap::{Args, Parser, Subcommand, ValueEnum};
+use std::net::IpAddr;
 use std::path::PathBuf;
 
-use crate::config::types::BackendType;
+use crate::config::types::{BackendType, PullPolicy};
+use crate::sandbox::BackendCapabilities;
 
 #[derive(Parser, Debug)]
 #[clap(name = "bashlet")]
@@ -28,6 +30,33 @@ pub struct GlobalOpts {
     /// Output format
     #[clap(long, global = true, default_value = "text", value_enum)]
     pub format: OutputFormat,
+
+    /// Print errors as JSON on stderr (implied by --format json)
+    #[clap(long, global = true)]
+    pub json_errors: bool,
+
+    /// Log format (json emits one structured object per line)
+    #[clap(
+        long,
+        global = true,
+        env = "BASHLET_LOG_FORMAT",
+        default_value = "text",
+        value_enum
+    )]
+    pub log_format: OutputFormat,
+
+    /// Write logs to this file at the -v level; the terminal then only shows warnings
+    #[clap(long, global = true, env = "BASHLET_LOG_FILE", value_name = "PATH")]
+    pub log_file: Option<PathBuf>,
+
+    /// Never download binaries or images; fail if they are not already present
+    #[clap(
+        long,
+        global = true,
+        env = "BASHLET_OFFLINE",
+        value_parser = clap::builder::FalseyValueParser::new()
+    )]
+    pub offline: bool,
 }
 
 #[derive(Subcommand, Debug)]
@@ -41,17 +70,47 @@ pub enum Commands {
     /// Terminate a session
     Terminate(TerminateArgs),
 
+    /// Replace a session's sandbox with a fresh one, keeping the session
+    Restart(RestartArgs),
+
+    /// Save a Docker session container as a new image
+    Commit(CommitArgs),
+
+    /// Show logs from a session's sandbox
+    Logs(LogsArgs),
+
+    /// Run commands in a session interactively, one per line
+    Shell(ShellArgs),
+
+    /// Remove a file or directory inside a session
+    Rm(RmArgs),
+
     /// Execute a one-shot command (create, run, terminate in one step)
     Exec(ExecArgs),
 
     /// List all active sessions
     List(ListArgs),
 
+    /// Stop the containers of sessions idle longer than `sandbox.idle_shutdown`
+    Reap,
+
     /// Initialize a new bashlet configuration
     Init(InitArgs),
 
     /// Manage configuration
     Config(ConfigArgs),
+
+    /// List or describe the presets defined in the configuration
+    Presets(PresetsArgs),
+
+    /// Inspect or clear cached downloads
+    Cache(CacheArgs),
+
+    /// Show version information
+    Version(VersionArgs),
+
+    /// Check the environment for common setup problems
+    Doctor,
 }
 
 // ============================================================================
@@ -68,25 +127,120 @@ pub struct CreateArgs {
     #[clap(long, short = 'p')]
     pub preset: Option<String>,
 
-    /// Mount host directories into sandbox (host_path:guest_path[:ro])
+    /// Apply the preset's mounts, env, and workdir, but skip its setup commands
+    #[clap(long, requires = "preset")]
+    pub no_setup: bool,
+
+    /// Mount host directories into sandbox (host_path:guest_path[:ro|:rw])
     #[clap(long = "mount", short = 'm', value_parser = parse_mount)]
     pub mounts: Vec<Mount>,
 
-    /// Environment variables to set in sandbox (KEY=VALUE)
+    /// Allow read-write mounts of sensitive host paths (see sandbox.denied_mount_paths)
+    #[clap(long)]
+    pub allow_unsafe_mount: bool,
+
+    /// Environment variables to set in sandbox (KEY=VALUE, or KEY to take the host's value)
     #[clap(long = "env", short = 'e', value_parser = parse_env_var)]
-    pub env_vars: Vec<(String, String)>,
+    pub env_vars: Vec<(String, Option<String>)>,
+
+    /// Copy host environment variables matching a glob (all if no PATTERN, repeatable)
+    #[clap(
+        long,
+        value_name = "PATTERN",
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "*"
+    )]
+    pub inherit_env: Vec<String>,
+
+    /// Also inherit variables that look like secrets (names with TOKEN, KEY, SECRET, ...)
+    #[clap(long)]
+    pub inherit_env_force: bool,
+
+    /// Load secret environment variables from FILE (KEY=VALUE lines), never logged or saved
+    #[clap(long, value_name = "FILE")]
+    pub secret_env_file: Option<PathBuf>,
 
     /// Working directory inside sandbox
-    #[clap(long, default_value = "/workspace")]
+    #[clap(long, default_value = "/workspace", value_parser = expand_path)]
     pub workdir: String,
 
     /// WASM binary to use as sandbox environment
     #[clap(long)]
     pub wasm: Option<PathBuf>,
 
+    /// Root filesystem image for Firecracker (overrides config and presets)
+    #[clap(long, value_name = "PATH", value_parser = parse_existing_file)]
+    pub rootfs: Option<PathBuf>,
+
+    /// Linux kernel image for Firecracker (overrides config)
+    #[clap(long, value_name = "PATH", value_parser = parse_existing_file)]
+    pub kernel: Option<PathBuf>,
+
+    /// Interpreter to run commands with, e.g. /bin/bash (overrides sandbox.shell)
+    #[clap(long, value_name = "PATH")]
+    pub shell: Option<String>,
+
+    /// When to pull the Docker image (overrides docker.pull_policy)
+    #[clap(long, value_enum)]
+    pub pull_policy: Option<PullPolicy>,
+
+    /// Extra argument passed verbatim to `docker run`, unvalidated (repeatable, e.g. --docker-arg=--gpus=all)
+    #[clap(long = "docker-arg", value_name = "ARG", allow_hyphen_values = true)]
+    pub docker_args: Vec<String>,
+
+    /// Shallow-clone a repository (URL[#REF]) and mount it at /workspace as the workdir
+    #[clap(long, value_name = "URL[#REF]", value_parser = parse_git_source, conflicts_with = "workdir")]
+    pub git: Option<GitSource>,
+
     /// Session time-to-live (e.g., "5m", "1h", "30s"). Session expires after this idle time.
     #[clap(long)]
     pub ttl: Option<String>,
+
+    /// DNS server for networked backends (repeatable)
+    #[clap(long = "dns", value_parser = parse_ip)]
+    pub dns: Vec<String>,
+
+    /// Extra /etc/hosts entry for networked backends (HOST:IP, repeatable)
+    #[clap(long = "add-host", value_parser = parse_host_entry)]
+    pub extra_hosts: Vec<(String, String)>,
+
+    /// Keep the sandbox running if a setup command fails (for debugging)
+    #[clap(long)]
+    pub keep_on_error: bool,
+
+    /// Print the merged configuration the session would use, without creating it
+    #[clap(long)]
+    pub print_config: bool,
+
+    #[clap(flatten)]
+    pub require: RequireArgs,
+}
+
+/// Capabilities the selected backend must provide.
+#[derive(Args, Debug)]
+pub struct RequireArgs {
+    /// Only use a backend with networking enabled
+    #[clap(long)]
+    pub require_network: bool,
+
+    /// Only use a backend whose filesystem persists between commands
+    #[clap(long)]
+    pub require_persistent_fs: bool,
+
+    /// Only use a backend that runs native Linux binaries (not WASM)
+    #[clap(long)]
+    pub require_native: bool,
+}
+
+impl RequireArgs {
+    pub fn capabilities(&self) -> BackendCapabilities {
+        BackendCapabilities {
+            native_linux: self.require_native,
+            networking: self.require_network,
+            persistent_fs: self.require_persistent_fs,
+        }
+    }
 }
 
 #[derive(Args, Debug)]
@@ -95,67 +249,335 @@ pub struct SessionRunArgs {
     pub session: String,
 
     /// Command to execute
-    pub command: String,
+    #[clap(required_unless_present = "script")]
+    pub command: Option<String>,
+
+    /// Run each line of FILE as a command, in order (blank lines and # comments are skipped)
+    #[clap(
+        long,
+        value_name = "FILE",
+        conflicts_with_all = ["command", "tty", "stdin", "stdout_file", "stderr_file", "head", "tail"]
+    )]
+    pub script: Option<PathBuf>,
+
+    /// Keep running script commands after one fails
+    #[clap(long, requires = "script")]
+    pub continue_on_error: bool,
 
     /// Create the session if it doesn't exist
     #[clap(long, short = 'C')]
     pub create: bool,
 
+    /// Restart the session's sandbox first if it fails a health check
+    #[clap(long)]
+    pub auto_restart: bool,
+
+    /// Allocate a TTY and attach the command to this terminal (Docker and SSH only)
+    #[clap(long, short = 't')]
+    pub tty: bool,
+
+    /// Pipe this process's stdin to the command (Docker, SSH, and Wasmer only)
+    #[clap(long, short = 'i', conflicts_with = "tty")]
+    pub stdin: bool,
+
+    /// Warn if the command uses more than this many MB on a writable mount's host filesystem
+    #[clap(long, value_name = "MB")]
+    pub mount_quota: Option<u64>,
+
+    /// Also write the JSON result to FILE, whatever the --format
+    #[clap(long, value_name = "FILE", conflicts_with = "tty")]
+    pub json_out: Option<PathBuf>,
+
+    /// Stream the command's stdout to FILE instead of capturing it (not subject to the output limit)
+    #[clap(long, value_name = "FILE", conflicts_with = "tty")]
+    pub stdout_file: Option<PathBuf>,
+
+    /// Stream the command's stderr to FILE instead of capturing it (not subject to the output limit)
+    #[clap(long, value_name = "FILE", conflicts_with = "tty")]
+    pub stderr_file: Option<PathBuf>,
+
+    /// Show only the first N lines of stdout and stderr
+    #[clap(long, value_name = "N", conflicts_with_all = ["tty", "tail"])]
+    pub head: Option<usize>,
+
+    /// Show only the last N lines of stdout and stderr
+    #[clap(long, value_name = "N", conflicts_with = "tty")]
+    pub tail: Option<usize>,
+
+    /// Apply --head/--tail to JSON output too, which is otherwise left whole
+    #[clap(long)]
+    pub truncate_json: bool,
+
+    /// Print the merged configuration the command would run with, without running it
+    #[clap(long)]
+    pub print_config: bool,
+
     /// Apply a preset configuration (requires --create)
     #[clap(long, short = 'p', requires = "create")]
     pub preset: Option<String>,
 
-    /// Mount host directories into sandbox (host_path:guest_path[:ro]) - requires --create
+    /// Apply the preset but skip its setup commands (requires --preset)
+    #[clap(long, requires = "preset")]
+    pub no_setup: bool,
+
+    /// Mount host directories into sandbox (host_path:guest_path[:ro|:rw]) - requires --create
     #[clap(long = "mount", short = 'm', value_parser = parse_mount, requires = "create")]
     pub mounts: Vec<Mount>,
 
-    /// Environment variables to set in sandbox (KEY=VALUE) - requires --create
+    /// Allow read-write mounts of sensitive host paths (see sandbox.denied_mount_paths)
+    #[clap(long, requires = "create")]
+    pub allow_unsafe_mount: bool,
+
+    /// Environment variables to set in sandbox (KEY=VALUE, or KEY to take the host's value) - requires --create
     #[clap(long = "env", short = 'e', value_parser = parse_env_var, requires = "create")]
-    pub env_vars: Vec<(String, String)>,
+    pub env_vars: Vec<(String, Option<String>)>,
 
     /// Working directory inside sandbox - requires --create
-    #[clap(long, default_value = "/workspace", requires = "create")]
+    #[clap(
+        long,
+        default_value = "/workspace",
+        requires = "create",
+        value_parser = expand_path
+    )]
     pub workdir: String,
 
     /// Session time-to-live (e.g., "5m", "1h", "30s") - requires --create
     #[clap(long, requires = "create")]
     pub ttl: Option<String>,
+
+    /// Interpreter to run commands with, e.g. /bin/bash - requires --create
+    #[clap(long, value_name = "PATH", requires = "create")]
+    pub shell: Option<String>,
+
+    /// DNS server for networked backends (repeatable) - requires --create
+    #[clap(long = "dns", value_parser = parse_ip, requires = "create")]
+    pub dns: Vec<String>,
+
+    /// Extra /etc/hosts entry (HOST:IP, repeatable) - requires --create
+    #[clap(long = "add-host", value_parser = parse_host_entry, requires = "create")]
+    pub extra_hosts: Vec<(String, String)>,
 }
 
 #[derive(Args, Debug)]
 pub struct TerminateArgs {
+    /// Session IDs or names
+    #[clap(required_unless_present = "pattern")]
+    pub sessions: Vec<String>,
+
+    /// Terminate all sessions whose name or ID matches this glob (e.g. "ci-*")
+    #[clap(long)]
+    pub pattern: Option<String>,
+
+    /// Don't ask for confirmation before terminating sessions matched by --pattern
+    #[clap(long, short = 'y')]
+    pub yes: bool,
+}
+
+#[derive(Args, Debug)]
+pub struct RestartArgs {
+    /// Session ID or name
+    pub session: String,
+}
+
+#[derive(Args, Debug)]
+pub struct CommitArgs {
+    /// Session ID or name
+    pub session: String,
+
+    /// Image to create (e.g. "my-env:latest")
+    pub image: String,
+}
+
+#[derive(Args, Debug)]
+pub struct ShellArgs {
+    /// Session ID or name
+    pub session: String,
+}
+
+#[derive(Args, Debug)]
+pub struct RmArgs {
+    /// Session ID or name
+    pub session: String,
+
+    /// Path inside the sandbox
+    pub path: String,
+
+    /// Remove directories and their contents
+    #[clap(long, short = 'r')]
+    pub recursive: bool,
+
+    /// Allow removing top-level paths such as / or /usr
+    #[clap(long)]
+    pub force: bool,
+}
+
+#[derive(Args, Debug)]
+pub struct LogsArgs {
     /// Session ID or name
     pub session: String,
+
+    /// Keep streaming new log output
+    #[clap(short, long)]
+    pub follow: bool,
 }
 
 #[derive(Args, Debug)]
 pub struct ExecArgs {
-    /// Command to execute
-    pub command: String,
+    /// Command to execute (run by a shell)
+    #[clap(required_unless_present = "argv")]
+    pub command: Option<String>,
+
+    /// Program and arguments to run without a shell, after `--`
+    #[clap(last = true, value_name = "ARGV", conflicts_with_all = ["command", "stdin"])]
+    pub argv: Vec<String>,
 
     /// Apply a preset configuration
     #[clap(long, short = 'p')]
     pub preset: Option<String>,
 
+    /// Apply the preset's mounts, env, and workdir, but skip its setup commands
+    #[clap(long, requires = "preset")]
+    pub no_setup: bool,
+
     /// Sandbox backend to use (wasmer, firecracker, auto)
     #[clap(long, short = 'b', value_enum)]
     pub backend: Option<BackendType>,
 
-    /// Mount host directories into sandbox (host_path:guest_path[:ro])
+    /// Mount host directories into sandbox (host_path:guest_path[:ro|:rw])
     #[clap(long = "mount", short = 'm', value_parser = parse_mount)]
     pub mounts: Vec<Mount>,
 
-    /// Environment variables to set in sandbox (KEY=VALUE)
+    /// Allow read-write mounts of sensitive host paths (see sandbox.denied_mount_paths)
+    #[clap(long)]
+    pub allow_unsafe_mount: bool,
+
+    /// Environment variables to set in sandbox (KEY=VALUE, or KEY to take the host's value)
     #[clap(long = "env", short = 'e', value_parser = parse_env_var)]
-    pub env_vars: Vec<(String, String)>,
+    pub env_vars: Vec<(String, Option<String>)>,
+
+    /// Copy host environment variables matching a glob (all if no PATTERN, repeatable)
+    #[clap(
+        long,
+        value_name = "PATTERN",
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "*"
+    )]
+    pub inherit_env: Vec<String>,
+
+    /// Also inherit variables that look like secrets (names with TOKEN, KEY, SECRET, ...)
+    #[clap(long)]
+    pub inherit_env_force: bool,
+
+    /// Load secret environment variables from FILE (KEY=VALUE lines), never logged or saved
+    #[clap(long, value_name = "FILE")]
+    pub secret_env_file: Option<PathBuf>,
 
     /// Working directory inside sandbox
-    #[clap(long, default_value = "/workspace")]
+    #[clap(long, default_value = "/workspace", value_parser = expand_path)]
     pub workdir: String,
 
     /// WASM binary to use as sandbox environment (deprecated, use --backend wasmer)
     #[clap(long)]
     pub wasm: Option<PathBuf>,
+
+    /// Root filesystem image for Firecracker (overrides config and presets)
+    #[clap(long, value_name = "PATH", value_parser = parse_existing_file)]
+    pub rootfs: Option<PathBuf>,
+
+    /// Linux kernel image for Firecracker (overrides config)
+    #[clap(long, value_name = "PATH", value_parser = parse_existing_file)]
+    pub kernel: Option<PathBuf>,
+
+    /// Interpreter to run commands with, e.g. /bin/bash (overrides sandbox.shell)
+    #[clap(long, value_name = "PATH")]
+    pub shell: Option<String>,
+
+    /// When to pull the Docker image (overrides docker.pull_policy)
+    #[clap(long, value_enum)]
+    pub pull_policy: Option<PullPolicy>,
+
+    /// Extra argument passed verbatim to `docker run`, unvalidated (repeatable, e.g. --docker-arg=--gpus=all)
+    #[clap(long = "docker-arg", value_name = "ARG", allow_hyphen_values = true)]
+    pub docker_args: Vec<String>,
+
+    /// Shallow-clone a repository (URL[#REF]) and mount it at /workspace as the workdir
+    #[clap(long, value_name = "URL[#REF]", value_parser = parse_git_source, conflicts_with = "workdir")]
+    pub git: Option<GitSource>,
+
+    /// Allocate a TTY and attach the command to this terminal (Docker and SSH only)
+    #[clap(long, short = 't')]
+    pub tty: bool,
+
+    /// Pipe this process's stdin to the command (Docker, SSH, and Wasmer only)
+    #[clap(long, short = 'i', conflicts_with = "tty")]
+    pub stdin: bool,
+
+    /// Warn if the command uses more than this many MB on a writable mount's host filesystem
+    #[clap(long, value_name = "MB")]
+    pub mount_quota: Option<u64>,
+
+    /// Also write the JSON result to FILE, whatever the --format
+    #[clap(long, value_name = "FILE", conflicts_with = "tty")]
+    pub json_out: Option<PathBuf>,
+
+    /// Stream the command's stdout to FILE instead of capturing it (not subject to the output limit)
+    #[clap(long, value_name = "FILE", conflicts_with = "tty")]
+    pub stdout_file: Option<PathBuf>,
+
+    /// Stream the command's stderr to FILE instead of capturing it (not subject to the output limit)
+    #[clap(long, value_name = "FILE", conflicts_with = "tty")]
+    pub stderr_file: Option<PathBuf>,
+
+    /// Show only the first N lines of stdout and stderr
+    #[clap(long, value_name = "N", conflicts_with_all = ["tty", "tail"])]
+    pub head: Option<usize>,
+
+    /// Show only the last N lines of stdout and stderr
+    #[clap(long, value_name = "N", conflicts_with = "tty")]
+    pub tail: Option<usize>,
+
+    /// Apply --head/--tail to JSON output too, which is otherwise left whole
+    #[clap(long)]
+    pub truncate_json: bool,
+
+    /// Print the merged configuration the command would run with, without running it
+    #[clap(long)]
+    pub print_config: bool,
+
+    /// Mount the current directory (at /workspace, or --cwd-mount=GUEST_PATH) and use it as workdir
+    #[clap(
+        long,
+        value_name = "GUEST_PATH",
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "/workspace",
+        conflicts_with = "git"
+    )]
+    pub cwd_mount: Option<String>,
+
+    /// DNS server for networked backends (repeatable)
+    #[clap(long = "dns", value_parser = parse_ip)]
+    pub dns: Vec<String>,
+
+    /// Extra /etc/hosts entry for networked backends (HOST:IP, repeatable)
+    #[clap(long = "add-host", value_parser = parse_host_entry)]
+    pub extra_hosts: Vec<(String, String)>,
+
+    /// Keep the sandbox and save it as a session if a setup command fails (for debugging)
+    #[clap(long)]
+    pub keep_on_error: bool,
+
+    /// Keep the sandbox as a session instead of tearing it down, and print its ID
+    #[clap(long)]
+    pub detach: bool,
+
+    /// Name for the session kept by --detach
+    #[clap(long, short = 'n', requires = "detach")]
+    pub name: Option<String>,
+
+    #[clap(flatten)]
+    pub require: RequireArgs,
 }
 
 #[derive(Args, Debug)]
@@ -163,6 +585,18 @@ pub struct ListArgs {
     /// Show all sessions including expired (for debugging)
     #[clap(long)]
     pub all: bool,
+
+    /// Only show sessions created within this long (e.g., "1h", "30m")
+    #[clap(long, value_name = "AGE")]
+    pub since: Option<String>,
+
+    /// Only show sessions created at least this long ago (e.g., "1d")
+    #[clap(long, value_name = "AGE")]
+    pub until: Option<String>,
+
+    /// Show creation times as ages ("5m ago")
+    #[clap(long)]
+    pub relative: bool,
 }
 
 // ============================================================================
@@ -174,6 +608,14 @@ pub struct InitArgs {
     /// Force overwrite existing configuration
     #[clap(short, long)]
     pub force: bool,
+
+    /// Write only the [sandbox] section
+    #[clap(long, conflicts_with = "with_presets")]
+    pub minimal: bool,
+
+    /// Include example presets (python, node)
+    #[clap(long)]
+    pub with_presets: bool,
 }
 
 #[derive(Args, Debug)]
@@ -188,6 +630,56 @@ pub enum ConfigAction {
     Show,
     /// Show configuration file path
     Path,
+    /// Open the configuration file in $EDITOR, creating it if missing
+    Edit,
+}
+
+#[derive(Args, Debug)]
+pub struct PresetsArgs {
+    #[clap(subcommand)]
+    pub action: PresetsAction,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum PresetsAction {
+    /// List presets with their backend and mount/env counts
+    List,
+    /// Show a preset's full configuration
+    Show {
+        /// Preset name
+        name: String,
+    },
+}
+
+#[derive(Args, Debug)]
+pub struct CacheArgs {
+    #[clap(subcommand)]
+    pub action: CacheAction,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum CacheAction {
+    /// Show cache disk usage by category
+    Info,
+    /// Remove cached files (everything if no option is given)
+    Clear {
+        /// Remove downloaded runtimes, kernels, and images
+        #[clap(long)]
+        assets: bool,
+        /// Remove rootfs copies left behind by VMs that are no longer running
+        #[clap(long)]
+        instances: bool,
+        /// Remove both assets and orphaned instance copies
+        #[clap(long)]
+        all: bool,
+    },
+}
+
+#[derive(Args, Debug)]
+pub struct VersionArgs {
+    /// Also report the versions of docker, ssh, wasmer, and firecracker, and KVM availability
+    #[clap(long)]
+    pub backends: bool,
 }
 
 // ============================================================================
@@ -199,30 +691,202 @@ pub struct Mount {
     pub host_path: PathBuf,
     pub guest_path: String,
     pub readonly: bool,
+    /// Whether `ro` or `rw` was given; if not, `sandbox.mounts_readonly_by_default` decides
+    pub explicit_access: bool,
+    /// Extra bind-mount options (e.g. `z`, `cached`), only honored by Docker
+    pub options: Vec<String>,
 }
 
+/// Repository given with `--git URL[#REF]`.
+#[derive(Debug, Clone)]
+pub struct GitSource {
+    pub url: String,
+    /// Branch, tag, or commit to check out (the remote HEAD if not given)
+    pub reference: Option<String>,
+}
+
+fn parse_git_source(s: &str) -> Result<GitSource, String> {
+    let (url, reference) = match s.rsplit_once('#') {
+        Some((url, reference)) => (url, Some(reference)),
+        None => (s, None),
+    };
+    if url.is_empty() {
+        return Err("Repository URL is empty".to_string());
+    }
+    if reference.is_some_and(|r| r.is_empty() || r.starts_with('-')) {
+        return Err(format!("Invalid git ref in '{}'", s));
+    }
+    Ok(GitSource {
+        url: url.to_string(),
+        reference: reference.map(str::to_string),
+    })
+}
+
+/// Bind-mount options accepted after the guest path, besides `ro`/`rw`.
+pub const MOUNT_OPTIONS: &[&str] = &[
+    "z",
+    "Z",
+    "cached",
+    "delegated",
+    "consistent",
+    "shared",
+    "rshared",
+    "slave",
+    "rslave",
+    "private",
+    "rprivate",
+];
+
 fn parse_mount(s: &str) -> Result<Mount, String> {
-    // Format: host_path:guest_path[:ro]
+    let invalid = |reason: String| format!("Invalid mount '{}': {}", s, reason);
+
+    // Format: host_path:guest_path[:opt1,opt2,...]
     let parts: Vec<&str> = s.split(':').collect();
-    match parts.len() {
-        2 => Ok(Mount {
-            host_path: PathBuf::from(parts[0]),
-            guest_path: parts[1].to_string(),
-            readonly: false,
-        }),
-        3 if parts[2] == "ro" => Ok(Mount {
-            host_path: PathBuf::from(parts[0]),
-            guest_path: parts[1].to_string(),
-            readonly: true,
-        }),
-        _ => Err("Mount format: host_path:guest_path[:ro]".to_string()),
+    let (host, guest, opts) = match parts.as_slice() {
+        [host, guest] => (*host, *guest, None),
+        [host, guest, opts] => (*host, *guest, Some(*opts)),
+        _ => {
+            let mut reason = "expected host_path:guest_path[:ro|rw][,options]".to_string();
+            if let Some((host, guest)) = s.split_once('=') {
+                reason.push_str(&format!(" (use ':' rather than '=': {}:{})", host, guest));
+            }
+            return Err(invalid(reason));
+        }
+    };
+
+    if host.is_empty() || guest.is_empty() {
+        return Err(invalid("host and guest paths can't be empty".to_string()));
+    }
+    if !guest.starts_with('/') {
+        let mut reason = format!("guest path '{}' must be absolute", guest);
+        if host.starts_with('/') {
+            let swapped = [guest, host].into_iter().chain(opts).collect::<Vec<_>>();
+            reason.push_str(&format!(
+                " (the host path comes first: {})",
+                swapped.join(":")
+            ));
+        }
+        return Err(invalid(reason));
+    }
+
+    let mut readonly = false;
+    let mut explicit_access = false;
+    let mut options = Vec::new();
+    for opt in opts.into_iter().flat_map(|o| o.split(',')) {
+        match opt {
+            "ro" => (readonly, explicit_access) = (true, true),
+            "rw" | "writable" => (readonly, explicit_access) = (false, true),
+            _ if MOUNT_OPTIONS.contains(&opt) => options.push(opt.to_string()),
+            _ => {
+                return Err(invalid(format!(
+                    "unknown option '{}' (expected ro, rw, writable, or one of: {})",
+                    opt,
+                    MOUNT_OPTIONS.join(", ")
+                )))
+            }
+        }
+    }
+
+    Ok(Mount {
+        host_path: PathBuf::from(expand_path(host).map_err(invalid)?),
+        guest_path: guest.to_string(),
+        readonly,
+        explicit_access,
+        options,
+    })
+}
+
+/// Parse a `;`-separated list of mounts, as given in BASHLET_MOUNTS.
+pub fn parse_mount_list(s: &str) -> Result<Vec<Mount>, String> {
+    s.split(';')
+        .map(str::trim)
+        .filter(|spec| !spec.is_empty())
+        .map(parse_mount)
+        .collect()
+}
+
+/// Parse a host file path, expanding it and checking that the file exists.
+fn parse_existing_file(s: &str) -> Result<PathBuf, String> {
+    let path = PathBuf::from(expand_path(s)?);
+    if path.is_file() {
+        Ok(path)
+    } else {
+        Err(format!("File not found: {}", path.display()))
     }
 }
 
-fn parse_env_var(s: &str) -> Result<(String, String), String> {
-    s.split_once('=')
-        .map(|(k, v)| (k.to_string(), v.to_string()))
-        .ok_or_else(|| "Environment variable format: KEY=VALUE".to_string())
+/// Expand a leading `~` and `$VAR` / `${VAR}` references, as a shell would.
+///
+/// Referencing an unset variable is an error rather than expanding to an
+/// empty string, which would silently point at the wrong directory.
+pub fn expand_path(path: &str) -> Result<String, String> {
+    let mut expanded = String::with_capacity(path.len());
+    let mut rest = path;
+
+    if rest == "~" || rest.starts_with("~/") {
+        let home = std::env::var("HOME").map_err(|_| "HOME is not set".to_string())?;
+        expanded.push_str(&home);
+        rest = &rest[1..];
+    }
+
+    while let Some(pos) = rest.find('$') {
+        expanded.push_str(&rest[..pos]);
+        let after = &rest[pos + 1..];
+
+        let (name, remainder) = if let Some(braced) = after.strip_prefix('{') {
+            let end = braced
+                .find('}')
+                .ok_or_else(|| format!("Unterminated ${{ in '{}'", path))?;
+            (&braced[..end], &braced[end + 1..])
+        } else {
+            let end = after
+                .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+                .unwrap_or(after.len());
+            (&after[..end], &after[end..])
+        };
+
+        if name.is_empty() {
+            // A lone `$` isn't a variable reference
+            expanded.push('$');
+            rest = after;
+            continue;
+        }
+
+        let value = std::env::var(name)
+            .map_err(|_| format!("Environment variable '{}' in '{}' is not set", name, path))?;
+        expanded.push_str(&value);
+        rest = remainder;
+    }
+    expanded.push_str(rest);
+
+    Ok(expanded)
+}
+
+fn parse_ip(s: &str) -> Result<String, String> {
+    s.parse::<IpAddr>()
+        .map(|ip| ip.to_string())
+        .map_err(|_| format!("Invalid IP address: {}", s))
+}
+
+fn parse_host_entry(s: &str) -> Result<(String, String), String> {
+    // Format: hostname:ip (the IP may itself contain colons for IPv6)
+    let (host, ip) = s
+        .split_once(':')
+        .ok_or_else(|| "Host entry format: HOST:IP".to_string())?;
+    if host.is_empty() {
+        return Err("Host entry format: HOST:IP".to_string());
+    }
+    Ok((host.to_string(), parse_ip(ip)?))
+}
+
+/// Parse `KEY=VALUE`, or a bare `KEY` whose value is taken from the host
+/// when the command runs.
+fn parse_env_var(s: &str) -> Result<(String, Option<String>), String> {
+    match s.split_once('=') {
+        Some((k, v)) => Ok((k.to_string(), Some(v.to_string()))),
+        None if !s.is_empty() => Ok((s.to_string(), None)),
+        None => Err("Environment variable format: KEY=VALUE or KEY".to_string()),
+    }
 }
 
 #[derive(Debug, Clone, Default, ValueEnum)]
@@ -231,3 +895,114 @@ pub enum OutputFormat {
     Text,
     Json,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_mount_options() {
+        let mount = parse_mount("./src:/workspace").unwrap();
+        assert!(!mount.readonly);
+        assert!(!mount.explicit_access);
+        assert!(mount.options.is_empty());
+
+        let mount = parse_mount("./src:/workspace:rw").unwrap();
+        assert!(!mount.readonly);
+        assert!(mount.explicit_access);
+
+        let mount = parse_mount("./src:/workspace:ro,z").unwrap();
+        assert!(mount.readonly);
+        assert_eq!(mount.options, vec!["z"]);
+
+        let mount = parse_mount("./src:/workspace:cached").unwrap();
+        assert!(!mount.readonly);
+        assert_eq!(mount.options, vec!["cached"]);
+
+        assert!(parse_mount("./src:/workspace:ro,bogus").is_err());
+        assert!(parse_mount("./src").is_err());
+    }
+
+    #[test]
+    fn test_parse_mount_errors() {
+        let err = parse_mount("./src/workspace").unwrap_err();
+        assert!(err.contains("'./src/workspace'"), "{}", err);
+
+        let err = parse_mount("./src=/workspace").unwrap_err();
+        assert!(err.contains("./src:/workspace"), "{}", err);
+
+        let err = parse_mount("/workspace:./src:ro").unwrap_err();
+        assert!(err.contains("./src:/workspace:ro"), "{}", err);
+
+        let mounts = parse_mount_list("./a:/a; ./b:/b:ro;").unwrap();
+        assert_eq!(mounts.len(), 2);
+        assert!(mounts[1].readonly);
+        assert!(parse_mount_list("./a:/a;./b").is_err());
+    }
+
+    #[test]
+    fn test_parse_env_var() {
+        assert_eq!(
+            parse_env_var("A=b=c").unwrap(),
+            ("A".to_string(), Some("b=c".to_string()))
+        );
+        assert_eq!(
+            parse_env_var("GITHUB_TOKEN").unwrap(),
+            ("GITHUB_TOKEN".to_string(), None)
+        );
+        assert!(parse_env_var("").is_err());
+    }
+
+    #[test]
+    fn test_parse_host_entry() {
+        assert_eq!(
+            parse_host_entry("db.internal:10.0.0.5").unwrap(),
+            ("db.internal".to_string(), "10.0.0.5".to_string())
+        );
+        assert_eq!(
+            parse_host_entry("v6host:fd00::1").unwrap(),
+            ("v6host".to_string(), "fd00::1".to_string())
+        );
+        assert!(parse_host_entry("db.internal:not-an-ip").is_err());
+        assert!(parse_host_entry(":10.0.0.5").is_err());
+        assert!(parse_ip("1.2.3").is_err());
+    }
+
+    #[test]
+    fn test_parse_git_source() {
+        let source = parse_git_source("https://example.com/repo.git#v1.2").unwrap();
+        assert_eq!(source.url, "https://example.com/repo.git");
+        assert_eq!(source.reference.as_deref(), Some("v1.2"));
+
+        let source = parse_git_source("git@example.com:org/repo.git").unwrap();
+        assert_eq!(source.url, "git@example.com:org/repo.git");
+        assert!(source.reference.is_none());
+
+        assert!(parse_git_source("https://example.com/repo.git#").is_err());
+        assert!(parse_git_source("https://example.com/repo.git#--upload-pack=x").is_err());
+        assert!(parse_git_source("#main").is_err());
+    }
+
+    #[test]
+    fn test_expand_path() {
+        std::env::set_var("BASHLET_TEST_EXPAND", "/srv/data");
+        std::env::remove_var("BASHLET_TEST_UNSET");
+
+        assert_eq!(
+            expand_path("$BASHLET_TEST_EXPAND/x").unwrap(),
+            "/srv/data/x"
+        );
+        assert_eq!(
+            expand_path("${BASHLET_TEST_EXPAND}_x").unwrap(),
+            "/srv/data_x"
+        );
+        assert_eq!(expand_path("/plain/path").unwrap(), "/plain/path");
+        assert_eq!(expand_path("/cost$/x").unwrap(), "/cost$/x");
+        assert!(expand_path("$BASHLET_TEST_UNSET/x").is_err());
+        assert!(expand_path("${BASHLET_TEST_EXPAND").is_err());
+
+        let mount = parse_mount("$BASHLET_TEST_EXPAND:/data:ro").unwrap();
+        assert_eq!(mount.host_path, PathBuf::from("/srv/data"));
+        assert_eq!(mount.guest_path, "/data");
+    }
+}
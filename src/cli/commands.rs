@@ -1,33 +1,239 @@
-use std::path::PathBuf;
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::io::{IsTerminal, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
 use std::time::{Duration, UNIX_EPOCH};
 
 use chrono::{DateTime, Local, Utc};
-use tracing::info;
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, BufReader};
+use tracing::{debug, info, warn};
 
 use crate::cli::args::{
-    ConfigAction, ConfigArgs, CreateArgs, ExecArgs, InitArgs, ListArgs, Mount, OutputFormat,
-    SessionRunArgs, TerminateArgs,
+    expand_path, parse_mount_list, CacheAction, CacheArgs, CommitArgs, ConfigAction, ConfigArgs,
+    CreateArgs, ExecArgs, GitSource, InitArgs, ListArgs, LogsArgs, Mount, OutputFormat,
+    PresetsAction, PresetsArgs, RestartArgs, RmArgs, SessionRunArgs, ShellArgs, TerminateArgs,
+    VersionArgs,
 };
-use crate::config::loader::get_config_path;
-use crate::config::types::{BashletConfig, SandboxConfig};
+use crate::cli::json::{
+    CheckStatus, CommandResultJson, DoctorCheckJson, DoctorReportJson, EffectiveConfigJson,
+    PresetJson, PresetListJson, PresetSummaryJson, ScriptResultJson, ScriptStepJson,
+    SessionListJson, SCHEMA_VERSION,
+};
+use crate::config::loader::{get_cache_dir, get_config_path, get_data_dir, load_config};
+use crate::config::types::{BackendType, BashletConfig, PresetConfig, SandboxConfig};
 use crate::error::{BashletError, Result};
-use crate::sandbox::{create_backend, CommandResult, RuntimeConfig};
-use crate::session::{parse_ttl, Session, SessionManager};
+use crate::sandbox::cache;
+use crate::sandbox::{
+    available_backends, create_backend, shell_join, validate_auto_priority, CommandFilter,
+    CommandResult, DockerBackend, NetworkSettings, OutputFiles, RuntimeConfig, SandboxBackend,
+    SshBackend,
+};
+use crate::session::{new_checkout_dir, parse_ttl, Session, SessionLock, SessionManager};
+
+/// Backends created by the running command, shut down on interrupt.
+static ACTIVE_BACKENDS: Mutex<Vec<Arc<dyn SandboxBackend>>> = Mutex::new(Vec::new());
+
+/// Combine configured DNS/host overrides with those given on the command line.
+fn network_settings(
+    config: &SandboxConfig,
+    dns: &[String],
+    extra_hosts: &[(String, String)],
+) -> NetworkSettings {
+    NetworkSettings {
+        dns: config.dns.iter().chain(dns).cloned().collect(),
+        extra_hosts: config
+            .extra_hosts
+            .iter()
+            .chain(extra_hosts)
+            .cloned()
+            .collect(),
+    }
+}
+
+/// Create a one-shot backend and register it so it can be shut down on Ctrl-C.
+///
+/// Only for sandboxes the command owns: backends reattached to a saved
+/// session must not be tracked, since shutting them down would destroy the
+/// session's container or VM. Call [`untrack_backend`] once a tracked
+/// sandbox is saved as a session.
+async fn create_tracked_backend(
+    config: &SandboxConfig,
+    runtime: RuntimeConfig,
+) -> Result<Arc<dyn SandboxBackend>> {
+    let backend: Arc<dyn SandboxBackend> = Arc::from(create_backend(config, runtime).await?);
+    if let Ok(mut active) = ACTIVE_BACKENDS.lock() {
+        active.push(Arc::clone(&backend));
+    }
+    Ok(backend)
+}
+
+/// Stop tracking a backend whose sandbox now belongs to a saved session.
+fn untrack_backend(backend: &dyn SandboxBackend) {
+    if let Ok(mut active) = ACTIVE_BACKENDS.lock() {
+        active.retain(|b| !std::ptr::addr_eq(Arc::as_ptr(b), backend));
+    }
+}
+
+/// Shut down a sandbox the running command owns.
+///
+/// A session-mode Firecracker backend only detaches on shutdown, so the VM
+/// it kept running is stopped here too.
+async fn destroy_backend(backend: &dyn SandboxBackend) -> Result<()> {
+    backend.shutdown().await?;
+    match backend.persistent_instance_id() {
+        Some(ref id) if cache::is_vm_instance(id) => cache::stop_instance(id),
+        _ => Ok(()),
+    }
+}
+
+/// Shut down every backend created by the running command.
+///
+/// Called from the Ctrl-C handler so interrupted commands don't leave
+/// containers, VMs, or SSH connections behind.
+pub async fn shutdown_active_backends() {
+    let backends = match ACTIVE_BACKENDS.lock() {
+        Ok(mut active) => std::mem::take(&mut *active),
+        Err(_) => return,
+    };
+
+    for backend in backends {
+        if let Err(e) = destroy_backend(backend.as_ref()).await {
+            warn!(backend = backend.name(), error = %e, "Failed to shut down sandbox");
+        }
+    }
+}
 
 // ============================================================================
 // Preset Helpers
 // ============================================================================
 
-/// Expand tilde (~) in a path string to the user's home directory.
-fn expand_tilde(path: &str) -> PathBuf {
-    if let Ok(home) = std::env::var("HOME") {
-        if let Some(stripped) = path.strip_prefix("~/") {
-            return PathBuf::from(home).join(stripped);
-        } else if path == "~" {
-            return PathBuf::from(home);
+/// Expand `~` and environment variables in a host path from a preset.
+fn expand_host_path(path: &str) -> Result<PathBuf> {
+    expand_path(path)
+        .map(PathBuf::from)
+        .map_err(BashletError::Config)
+}
+
+/// Substrings of variable names that suggest a credential
+const SECRET_NAME_HINTS: &[&str] = &["TOKEN", "KEY", "SECRET", "PASSWORD", "CREDENTIAL"];
+
+/// Resolve `--env` values, taking a bare `KEY`'s value from the host like
+/// `docker run -e KEY`. Unset host variables are skipped with a warning.
+fn resolve_env_vars(vars: &[(String, Option<String>)]) -> Vec<(String, String)> {
+    vars.iter()
+        .filter_map(|(key, value)| match value {
+            Some(value) => Some((key.clone(), value.clone())),
+            None => match std::env::var(key) {
+                Ok(value) => Some((key.clone(), value)),
+                Err(_) => {
+                    warn!(key = %key, "Not setting --env variable that is unset on the host");
+                    None
+                }
+            },
+        })
+        .collect()
+}
+
+/// Copy host environment variables matching any of `patterns` into `env_vars`.
+///
+/// Variables already set explicitly (or by a preset) keep their value.
+/// Secret-looking names are skipped with a warning unless `force` is set.
+fn inherit_host_env(
+    patterns: &[String],
+    force: bool,
+    env_vars: &mut Vec<(String, String)>,
+) -> Result<()> {
+    if patterns.is_empty() {
+        return Ok(());
+    }
+
+    let globs = patterns
+        .iter()
+        .map(|p| {
+            glob::Pattern::new(p).map_err(|e| {
+                BashletError::Config(format!("Invalid --inherit-env pattern '{}': {}", p, e))
+            })
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    let mut host_vars: Vec<(String, String)> = std::env::vars()
+        .filter(|(key, _)| globs.iter().any(|g| g.matches(key)))
+        .filter(|(key, _)| !env_vars.iter().any(|(k, _)| k == key))
+        .collect();
+    host_vars.sort();
+
+    for (key, value) in host_vars {
+        let upper = key.to_uppercase();
+        if !force && SECRET_NAME_HINTS.iter().any(|hint| upper.contains(hint)) {
+            warn!(
+                key = %key,
+                "Not inheriting variable that looks like a secret (use --inherit-env-force or --env)"
+            );
+            continue;
+        }
+        env_vars.push((key, value));
+    }
+
+    Ok(())
+}
+
+/// Read `--secret-env-file`: KEY=VALUE lines, skipping blanks and `#` comments.
+///
+/// Errors never quote the line, since it may hold a secret.
+fn read_secret_env_file(path: &Option<PathBuf>) -> Result<Vec<(String, String)>> {
+    let Some(path) = path else {
+        return Ok(Vec::new());
+    };
+    let contents = std::fs::read_to_string(path).map_err(|e| {
+        BashletError::Config(format!(
+            "Failed to read secret env file {}: {}",
+            path.display(),
+            e
+        ))
+    })?;
+
+    contents
+        .lines()
+        .enumerate()
+        .map(|(i, line)| (i + 1, line.trim()))
+        .filter(|(_, line)| !line.is_empty() && !line.starts_with('#'))
+        .map(|(number, line)| match line.split_once('=') {
+            Some((key, value)) if !key.trim().is_empty() => {
+                Ok((key.trim().to_string(), value.to_string()))
+            }
+            _ => Err(BashletError::Config(format!(
+                "Invalid line {} in secret env file {}: expected KEY=VALUE",
+                number,
+                path.display()
+            ))),
+        })
+        .collect()
+}
+
+/// Mounts from BASHLET_MOUNTS (`;`-separated), followed by the `--mount` flags.
+///
+/// Mounts given without `ro` or `rw` are made read-only if `readonly_by_default`.
+fn with_env_mounts(cli_mounts: &[Mount], readonly_by_default: bool) -> Result<Vec<Mount>> {
+    let mut mounts = match std::env::var("BASHLET_MOUNTS") {
+        Ok(specs) => parse_mount_list(&specs)
+            .map_err(|e| BashletError::Config(format!("BASHLET_MOUNTS: {}", e)))?,
+        Err(_) => Vec::new(),
+    };
+    mounts.extend_from_slice(cli_mounts);
+
+    for mount in &mut mounts {
+        if !mount.explicit_access {
+            mount.readonly = readonly_by_default;
         }
+        debug!(
+            host = %mount.host_path.display(),
+            guest = %mount.guest_path,
+            readonly = mount.readonly,
+            "Resolved mount"
+        );
     }
-    PathBuf::from(path)
+    Ok(mounts)
 }
 
 /// Apply a preset configuration, merging with CLI arguments.
@@ -50,15 +256,19 @@ fn apply_preset(
     info!(preset = %preset_name, "Applying preset configuration");
 
     // Merge mounts (preset first, CLI args can add more)
-    let preset_mounts: Vec<Mount> = preset
+    let preset_mounts = preset
         .mounts
         .iter()
-        .map(|(host, guest, ro)| Mount {
-            host_path: expand_tilde(host),
-            guest_path: guest.clone(),
-            readonly: *ro,
+        .map(|(host, guest, ro)| {
+            Ok(Mount {
+                host_path: expand_host_path(host)?,
+                guest_path: guest.clone(),
+                readonly: *ro,
+                explicit_access: true,
+                options: Vec::new(),
+            })
         })
-        .collect();
+        .collect::<Result<Vec<_>>>()?;
     mounts.splice(0..0, preset_mounts);
 
     // Merge env vars (preset first, CLI args can override)
@@ -80,12 +290,305 @@ fn apply_preset(
 
     // Apply rootfs_image for Firecracker
     if let Some(ref rootfs) = preset.rootfs_image {
-        sandbox_config.firecracker.rootfs_path = Some(expand_tilde(&rootfs.display().to_string()));
+        sandbox_config.firecracker.rootfs_path =
+            Some(expand_host_path(&rootfs.display().to_string())?);
     }
 
     Ok(preset.setup_commands.clone())
 }
 
+/// Reject read-write mounts of denied host paths, and warn about mounting $HOME.
+fn check_mount_safety(mounts: &[Mount], denied: &[PathBuf], allow_unsafe: bool) -> Result<()> {
+    let home = std::env::var_os("HOME").map(PathBuf::from);
+
+    for mount in mounts {
+        // Resolve symlinks and `..` so the denylist can't be sidestepped
+        let path = std::fs::canonicalize(&mount.host_path).unwrap_or(mount.host_path.clone());
+
+        if home.as_ref() == Some(&path) {
+            warn!(
+                path = %path.display(),
+                readonly = mount.readonly,
+                "Mounting your home directory exposes SSH keys, credentials, and shell history to the sandbox"
+            );
+        }
+
+        // Everything is under `/`, so it only denies itself
+        let is_denied = denied
+            .iter()
+            .any(|d| path == *d || (d.parent().is_some() && path.starts_with(d)));
+        if is_denied && !mount.readonly {
+            if !allow_unsafe {
+                return Err(BashletError::UnsafeMount {
+                    path: path.display().to_string(),
+                });
+            }
+            warn!(path = %path.display(), "Mounting sensitive host path read-write");
+        }
+    }
+    Ok(())
+}
+
+/// Apply --rootfs/--kernel, which win over config and presets.
+fn apply_image_overrides(
+    sandbox_config: &mut SandboxConfig,
+    rootfs: &Option<PathBuf>,
+    kernel: &Option<PathBuf>,
+) {
+    if let Some(rootfs) = rootfs {
+        sandbox_config.firecracker.rootfs_path = Some(rootfs.clone());
+    }
+    if let Some(kernel) = kernel {
+        sandbox_config.firecracker.kernel_path = Some(kernel.clone());
+    }
+}
+
+/// Apply --shell, which wins over config.
+fn apply_shell_override(sandbox_config: &mut SandboxConfig, shell: &Option<String>) {
+    if let Some(shell) = shell {
+        sandbox_config.shell = Some(shell.clone());
+    }
+}
+
+/// Guest path a `--git` checkout is mounted at and used as the workdir.
+const GIT_WORKDIR: &str = "/workspace";
+
+/// Shallow clone made for `--git`.
+///
+/// The directory is removed on drop unless [`GitCheckout::keep`] hands it to
+/// a saved session, which removes it when terminated.
+struct GitCheckout {
+    path: PathBuf,
+    keep: bool,
+}
+
+impl GitCheckout {
+    /// Pick a new directory under the data dir, without cloning into it yet,
+    /// so `--print-config` can show the mount.
+    fn new() -> Self {
+        Self {
+            path: new_checkout_dir(),
+            keep: false,
+        }
+    }
+
+    /// Clone `source` into the checkout directory.
+    ///
+    /// Fetching a single ref with `--depth 1` works for branches, tags, and
+    /// (on servers that allow it) commit hashes alike.
+    async fn clone_repo(&self, source: &GitSource) -> Result<()> {
+        tokio::fs::create_dir_all(&self.path).await?;
+        info!(url = %source.url, path = %self.path.display(), "Cloning repository");
+
+        let reference = source.reference.as_deref().unwrap_or("HEAD");
+        let steps: [&[&str]; 3] = [
+            &["init", "-q"],
+            &["fetch", "-q", "--depth", "1", "--", &source.url, reference],
+            &["checkout", "-q", "FETCH_HEAD"],
+        ];
+        for args in steps {
+            let output = tokio::process::Command::new("git")
+                .arg("-C")
+                .arg(&self.path)
+                .args(args)
+                .stdin(std::process::Stdio::null())
+                .output()
+                .await
+                .map_err(|e| BashletError::GitClone {
+                    url: source.url.clone(),
+                    reason: format!("failed to run git: {}", e),
+                })?;
+            if !output.status.success() {
+                return Err(BashletError::GitClone {
+                    url: source.url.clone(),
+                    reason: String::from_utf8_lossy(&output.stderr).trim().to_string(),
+                });
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Mount the checkout as the working directory.
+    fn mount(&self, mounts: &mut Vec<Mount>, workdir: &mut String) {
+        mounts.push(Mount {
+            host_path: self.path.clone(),
+            guest_path: GIT_WORKDIR.to_string(),
+            readonly: false,
+            explicit_access: true,
+            options: Vec::new(),
+        });
+        *workdir = GIT_WORKDIR.to_string();
+    }
+
+    /// Leave the checkout in place for a saved session.
+    fn keep(mut self) {
+        self.keep = true;
+    }
+}
+
+impl Drop for GitCheckout {
+    fn drop(&mut self) {
+        // Never cloned, e.g. for --print-config
+        if self.keep || !self.path.exists() {
+            return;
+        }
+        if let Err(e) = std::fs::remove_dir_all(&self.path) {
+            warn!(path = %self.path.display(), error = %e, "Failed to remove git checkout");
+        }
+    }
+}
+
+/// Run preset setup commands, failing on the first non-zero exit.
+async fn run_setup_commands(backend: &dyn SandboxBackend, setup_commands: &[String]) -> Result<()> {
+    for cmd in setup_commands {
+        info!(command = %cmd, "Running setup command");
+        let result = backend.execute(cmd).await?;
+        debug!(
+            command = %cmd,
+            exit_code = result.exit_code,
+            stdout = %result.stdout,
+            stderr = %result.stderr,
+            "Setup command finished"
+        );
+
+        if result.exit_code != 0 {
+            // Tools like pip report some errors on stdout, so fall back to it
+            let output = if result.stderr.trim().is_empty() {
+                &result.stdout
+            } else {
+                &result.stderr
+            };
+            return Err(BashletError::SetupCommandFailed {
+                command: cmd.clone(),
+                exit_code: result.exit_code,
+                output: tail_lines(output, SETUP_OUTPUT_TAIL_LINES),
+            });
+        }
+    }
+    Ok(())
+}
+
+/// Teardown commands of a session's preset, if it still exists.
+fn preset_teardown_commands(config: &BashletConfig, preset_name: Option<&str>) -> Vec<String> {
+    preset_name
+        .and_then(|name| config.presets.get(name))
+        .map(|preset| preset.teardown_commands.clone())
+        .unwrap_or_default()
+}
+
+/// Run preset teardown commands. Failures are logged and don't stop the rest.
+async fn run_teardown_commands(backend: &dyn SandboxBackend, teardown_commands: &[String]) {
+    for cmd in teardown_commands {
+        info!(command = %cmd, "Running teardown command");
+        match backend.execute(cmd).await {
+            Ok(result) if result.exit_code == 0 => {}
+            Ok(result) => {
+                warn!(
+                    command = %cmd,
+                    exit_code = result.exit_code,
+                    stderr = %result.stderr,
+                    "Teardown command failed"
+                );
+            }
+            Err(e) => warn!(command = %cmd, error = %e, "Teardown command failed"),
+        }
+    }
+}
+
+/// Lines of a failed setup command's output kept in the error
+const SETUP_OUTPUT_TAIL_LINES: usize = 20;
+
+/// Last `n` lines of `text`.
+fn tail_lines(text: &str, n: usize) -> String {
+    let lines: Vec<&str> = text.trim_end().lines().collect();
+    lines[lines.len().saturating_sub(n)..].join("\n")
+}
+
+/// Handle a failed setup step.
+///
+/// With `keep_on_error` the session is saved and the sandbox is left running
+/// so it can be inspected; otherwise the sandbox is torn down.
+/// Returns the original error to propagate.
+async fn handle_setup_failure(
+    backend: &dyn SandboxBackend,
+    manager: &SessionManager,
+    session: &Session,
+    keep_on_error: bool,
+    error: BashletError,
+) -> BashletError {
+    if !keep_on_error {
+        if let Err(e) = destroy_backend(backend).await {
+            warn!(error = %e, "Failed to shut down sandbox after setup failure");
+        }
+        return error;
+    }
+
+    if let Err(e) = manager.save(session).await {
+        warn!(error = %e, "Failed to save session for debugging");
+        return error;
+    }
+    untrack_backend(backend);
+
+    let info = backend.info();
+    eprintln!(
+        "Setup failed; keeping {} sandbox for debugging as session '{}'",
+        info.backend_type,
+        session.display_id()
+    );
+    if let Some(instance_id) = info.instance_id {
+        eprintln!("  instance: {}", instance_id);
+    }
+    eprintln!("  inspect:  bashlet run {} \"sh\"", session.display_id());
+    eprintln!("  cleanup:  bashlet terminate {}", session.display_id());
+
+    error
+}
+
+/// Save the one-shot sandbox as a session, then run the command in it.
+///
+/// The session is saved first so it survives a failing command. `name_lock`
+/// is released once it is saved.
+#[allow(clippy::too_many_arguments)]
+async fn run_detached(
+    backend: &dyn SandboxBackend,
+    manager: &SessionManager,
+    session: &Session,
+    name_lock: Option<SessionLock>,
+    command: &str,
+    tty: bool,
+    stdin: Option<&[u8]>,
+    files: &OutputFiles,
+    mount_usage: &Option<MountUsage>,
+    json_out: Option<&Path>,
+    lines: OutputLines,
+    format: OutputFormat,
+) -> Result<()> {
+    manager.save(session).await?;
+    drop(name_lock);
+    untrack_backend(backend);
+
+    if tty {
+        let exit_code = backend.execute_interactive(command).await?;
+        MountUsage::warn_on_growth(mount_usage);
+        eprintln!("Session: {}", session.display_id());
+        exit_with_code(exit_code);
+        return Ok(());
+    }
+
+    let result = execute_with_input(backend, command, stdin, files).await?;
+    MountUsage::warn_on_growth(mount_usage);
+
+    if let OutputFormat::Text = format {
+        eprintln!("Session: {}", session.display_id());
+    }
+    let json = CommandResultJson {
+        session: Some(session.display_id()),
+        ..CommandResultJson::new(&result)
+    };
+    output_command_result(&json, format, json_out, lines)
+}
+
 // ============================================================================
 // Session Commands
 // ============================================================================
@@ -96,6 +599,12 @@ pub async fn create(args: CreateArgs, config: BashletConfig, format: OutputForma
 
     let manager = SessionManager::new();
 
+    // Held until the session is saved, so a racing create can't reuse the name
+    let _lock = match args.name {
+        Some(ref name) => Some(manager.lock(name).await?),
+        None => None,
+    };
+
     // Check if name already exists
     if let Some(ref name) = args.name {
         if manager.get(name).await.is_ok() {
@@ -119,12 +628,12 @@ pub async fn create(args: CreateArgs, config: BashletConfig, format: OutputForma
     }
 
     // Prepare mutable args for preset merging
-    let mut mounts = args.mounts.clone();
-    let mut env_vars = args.env_vars.clone();
+    let mut mounts = with_env_mounts(&args.mounts, config.sandbox.mounts_readonly_by_default)?;
+    let mut env_vars = resolve_env_vars(&args.env_vars);
     let mut workdir = args.workdir.clone();
 
     // Apply preset if specified
-    let setup_commands = if let Some(ref preset_name) = args.preset {
+    let mut setup_commands = if let Some(ref preset_name) = args.preset {
         apply_preset(
             preset_name,
             &config,
@@ -136,9 +645,33 @@ pub async fn create(args: CreateArgs, config: BashletConfig, format: OutputForma
     } else {
         vec![]
     };
+    if args.no_setup {
+        info!("Skipping preset setup commands (--no-setup)");
+        setup_commands.clear();
+    }
+    inherit_host_env(&args.inherit_env, args.inherit_env_force, &mut env_vars)?;
+    apply_image_overrides(&mut sandbox_config, &args.rootfs, &args.kernel);
+    apply_shell_override(&mut sandbox_config, &args.shell);
+    if let Some(policy) = args.pull_policy {
+        sandbox_config.docker.pull_policy = Some(policy);
+    }
+    sandbox_config
+        .docker
+        .extra_run_args
+        .extend(args.docker_args.iter().cloned());
+    check_mount_safety(
+        &mounts,
+        &sandbox_config.denied_mount_paths,
+        args.allow_unsafe_mount,
+    )?;
+
+    let checkout = args.git.as_ref().map(|_| GitCheckout::new());
+    if let Some(ref checkout) = checkout {
+        checkout.mount(&mut mounts, &mut workdir);
+    }
 
     // Create session
-    let session = Session::new(
+    let mut session = Session::new(
         args.name,
         mounts.clone(),
         env_vars.clone(),
@@ -147,33 +680,56 @@ pub async fn create(args: CreateArgs, config: BashletConfig, format: OutputForma
         ttl_seconds,
         args.preset.clone(),
     );
+    session.dns = args.dns.clone();
+    session.extra_hosts = args.extra_hosts.clone();
+    session.required_capabilities = args.require.capabilities();
+    session.rootfs_path = args.rootfs.clone();
+    session.kernel_path = args.kernel.clone();
+    session.git_checkout = checkout.as_ref().map(|c| c.path.clone());
+    session.shell = args.shell.clone();
 
     // Create the sandbox backend
     let runtime = RuntimeConfig {
         mounts,
         env_vars,
+        secret_env_vars: read_secret_env_file(&args.secret_env_file)?,
         workdir,
         memory_limit_mb: sandbox_config.memory_limit_mb,
         timeout_seconds: sandbox_config.timeout_seconds,
+        max_output_bytes: sandbox_config.max_output_bytes,
+        instance_id: None,
+        network: network_settings(&sandbox_config, &args.dns, &args.extra_hosts),
+        required: args.require.capabilities(),
     };
-    let backend = create_backend(&sandbox_config, runtime).await?;
+    if args.print_config {
+        return print_effective_config(&sandbox_config, &runtime, &setup_commands, format);
+    }
+    if let (Some(checkout), Some(source)) = (&checkout, &args.git) {
+        checkout.clone_repo(source).await?;
+    }
+    let backend = create_tracked_backend(&sandbox_config, runtime).await?;
+    session.instance_id = backend.persistent_instance_id();
 
     // Run setup commands
-    for cmd in &setup_commands {
-        info!(command = %cmd, "Running setup command");
-        let result = backend.execute(cmd).await?;
-        if result.exit_code != 0 {
-            return Err(BashletError::SandboxExecution(format!(
-                "Setup command failed: {}",
-                cmd
-            )));
+    if let Err(e) = run_setup_commands(backend.as_ref(), &setup_commands).await {
+        let error =
+            handle_setup_failure(backend.as_ref(), &manager, &session, args.keep_on_error, e).await;
+        if args.keep_on_error {
+            if let Some(checkout) = checkout {
+                checkout.keep();
+            }
         }
+        return Err(error);
     }
 
     // Save session
     let session_id = session.id.clone();
     let session_name = session.name.clone();
     manager.save(&session).await?;
+    untrack_backend(backend.as_ref());
+    if let Some(checkout) = checkout {
+        checkout.keep();
+    }
 
     match format {
         OutputFormat::Text => {
@@ -199,14 +755,58 @@ pub async fn create(args: CreateArgs, config: BashletConfig, format: OutputForma
 
 /// Execute a command in an existing session
 pub async fn run(args: SessionRunArgs, config: BashletConfig, format: OutputFormat) -> Result<()> {
-    info!(session = %args.session, command = %args.command, "Running command in session");
+    info!(session = %args.session, command = ?args.command, "Running command in session");
+
+    if args.tty {
+        require_terminal()?;
+    }
+    let stdin = if args.stdin {
+        Some(read_stdin().await?)
+    } else {
+        None
+    };
+    let files = output_files(&args.stdout_file, &args.stderr_file)?;
+
+    let script = args.script.as_deref().map(read_script).transpose()?;
+
+    // Reject before touching the session, and a script before any line runs
+    let filter = CommandFilter::from_config(&config.sandbox)?;
+    match script {
+        Some(ref script) => script
+            .iter()
+            .try_for_each(|(_, command)| filter.check(command))?,
+        None => filter.check(args.command.as_deref().unwrap_or_default())?,
+    }
 
     let manager = SessionManager::new();
 
-    // Try to get existing session, or create if --create flag is set
-    let (session, setup_commands) = match manager.get(&args.session).await {
-        Ok(session) => (session, vec![]),
-        Err(crate::error::BashletError::SessionNotFound { .. }) if args.create => {
+    // Serialize session creation and sandbox startup with concurrent commands.
+    // An existing session is locked by ID whether it was named by ID or name;
+    // only creating one by name, before it has an ID, locks the name.
+    let mut lock = None;
+    let mut existing = manager.lock_existing(&args.session).await;
+    if args.create && matches!(existing, Err(BashletError::SessionNotFound { .. })) {
+        let name_lock = manager.lock(&args.session).await?;
+        // Another command may have created it while we waited
+        existing = match manager.get(&args.session).await {
+            Ok(_) => {
+                drop(name_lock);
+                manager.lock_existing(&args.session).await
+            }
+            Err(e) => {
+                lock = Some(name_lock);
+                Err(e)
+            }
+        };
+    }
+
+    // Use the existing session, or create if --create flag is set
+    let (mut session, setup_commands) = match existing {
+        Ok((session, id_lock)) => {
+            lock = Some(id_lock);
+            (session, vec![])
+        }
+        Err(BashletError::SessionNotFound { .. }) if args.create => {
             info!(session = %args.session, "Session not found, creating new session");
 
             // Parse TTL if provided, otherwise use default from config
@@ -219,13 +819,14 @@ pub async fn run(args: SessionRunArgs, config: BashletConfig, format: OutputForm
             };
 
             // Prepare mutable args for preset merging
-            let mut mounts = args.mounts.clone();
-            let mut env_vars = args.env_vars.clone();
+            let mut mounts =
+                with_env_mounts(&args.mounts, config.sandbox.mounts_readonly_by_default)?;
+            let mut env_vars = resolve_env_vars(&args.env_vars);
             let mut workdir = args.workdir.clone();
             let mut sandbox_config = config.sandbox.clone();
 
             // Apply preset if specified
-            let setup_commands = if let Some(ref preset_name) = args.preset {
+            let mut setup_commands = if let Some(ref preset_name) = args.preset {
                 apply_preset(
                     preset_name,
                     &config,
@@ -237,9 +838,18 @@ pub async fn run(args: SessionRunArgs, config: BashletConfig, format: OutputForm
             } else {
                 vec![]
             };
+            if args.no_setup {
+                info!("Skipping preset setup commands (--no-setup)");
+                setup_commands.clear();
+            }
+            check_mount_safety(
+                &mounts,
+                &sandbox_config.denied_mount_paths,
+                args.allow_unsafe_mount,
+            )?;
 
             // Create session with the provided name
-            let session = Session::new(
+            let mut session = Session::new(
                 Some(args.session.clone()),
                 mounts,
                 env_vars,
@@ -248,149 +858,1076 @@ pub async fn run(args: SessionRunArgs, config: BashletConfig, format: OutputForm
                 ttl_seconds,
                 args.preset.clone(),
             );
+            session.dns = args.dns.clone();
+            session.extra_hosts = args.extra_hosts.clone();
+            session.shell = args.shell.clone();
 
-            manager.save(&session).await?;
+            if !args.print_config {
+                manager.save(&session).await?;
+            }
             (session, setup_commands)
         }
         Err(e) => return Err(e),
     };
 
+    if args.print_config {
+        let (sandbox_config, runtime) = session_runtime(&session, &config);
+        return print_effective_config(&sandbox_config, &runtime, &setup_commands, format);
+    }
+
     manager.touch(&args.session).await?;
 
-    // Build sandbox config from session
-    let mut sandbox_config = config.sandbox.clone();
-    if let Some(wasm_path) = &session.wasm_binary {
-        sandbox_config.wasmer.wasm_binary = Some(wasm_path.clone());
+    let mut backend = session_backend(&manager, &mut session, &config).await?;
+    if args.auto_restart && !backend.health_check().await.unwrap_or(false) {
+        warn!(session = %args.session, "Session sandbox is not responding, restarting it");
+        if let Err(e) = backend.shutdown().await {
+            warn!(error = %e, "Failed to shut down unresponsive sandbox");
+        }
+        backend = restart_session(&manager, &mut session, &config).await?;
     }
 
-    let runtime = RuntimeConfig {
-        mounts: session.get_mounts(),
-        env_vars: session.env_vars.clone(),
-        workdir: session.workdir.clone(),
-        memory_limit_mb: sandbox_config.memory_limit_mb,
-        timeout_seconds: sandbox_config.timeout_seconds,
-    };
-
-    let backend = create_backend(&sandbox_config, runtime).await?;
+    let mount_usage = MountUsage::record(
+        &session.get_mounts(),
+        args.mount_quota.or(config.sandbox.mount_quota_mb),
+    );
 
     // Run setup commands if this is a newly created session
-    for cmd in &setup_commands {
-        info!(command = %cmd, "Running setup command");
-        let result = backend.execute(cmd).await?;
-        if result.exit_code != 0 {
-            return Err(BashletError::SandboxExecution(format!(
-                "Setup command failed: {}",
-                cmd
-            )));
-        }
+    run_setup_commands(backend.as_ref(), &setup_commands).await?;
+    drop(lock);
+
+    if let Some(script) = script {
+        let exit_code = run_script(
+            backend.as_ref(),
+            &script,
+            args.continue_on_error,
+            args.json_out.as_deref(),
+            format,
+        )
+        .await?;
+        MountUsage::warn_on_growth(&mount_usage);
+        exit_with_code(exit_code);
+        return Ok(());
+    }
+
+    // Present unless --script was given
+    let command = args.command.unwrap_or_default();
+
+    if args.tty {
+        let exit_code = backend.execute_interactive(&command).await?;
+        MountUsage::warn_on_growth(&mount_usage);
+        exit_with_code(exit_code);
+        return Ok(());
     }
 
-    let result = backend.execute(&args.command).await?;
+    let result = execute_with_input(backend.as_ref(), &command, stdin.as_deref(), &files).await?;
+    MountUsage::warn_on_growth(&mount_usage);
 
-    output_command_result(&result, format);
+    output_command_result(
+        &CommandResultJson::new(&result),
+        format,
+        args.json_out.as_deref(),
+        OutputLines::new(args.head, args.tail, args.truncate_json),
+    )
+}
 
-    Ok(())
+/// Read a script file into (line number, command) pairs.
+fn read_script(path: &std::path::Path) -> Result<Vec<(usize, String)>> {
+    let content = std::fs::read_to_string(path).map_err(|e| {
+        BashletError::Config(format!("Failed to read script {}: {}", path.display(), e))
+    })?;
+
+    Ok(content
+        .lines()
+        .enumerate()
+        .map(|(i, line)| (i + 1, line.trim()))
+        .filter(|(_, line)| !line.is_empty() && !line.starts_with('#'))
+        .map(|(n, line)| (n, line.to_string()))
+        .collect())
 }
 
-/// Terminate a session
-pub async fn terminate(args: TerminateArgs, format: OutputFormat) -> Result<()> {
-    info!(session = %args.session, "Terminating session");
+/// Run script commands in order against one backend.
+///
+/// Stops at the first failure unless `continue_on_error` is set, and returns
+/// the exit code of the first failed command (0 if all succeeded).
+async fn run_script(
+    backend: &dyn SandboxBackend,
+    script: &[(usize, String)],
+    continue_on_error: bool,
+    json_out: Option<&Path>,
+    format: OutputFormat,
+) -> Result<i32> {
+    let mut results = Vec::new();
+    let mut failures = Vec::new();
+
+    for (line, command) in script {
+        let result = backend.execute(command).await?;
+
+        if let OutputFormat::Text = format {
+            print!("{}", result.stdout);
+            std::io::stdout().flush()?;
+            eprint!("{}", result.stderr);
+        }
 
-    let manager = SessionManager::new();
-    manager.delete(&args.session).await?;
+        let failed = result.exit_code != 0;
+        if failed {
+            failures.push((*line, command.as_str(), result.exit_code));
+        }
+        results.push((*line, command.as_str(), result));
+
+        if failed && !continue_on_error {
+            break;
+        }
+    }
+
+    let exit_code = failures.first().map_or(0, |(_, _, code)| *code);
+
+    let json = ScriptResultJson {
+        schema_version: SCHEMA_VERSION,
+        steps: results
+            .iter()
+            .map(|(line, command, result)| ScriptStepJson {
+                line: *line,
+                command,
+                result: CommandResultJson::new(result),
+            })
+            .collect(),
+        exit_code,
+    };
+    write_json_out(json_out, &json)?;
 
     match format {
         OutputFormat::Text => {
-            println!("Session '{}' terminated", args.session);
+            for (line, command, code) in &failures {
+                eprintln!("Line {} failed (exit {}): {}", line, code, command);
+            }
+            if results.len() < script.len() {
+                eprintln!(
+                    "Stopped after {} of {} commands (use --continue-on-error to run the rest)",
+                    results.len(),
+                    script.len()
+                );
+            }
         }
         OutputFormat::Json => {
-            println!(
-                "{}",
-                serde_json::json!({
-                    "terminated": args.session,
-                })
-            );
+            println!("{}", serde_json::to_string(&json)?);
         }
     }
 
-    Ok(())
+    Ok(exit_code)
 }
 
-/// Execute a one-shot command (create, run, terminate)
-pub async fn exec(args: ExecArgs, config: BashletConfig, format: OutputFormat) -> Result<()> {
-    info!(command = %args.command, "Executing one-shot command");
+/// Recreate the backend for an existing session.
+///
+/// Reattaches to the session's persistent container when it has one, and
+/// records a newly started container on the session.
+async fn session_backend(
+    manager: &SessionManager,
+    session: &mut Session,
+    config: &BashletConfig,
+) -> Result<Arc<dyn SandboxBackend>> {
+    let (sandbox_config, runtime) = session_runtime(session, config);
+    let backend: Arc<dyn SandboxBackend> =
+        Arc::from(create_backend(&sandbox_config, runtime).await?);
+
+    // Remember the container if it was started (or restarted) by this command
+    let instance_id = backend.persistent_instance_id();
+    if instance_id != session.instance_id || session.idle_stopped {
+        session.instance_id = instance_id;
+        session.idle_stopped = false;
+        session.touch();
+        manager.save(session).await?;
+    }
 
-    // Build sandbox config
-    let mut sandbox_config = config.sandbox.clone();
+    Ok(backend)
+}
 
-    // Override backend if specified
-    if let Some(backend) = args.backend.clone() {
-        sandbox_config.backend = backend;
+/// Replace a session's sandbox with a fresh one built from its stored mounts,
+/// env, and workdir.
+///
+/// A Docker session container or session VM is removed and a new one started
+/// in its place; other backends simply reconnect (e.g. SSH opens a new
+/// ControlMaster).
+async fn restart_session(
+    manager: &SessionManager,
+    session: &mut Session,
+    config: &BashletConfig,
+) -> Result<Arc<dyn SandboxBackend>> {
+    let (sandbox_config, mut runtime) = session_runtime(session, config);
+    match runtime.instance_id.take() {
+        Some(ref id) if cache::is_vm_instance(id) => cache::stop_instance(id)?,
+        Some(ref container_id) => DockerBackend::remove_container(container_id).await?,
+        None => {}
     }
 
-    // Handle legacy --wasm flag
-    if let Some(wasm_path) = args.wasm.clone() {
-        sandbox_config.wasmer.wasm_binary = Some(wasm_path);
+    let backend: Arc<dyn SandboxBackend> =
+        Arc::from(create_backend(&sandbox_config, runtime).await?);
+    if !backend.health_check().await? {
+        return Err(BashletError::SandboxInit(format!(
+            "restarted sandbox for session '{}' is not responding",
+            session.name.as_deref().unwrap_or(session.display_id())
+        )));
     }
 
-    // Prepare mutable args for preset merging
-    let mut mounts = args.mounts.clone();
-    let mut env_vars = args.env_vars.clone();
-    let mut workdir = args.workdir.clone();
+    session.instance_id = backend.persistent_instance_id();
+    session.idle_stopped = false;
+    session.touch();
+    manager.save(session).await?;
 
-    // Apply preset if specified
-    let setup_commands = if let Some(ref preset_name) = args.preset {
-        apply_preset(
-            preset_name,
-            &config,
-            &mut mounts,
-            &mut env_vars,
-            &mut workdir,
-            &mut sandbox_config,
-        )?
-    } else {
-        vec![]
-    };
+    Ok(backend)
+}
+
+/// Build the sandbox and runtime configuration of an existing session.
+fn session_runtime(session: &Session, config: &BashletConfig) -> (SandboxConfig, RuntimeConfig) {
+    let mut sandbox_config = config.sandbox.clone();
+    if let Some(wasm_path) = &session.wasm_binary {
+        sandbox_config.wasmer.wasm_binary = Some(wasm_path.clone());
+    }
+    apply_image_overrides(
+        &mut sandbox_config,
+        &session.rootfs_path,
+        &session.kernel_path,
+    );
+    apply_shell_override(&mut sandbox_config, &session.shell);
+
+    // Sessions with a live container or VM always go back to it
+    match session.instance_id {
+        Some(ref id) if cache::is_vm_instance(id) => {
+            sandbox_config.backend = BackendType::Firecracker;
+            sandbox_config.firecracker.session_mode = true;
+        }
+        Some(_) => {
+            sandbox_config.backend = BackendType::Docker;
+            sandbox_config.docker.session_mode = true;
+        }
+        None => {}
+    }
 
     let runtime = RuntimeConfig {
-        mounts,
-        env_vars,
-        workdir,
+        mounts: session.get_mounts(),
+        env_vars: session.env_vars.clone(),
+        secret_env_vars: Vec::new(),
+        workdir: session.workdir.clone(),
         memory_limit_mb: sandbox_config.memory_limit_mb,
         timeout_seconds: sandbox_config.timeout_seconds,
+        max_output_bytes: sandbox_config.max_output_bytes,
+        instance_id: session.instance_id.clone(),
+        network: network_settings(&sandbox_config, &session.dns, &session.extra_hosts),
+        required: session.required_capabilities.clone(),
     };
 
-    let backend = create_backend(&sandbox_config, runtime).await?;
+    (sandbox_config, runtime)
+}
 
-    // Run setup commands
-    for cmd in &setup_commands {
-        info!(command = %cmd, "Running setup command");
-        let result = backend.execute(cmd).await?;
-        if result.exit_code != 0 {
-            return Err(BashletError::SandboxExecution(format!(
-                "Setup command failed: {}",
-                cmd
-            )));
-        }
+/// Print the configuration a command would run with, for `--print-config`.
+fn print_effective_config(
+    sandbox_config: &SandboxConfig,
+    runtime: &RuntimeConfig,
+    setup_commands: &[String],
+    format: OutputFormat,
+) -> Result<()> {
+    let effective = EffectiveConfigJson::new(sandbox_config, runtime, setup_commands);
+    match format {
+        OutputFormat::Text => println!("{}", serde_json::to_string_pretty(&effective)?),
+        OutputFormat::Json => println!("{}", serde_json::to_string(&effective)?),
     }
-
-    let result = backend.execute(&args.command).await?;
-
-    output_command_result(&result, format);
-
     Ok(())
 }
 
-/// List all active sessions
-pub async fn list(args: ListArgs, format: OutputFormat) -> Result<()> {
+/// Stop the containers of sessions idle for longer than `sandbox.idle_shutdown`
+pub async fn reap(config: BashletConfig, format: OutputFormat) -> Result<()> {
     let manager = SessionManager::new();
 
-    // Cleanup expired sessions first (unless --all)
-    if !args.all {
+    if config.sandbox.idle_shutdown.is_none() {
+        if let OutputFormat::Text = format {
+            eprintln!("sandbox.idle_shutdown is not set; no containers to stop");
+        }
+    }
+    let stopped = stop_idle_sessions(&manager, &config).await?;
+
+    match format {
+        OutputFormat::Text => {
+            for session in &stopped {
+                println!("Stopped idle session '{}'", session);
+            }
+        }
+        OutputFormat::Json => {
+            println!("{}", serde_json::json!({ "stopped": stopped }));
+        }
+    }
+    Ok(())
+}
+
+/// Stop the containers of sessions idle for longer than `sandbox.idle_shutdown`,
+/// returning the names (or IDs) of the sessions stopped.
+///
+/// Sessions are kept, and their next command starts the container again.
+/// Sessions another command is using are skipped.
+async fn stop_idle_sessions(
+    manager: &SessionManager,
+    config: &BashletConfig,
+) -> Result<Vec<String>> {
+    let mut stopped = Vec::new();
+    let Some(ref idle_shutdown) = config.sandbox.idle_shutdown else {
+        return Ok(stopped);
+    };
+    let idle_seconds = parse_ttl(idle_shutdown)?;
+
+    for session in manager.list().await? {
+        // A stopped VM can't be resumed with its state, so session VMs keep running
+        let Some(ref instance_id) = session.instance_id else {
+            continue;
+        };
+        if session.idle_stopped || cache::is_vm_instance(instance_id) {
+            continue;
+        }
+
+        let Some(_lock) = manager.try_lock(&session.id).await? else {
+            continue;
+        };
+
+        // Re-read under the lock, in case a command ran since listing
+        let Ok(mut session) = manager.get(&session.id).await else {
+            continue;
+        };
+        let Some(ref container_id) = session.instance_id else {
+            continue;
+        };
+        if unix_now().saturating_sub(session.last_activity) < idle_seconds {
+            continue;
+        }
+
+        match DockerBackend::stop_idle_container(container_id).await {
+            Ok(()) => {
+                session.idle_stopped = true;
+                match manager.save(&session).await {
+                    Ok(()) => stopped.push(session.name.clone().unwrap_or(session.id)),
+                    Err(e) => {
+                        warn!(session = %session.id, error = %e, "Failed to save idle session")
+                    }
+                }
+            }
+            Err(e) => warn!(session = %session.id, error = %e, "Failed to stop idle session"),
+        }
+    }
+
+    Ok(stopped)
+}
+
+/// Interactive loop that runs each line of input in a session
+pub async fn shell(args: ShellArgs, config: BashletConfig) -> Result<()> {
+    let manager = SessionManager::new();
+    let mut session = manager.get(&args.session).await?;
+    let backend = session_backend(&manager, &mut session, &config).await?;
+    let filter = CommandFilter::from_config(&config.sandbox)?;
+
+    let interactive = std::io::stdin().is_terminal();
+    let prompt = format!(
+        "{}> ",
+        session.name.as_deref().unwrap_or(session.display_id())
+    );
+    let mut lines = BufReader::new(tokio::io::stdin()).lines();
+
+    loop {
+        if interactive {
+            eprint!("{}", prompt);
+            std::io::stderr().flush()?;
+        }
+
+        let Some(line) = lines.next_line().await? else {
+            break;
+        };
+        let command = line.trim();
+        if command.is_empty() {
+            continue;
+        }
+        if command == "exit" {
+            break;
+        }
+
+        if let Err(e) = filter.check(command) {
+            eprintln!("Error: {}", e);
+            continue;
+        }
+
+        manager.touch(&session.id).await?;
+
+        match backend.execute(command).await {
+            Ok(result) => {
+                print!("{}", result.stdout);
+                std::io::stdout().flush()?;
+                eprint!("{}", result.stderr);
+                if result.exit_code != 0 && interactive {
+                    eprintln!("[exit {}]", result.exit_code);
+                }
+            }
+            Err(e) => eprintln!("Error: {}", e),
+        }
+    }
+
+    Ok(())
+}
+
+/// Remove a file or directory inside a session
+pub async fn rm(args: RmArgs, config: BashletConfig, format: OutputFormat) -> Result<()> {
+    if !args.force && is_protected_path(&args.path) {
+        return Err(BashletError::Config(format!(
+            "Refusing to remove '{}'; pass --force if you really mean it",
+            args.path
+        )));
+    }
+
+    let manager = SessionManager::new();
+    let mut session = manager.get(&args.session).await?;
+    manager.touch(&session.id).await?;
+
+    let backend = session_backend(&manager, &mut session, &config).await?;
+    backend.remove(&args.path, args.recursive).await?;
+
+    if let OutputFormat::Json = format {
+        println!(
+            "{}",
+            serde_json::json!({
+                "session": args.session,
+                "removed": args.path,
+            })
+        );
+    }
+
+    Ok(())
+}
+
+/// Paths too broad to remove without --force: the root, relative
+/// shorthands, and top-level directories like /usr or /workspace.
+fn is_protected_path(path: &str) -> bool {
+    let trimmed = path.trim().trim_end_matches('/');
+    if matches!(trimmed, "" | "." | ".." | "~" | "*") {
+        return true;
+    }
+    trimmed
+        .strip_prefix('/')
+        .is_some_and(|rest| !rest.contains('/'))
+}
+
+/// Terminate one or more sessions
+pub async fn terminate(
+    args: TerminateArgs,
+    config: BashletConfig,
+    format: OutputFormat,
+) -> Result<()> {
+    let manager = SessionManager::new();
+    let mut targets = args.sessions.clone();
+
+    if let Some(ref pattern) = args.pattern {
+        let glob = glob::Pattern::new(pattern)
+            .map_err(|e| BashletError::Config(format!("Invalid pattern '{}': {}", pattern, e)))?;
+
+        // A literal name or ID always matches, even if it contains glob characters
+        let matched: Vec<String> = manager
+            .list()
+            .await?
+            .into_iter()
+            .filter(|s| {
+                s.id == *pattern
+                    || glob.matches(&s.id)
+                    || s.name
+                        .as_deref()
+                        .is_some_and(|n| n == pattern || glob.matches(n))
+            })
+            .filter(|s| {
+                !targets
+                    .iter()
+                    .any(|t| *t == s.id || s.name.as_deref() == Some(t))
+            })
+            .map(|s| s.name.unwrap_or(s.id))
+            .collect();
+
+        if matched.is_empty() {
+            eprintln!("No sessions match '{}'", pattern);
+        } else if !args.yes
+            && !confirm(&format!(
+                "Terminate {} session(s) matching '{}'?",
+                matched.len(),
+                pattern
+            ))?
+        {
+            eprintln!("Aborted");
+            return Ok(());
+        }
+        targets.extend(matched);
+    }
+
+    let mut terminated = Vec::new();
+    let mut failures = Vec::new();
+    for target in targets {
+        info!(session = %target, "Terminating session");
+        match terminate_session(&manager, &config, &target).await {
+            Ok(()) => {
+                if let OutputFormat::Text = format {
+                    println!("Session '{}' terminated", target);
+                }
+                terminated.push(target);
+            }
+            Err(e) => {
+                if let OutputFormat::Text = format {
+                    eprintln!("Failed to terminate '{}': {}", target, e);
+                }
+                failures.push((target, e));
+            }
+        }
+    }
+
+    if let OutputFormat::Json = format {
+        let failed: Vec<_> = failures
+            .iter()
+            .map(|(session, e)| serde_json::json!({ "session": session, "error": e.to_string() }))
+            .collect();
+        println!(
+            "{}",
+            serde_json::json!({
+                "terminated": terminated,
+                "failed": failed,
+            })
+        );
+    }
+
+    // A single failed target reports its own error; several are summarized
+    match failures.len() {
+        0 => Ok(()),
+        1 if terminated.is_empty() => Err(failures.remove(0).1),
+        n => Err(anyhow::anyhow!("{} session(s) failed to terminate", n).into()),
+    }
+}
+
+/// Replace a session's sandbox, e.g. after its container was killed externally
+pub async fn restart(args: RestartArgs, config: BashletConfig, format: OutputFormat) -> Result<()> {
+    info!(session = %args.session, "Restarting session");
+
+    let manager = SessionManager::new();
+    let (mut session, _lock) = manager.lock_existing(&args.session).await?;
+    let previous = session.instance_id.clone();
+
+    restart_session(&manager, &mut session, &config).await?;
+
+    match format {
+        OutputFormat::Text => match session.instance_id {
+            Some(ref container_id) => println!(
+                "Session '{}' restarted (container {})",
+                args.session,
+                &container_id[..container_id.len().min(12)]
+            ),
+            None => println!("Session '{}' restarted", args.session),
+        },
+        OutputFormat::Json => {
+            println!(
+                "{}",
+                serde_json::json!({
+                    "session": args.session,
+                    "instance_id": session.instance_id,
+                    "previous_instance_id": previous,
+                })
+            );
+        }
+    }
+
+    Ok(())
+}
+
+/// Terminate a single session, removing its persistent container if it has one.
+///
+/// Preset teardown commands run first, in a backend rebuilt from the session.
+async fn terminate_session(
+    manager: &SessionManager,
+    config: &BashletConfig,
+    id_or_name: &str,
+) -> Result<()> {
+    // Held through the delete, so a concurrent run can't save the session back
+    let (mut session, _lock) = manager.lock_existing(id_or_name).await?;
+
+    let teardown_commands = preset_teardown_commands(config, session.preset.as_deref());
+    if !teardown_commands.is_empty() {
+        match session_backend(manager, &mut session, config).await {
+            Ok(backend) => {
+                run_teardown_commands(backend.as_ref(), &teardown_commands).await;
+                if let Err(e) = backend.shutdown().await {
+                    warn!(error = %e, "Failed to shut down sandbox");
+                }
+            }
+            Err(e) => warn!(error = %e, "Skipping teardown commands, sandbox unavailable"),
+        }
+    }
+
+    match session.instance_id {
+        Some(ref id) if cache::is_vm_instance(id) => cache::stop_instance(id)?,
+        Some(ref container_id) => DockerBackend::remove_container(container_id).await?,
+        None => {}
+    }
+    manager.delete(&session.id).await
+}
+
+/// Free space on the host filesystems behind writable mounts, recorded
+/// before a command so growth past the --mount-quota threshold can be reported.
+///
+/// This is a soft guardrail: concurrent host activity counts too, and nothing
+/// stops the command from writing.
+struct MountUsage {
+    threshold_bytes: u64,
+    baseline: Vec<(PathBuf, u64)>,
+}
+
+impl MountUsage {
+    /// Record free space for each writable mount, or None without a quota.
+    fn record(mounts: &[Mount], quota_mb: Option<u64>) -> Option<Self> {
+        let threshold_bytes = quota_mb? * 1024 * 1024;
+        let baseline = mounts
+            .iter()
+            .filter(|m| !m.readonly)
+            .filter_map(|m| {
+                fs2::available_space(&m.host_path)
+                    .ok()
+                    .map(|free| (m.host_path.clone(), free))
+            })
+            .collect();
+
+        Some(Self {
+            threshold_bytes,
+            baseline,
+        })
+    }
+
+    /// Warn about every mount whose filesystem lost more than the threshold.
+    fn warn_on_growth(usage: &Option<Self>) {
+        let Some(usage) = usage else {
+            return;
+        };
+
+        for (path, before) in &usage.baseline {
+            let Ok(after) = fs2::available_space(path) else {
+                continue;
+            };
+            let used = before.saturating_sub(after);
+            if used > usage.threshold_bytes {
+                warn!(
+                    mount = %path.display(),
+                    used = %format_bytes(used),
+                    quota = %format_bytes(usage.threshold_bytes),
+                    "Writable mount's host filesystem grew past the mount quota"
+                );
+            }
+        }
+    }
+}
+
+/// Read all of this process's stdin, for --stdin.
+async fn read_stdin() -> Result<Vec<u8>> {
+    let mut input = Vec::new();
+    tokio::io::stdin().read_to_end(&mut input).await?;
+    Ok(input)
+}
+
+/// Execute a command, piping `stdin` to it when given and sending its output
+/// to `files`.
+async fn execute_with_input(
+    backend: &dyn SandboxBackend,
+    command: &str,
+    stdin: Option<&[u8]>,
+    files: &OutputFiles,
+) -> Result<CommandResult> {
+    if !files.is_empty() {
+        return backend.execute_to_files(command, stdin, files).await;
+    }
+    match stdin {
+        Some(input) => backend.execute_with_stdin(command, input).await,
+        None => backend.execute(command).await,
+    }
+}
+
+/// Check the --stdout-file/--stderr-file paths can be written before
+/// starting a sandbox, creating the files.
+fn output_files(stdout: &Option<PathBuf>, stderr: &Option<PathBuf>) -> Result<OutputFiles> {
+    for path in [stdout, stderr].into_iter().flatten() {
+        std::fs::File::create(path).map_err(|e| {
+            BashletError::Config(format!(
+                "Cannot write output file {}: {}",
+                path.display(),
+                e
+            ))
+        })?;
+    }
+    Ok(OutputFiles {
+        stdout: stdout.clone(),
+        stderr: stderr.clone(),
+    })
+}
+
+/// Fail early when --tty is requested without a terminal to attach to.
+fn require_terminal() -> Result<()> {
+    if std::io::stdin().is_terminal() {
+        Ok(())
+    } else {
+        Err(BashletError::Unsupported {
+            operation: "tty".to_string(),
+            reason: "stdin is not a terminal".to_string(),
+        })
+    }
+}
+
+/// Exit with the sandboxed command's status if it failed.
+fn exit_with_code(exit_code: i32) {
+    if exit_code != 0 {
+        std::process::exit(exit_code);
+    }
+}
+
+/// Ask a yes/no question on the terminal, defaulting to no.
+fn confirm(prompt: &str) -> Result<bool> {
+    eprint!("{} [y/N] ", prompt);
+    std::io::stderr().flush()?;
+
+    let mut answer = String::new();
+    std::io::stdin().read_line(&mut answer)?;
+    Ok(matches!(answer.trim().to_lowercase().as_str(), "y" | "yes"))
+}
+
+/// Commit a session's Docker container as a new image
+pub async fn commit(args: CommitArgs, format: OutputFormat) -> Result<()> {
+    info!(session = %args.session, image = %args.image, "Committing session");
+
+    let manager = SessionManager::new();
+    let session = manager.get(&args.session).await?;
+
+    let container_id = session
+        .instance_id
+        .as_deref()
+        .filter(|id| !cache::is_vm_instance(id))
+        .ok_or_else(|| BashletError::Unsupported {
+            operation: "commit".to_string(),
+            reason: format!(
+                "session '{}' has no persistent container; commit requires the docker backend with sandbox.docker.session_mode = true",
+                args.session
+            ),
+        })?;
+
+    let image_id = DockerBackend::commit_container(container_id, &args.image).await?;
+
+    match format {
+        OutputFormat::Text => {
+            println!("{}", args.image);
+        }
+        OutputFormat::Json => {
+            println!(
+                "{}",
+                serde_json::json!({
+                    "session": args.session,
+                    "image": args.image,
+                    "image_id": image_id,
+                })
+            );
+        }
+    }
+
+    Ok(())
+}
+
+/// Show logs from a session's Docker container
+pub async fn logs(args: LogsArgs) -> Result<()> {
+    let manager = SessionManager::new();
+    let session = manager.get(&args.session).await?;
+
+    // Session containers keep docker logs and session VMs a console log;
+    // Wasmer runs leave nothing behind to read.
+    let instance_id = session
+        .instance_id
+        .as_deref()
+        .ok_or_else(|| BashletError::Unsupported {
+            operation: "logs".to_string(),
+            reason: format!(
+                "session '{}' has no persistent sandbox; logs are only available for sessions with session_mode = true",
+                args.session
+            ),
+        })?;
+
+    if cache::is_vm_instance(instance_id) {
+        stream_vm_console(instance_id, args.follow).await
+    } else {
+        DockerBackend::stream_logs(instance_id, args.follow).await
+    }
+}
+
+/// Print a session VM's console log, then with `follow` keep printing what
+/// the VM appends until it stops.
+async fn stream_vm_console(instance_id: &str, follow: bool) -> Result<()> {
+    let path = cache::instance_log_path(instance_id);
+    let mut file = tokio::fs::File::open(&path).await.map_err(|e| {
+        BashletError::SandboxExecution(format!(
+            "Failed to read VM console log {}: {}",
+            path.display(),
+            e
+        ))
+    })?;
+
+    let mut stdout = std::io::stdout();
+    let mut buf = vec![0u8; 8192];
+    loop {
+        let n = file.read(&mut buf).await?;
+        if n > 0 {
+            stdout.write_all(&buf[..n])?;
+            continue;
+        }
+        stdout.flush()?;
+        if !follow || !cache::instance_running(instance_id) {
+            return Ok(());
+        }
+        tokio::time::sleep(Duration::from_millis(250)).await;
+    }
+}
+
+/// Execute a one-shot command (create, run, terminate)
+pub async fn exec(args: ExecArgs, config: BashletConfig, format: OutputFormat) -> Result<()> {
+    // An argv runs as its quoted equivalent wherever a command string is needed
+    let command = match args.command {
+        Some(ref command) => command.clone(),
+        None => shell_join(&args.argv),
+    };
+    info!(command = %command, "Executing one-shot command");
+    CommandFilter::from_config(&config.sandbox)?.check(&command)?;
+
+    if args.tty {
+        require_terminal()?;
+    }
+    let stdin = if args.stdin {
+        Some(read_stdin().await?)
+    } else {
+        None
+    };
+    let files = output_files(&args.stdout_file, &args.stderr_file)?;
+
+    let manager = SessionManager::new();
+    // Held until the detached session is saved, so a racing create can't reuse the name
+    let name_lock = match args.name {
+        Some(ref name) => Some(manager.lock(name).await?),
+        None => None,
+    };
+    if let Some(ref name) = args.name {
+        if manager.get(name).await.is_ok() {
+            return Err(BashletError::SessionNameExists { name: name.clone() });
+        }
+    }
+    let ttl_seconds = match &config.sandbox.default_idle_timeout {
+        Some(default_ttl) => Some(parse_ttl(default_ttl)?),
+        None => None,
+    };
+
+    // Build sandbox config
+    let mut sandbox_config = config.sandbox.clone();
+
+    // Override backend if specified
+    if let Some(backend) = args.backend.clone() {
+        sandbox_config.backend = backend;
+    }
+
+    // Handle legacy --wasm flag
+    if let Some(wasm_path) = args.wasm.clone() {
+        sandbox_config.wasmer.wasm_binary = Some(wasm_path);
+    }
+
+    // Prepare mutable args for preset merging
+    let mut mounts = with_env_mounts(&args.mounts, config.sandbox.mounts_readonly_by_default)?;
+    let mut env_vars = resolve_env_vars(&args.env_vars);
+    let mut workdir = args.workdir.clone();
+
+    // Apply preset if specified
+    let mut setup_commands = if let Some(ref preset_name) = args.preset {
+        apply_preset(
+            preset_name,
+            &config,
+            &mut mounts,
+            &mut env_vars,
+            &mut workdir,
+            &mut sandbox_config,
+        )?
+    } else {
+        vec![]
+    };
+    if args.no_setup {
+        info!("Skipping preset setup commands (--no-setup)");
+        setup_commands.clear();
+    }
+    inherit_host_env(&args.inherit_env, args.inherit_env_force, &mut env_vars)?;
+    apply_image_overrides(&mut sandbox_config, &args.rootfs, &args.kernel);
+    apply_shell_override(&mut sandbox_config, &args.shell);
+    if let Some(policy) = args.pull_policy {
+        sandbox_config.docker.pull_policy = Some(policy);
+    }
+    sandbox_config
+        .docker
+        .extra_run_args
+        .extend(args.docker_args.iter().cloned());
+
+    // Mount the current directory and work from it
+    if let Some(ref guest_path) = args.cwd_mount {
+        mounts.push(Mount {
+            host_path: std::env::current_dir()?,
+            guest_path: guest_path.clone(),
+            readonly: false,
+            explicit_access: true,
+            options: Vec::new(),
+        });
+        workdir = guest_path.clone();
+    }
+    check_mount_safety(
+        &mounts,
+        &sandbox_config.denied_mount_paths,
+        args.allow_unsafe_mount,
+    )?;
+
+    // Removed when this returns, unless the sandbox is kept as a session
+    let checkout = args.git.as_ref().map(|_| GitCheckout::new());
+    if let Some(ref checkout) = checkout {
+        checkout.mount(&mut mounts, &mut workdir);
+    }
+
+    let runtime = RuntimeConfig {
+        mounts: mounts.clone(),
+        env_vars: env_vars.clone(),
+        secret_env_vars: read_secret_env_file(&args.secret_env_file)?,
+        workdir: workdir.clone(),
+        memory_limit_mb: sandbox_config.memory_limit_mb,
+        timeout_seconds: sandbox_config.timeout_seconds,
+        max_output_bytes: sandbox_config.max_output_bytes,
+        instance_id: None,
+        network: network_settings(&sandbox_config, &args.dns, &args.extra_hosts),
+        required: args.require.capabilities(),
+    };
+    if args.print_config {
+        return print_effective_config(&sandbox_config, &runtime, &setup_commands, format);
+    }
+    if let (Some(checkout), Some(source)) = (&checkout, &args.git) {
+        checkout.clone_repo(source).await?;
+    }
+
+    let backend = create_tracked_backend(&sandbox_config, runtime).await?;
+
+    let mount_usage =
+        MountUsage::record(&mounts, args.mount_quota.or(sandbox_config.mount_quota_mb));
+
+    // Only saved if the sandbox is kept (--detach or --keep-on-error)
+    let mut session = Session::new(
+        args.name.clone(),
+        mounts,
+        env_vars,
+        workdir,
+        sandbox_config.wasmer.wasm_binary.clone(),
+        ttl_seconds,
+        args.preset.clone(),
+    );
+    session.instance_id = backend.persistent_instance_id();
+    session.dns = args.dns.clone();
+    session.extra_hosts = args.extra_hosts.clone();
+    session.required_capabilities = args.require.capabilities();
+    session.rootfs_path = args.rootfs.clone();
+    session.kernel_path = args.kernel.clone();
+    session.git_checkout = checkout.as_ref().map(|c| c.path.clone());
+    session.shell = args.shell.clone();
+
+    // Run setup commands
+    if let Err(e) = run_setup_commands(backend.as_ref(), &setup_commands).await {
+        let error =
+            handle_setup_failure(backend.as_ref(), &manager, &session, args.keep_on_error, e).await;
+        if args.keep_on_error {
+            if let Some(checkout) = checkout {
+                checkout.keep();
+            }
+        }
+        return Err(error);
+    }
+
+    if args.detach {
+        if let Some(checkout) = checkout {
+            checkout.keep();
+        }
+        return run_detached(
+            backend.as_ref(),
+            &manager,
+            &session,
+            name_lock,
+            &command,
+            args.tty,
+            stdin.as_deref(),
+            &files,
+            &mount_usage,
+            args.json_out.as_deref(),
+            OutputLines::new(args.head, args.tail, args.truncate_json),
+            format,
+        )
+        .await;
+    }
+
+    let teardown_commands = preset_teardown_commands(&config, args.preset.as_deref());
+
+    if args.tty {
+        let exit_code = backend.execute_interactive(&command).await;
+        MountUsage::warn_on_growth(&mount_usage);
+        run_teardown_commands(backend.as_ref(), &teardown_commands).await;
+        if let Err(e) = destroy_backend(backend.as_ref()).await {
+            warn!(error = %e, "Failed to shut down sandbox");
+        }
+        // exit_with_code skips destructors
+        drop(checkout);
+        exit_with_code(exit_code?);
+        return Ok(());
+    }
+
+    let result = if args.argv.is_empty() {
+        execute_with_input(backend.as_ref(), &command, stdin.as_deref(), &files).await
+    } else {
+        backend
+            .execute_argv(&args.argv)
+            .await
+            .and_then(|mut result| {
+                files.write_result(&mut result)?;
+                Ok(result)
+            })
+    };
+    MountUsage::warn_on_growth(&mount_usage);
+    run_teardown_commands(backend.as_ref(), &teardown_commands).await;
+
+    // One-shot: tear the sandbox down before reporting the result
+    if let Err(e) = destroy_backend(backend.as_ref()).await {
+        warn!(error = %e, "Failed to shut down sandbox");
+    }
+    // output_command_result exits on a failing command, skipping destructors
+    drop(checkout);
+
+    output_command_result(
+        &CommandResultJson::new(&result?),
+        format,
+        args.json_out.as_deref(),
+        OutputLines::new(args.head, args.tail, args.truncate_json),
+    )
+}
+
+/// List all active sessions
+pub async fn list(args: ListArgs, format: OutputFormat) -> Result<()> {
+    let manager = SessionManager::new();
+
+    // Cleanup expired sessions first (unless --all)
+    if !args.all {
         manager.cleanup_expired().await?;
     }
 
-    let sessions = manager.list().await?;
+    let since = args.since.as_deref().map(parse_ttl).transpose()?;
+    let until = args.until.as_deref().map(parse_ttl).transpose()?;
+    let now = unix_now();
+
+    let sessions: Vec<Session> = manager
+        .list()
+        .await?
+        .into_iter()
+        .filter(|s| {
+            let age = now.saturating_sub(s.created_at);
+            since.is_none_or(|max| age <= max) && until.is_none_or(|min| age >= min)
+        })
+        .collect();
 
     match format {
         OutputFormat::Text => {
@@ -398,14 +1935,22 @@ pub async fn list(args: ListArgs, format: OutputFormat) -> Result<()> {
                 println!("No active sessions");
             } else {
                 println!(
-                    "{:<12} {:<16} {:<20} {:<10} MOUNTS",
-                    "ID", "NAME", "CREATED", "TTL"
+                    "{:<12} {:<16} {:<12} {:<20} {:<10} MOUNTS",
+                    "ID", "NAME", "PRESET", "CREATED", "TTL"
                 );
-                println!("{}", "-".repeat(70));
+                println!("{}", "-".repeat(83));
 
                 for session in sessions {
                     let name = session.name.as_deref().unwrap_or("-");
-                    let created = format_timestamp(session.created_at);
+                    let preset = session.preset.as_deref().unwrap_or("-");
+                    let created = if args.relative {
+                        format!(
+                            "{} ago",
+                            format_duration(now.saturating_sub(session.created_at))
+                        )
+                    } else {
+                        format_timestamp(session.created_at)
+                    };
                     let ttl = session
                         .ttl_seconds
                         .map(format_duration)
@@ -424,30 +1969,17 @@ pub async fn list(args: ListArgs, format: OutputFormat) -> Result<()> {
                     };
 
                     println!(
-                        "{:<12} {:<16} {:<20} {:<10} {}{}",
-                        session.id, name, created, ttl, mounts, expired_marker
+                        "{:<12} {:<16} {:<12} {:<20} {:<10} {}{}",
+                        session.id, name, preset, created, ttl, mounts, expired_marker
                     );
                 }
             }
         }
         OutputFormat::Json => {
-            let json_sessions: Vec<_> = sessions
-                .iter()
-                .map(|s| {
-                    serde_json::json!({
-                        "id": s.id,
-                        "name": s.name,
-                        "created_at": s.created_at,
-                        "last_activity": s.last_activity,
-                        "ttl_seconds": s.ttl_seconds,
-                        "expired": s.is_expired(),
-                        "mounts": s.mounts,
-                        "workdir": s.workdir,
-                    })
-                })
-                .collect();
-
-            println!("{}", serde_json::to_string_pretty(&json_sessions)?);
+            println!(
+                "{}",
+                serde_json::to_string_pretty(&SessionListJson::new(&sessions))?
+            );
         }
     }
 
@@ -458,6 +1990,30 @@ pub async fn list(args: ListArgs, format: OutputFormat) -> Result<()> {
 // Config Commands
 // ============================================================================
 
+/// Starter presets written by `init --with-presets`.
+fn example_presets() -> HashMap<String, PresetConfig> {
+    HashMap::from([
+        (
+            "python".to_string(),
+            PresetConfig {
+                env_vars: vec![("PYTHONUNBUFFERED".to_string(), "1".to_string())],
+                workdir: Some("/workspace".to_string()),
+                setup_commands: vec!["python3 --version".to_string()],
+                ..Default::default()
+            },
+        ),
+        (
+            "node".to_string(),
+            PresetConfig {
+                env_vars: vec![("NODE_ENV".to_string(), "development".to_string())],
+                workdir: Some("/workspace".to_string()),
+                setup_commands: vec!["node --version".to_string()],
+                ..Default::default()
+            },
+        ),
+    ])
+}
+
 pub async fn init(args: InitArgs) -> Result<()> {
     let config_path = get_config_path();
 
@@ -473,9 +2029,23 @@ pub async fn init(args: InitArgs) -> Result<()> {
     }
 
     // Write default configuration
-    let default_config = BashletConfig::default();
-    let toml_str = toml::to_string_pretty(&default_config)
-        .map_err(|e| crate::error::BashletError::Config(e.to_string()))?;
+    let mut default_config = BashletConfig::default();
+    if args.with_presets {
+        default_config.presets = example_presets();
+    }
+
+    let toml_str = if args.minimal {
+        #[derive(serde::Serialize)]
+        struct MinimalConfig<'a> {
+            sandbox: &'a SandboxConfig,
+        }
+        toml::to_string_pretty(&MinimalConfig {
+            sandbox: &default_config.sandbox,
+        })
+    } else {
+        toml::to_string_pretty(&default_config)
+    }
+    .map_err(|e| crate::error::BashletError::Config(e.to_string()))?;
 
     std::fs::write(&config_path, toml_str)?;
 
@@ -506,39 +2076,769 @@ pub async fn config(args: ConfigArgs, config: BashletConfig) -> Result<()> {
         ConfigAction::Path => {
             println!("{}", get_config_path().display());
         }
+        ConfigAction::Edit => edit_config()?,
+    }
+    Ok(())
+}
+
+pub async fn presets(args: PresetsArgs, config: BashletConfig, format: OutputFormat) -> Result<()> {
+    match args.action {
+        PresetsAction::List => {
+            let mut names: Vec<&String> = config.presets.keys().collect();
+            names.sort();
+
+            match format {
+                OutputFormat::Text => {
+                    if names.is_empty() {
+                        println!("No presets defined in {}", get_config_path().display());
+                        return Ok(());
+                    }
+                    println!(
+                        "{:<20} {:<12} {:>6} {:>6}",
+                        "NAME", "BACKEND", "MOUNTS", "ENV"
+                    );
+                    for name in names {
+                        let preset = &config.presets[name];
+                        let backend = preset
+                            .backend
+                            .as_ref()
+                            .map_or_else(|| "-".to_string(), |b| format!("{:?}", b).to_lowercase());
+                        println!(
+                            "{:<20} {:<12} {:>6} {:>6}",
+                            name,
+                            backend,
+                            preset.mounts.len(),
+                            preset.env_vars.len()
+                        );
+                    }
+                }
+                OutputFormat::Json => {
+                    let output = PresetListJson {
+                        schema_version: SCHEMA_VERSION,
+                        presets: names
+                            .into_iter()
+                            .map(|name| PresetSummaryJson::new(name, &config.presets[name]))
+                            .collect(),
+                    };
+                    println!("{}", serde_json::to_string_pretty(&output)?);
+                }
+            }
+        }
+        PresetsAction::Show { name } => {
+            let preset = config
+                .presets
+                .get(&name)
+                .ok_or_else(|| BashletError::PresetNotFound { name: name.clone() })?;
+
+            match format {
+                OutputFormat::Text => {
+                    let toml_str = toml::to_string_pretty(preset)
+                        .map_err(|e| BashletError::Config(e.to_string()))?;
+                    println!("[presets.{}]", name);
+                    print!("{}", toml_str);
+                }
+                OutputFormat::Json => {
+                    let output = PresetJson {
+                        schema_version: SCHEMA_VERSION,
+                        name: &name,
+                        preset,
+                    };
+                    println!("{}", serde_json::to_string_pretty(&output)?);
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Editors tried, in order, when $EDITOR is not set
+const FALLBACK_EDITORS: &[&str] = &["vi", "nano"];
+
+/// Open the config file in an editor until it parses or the user gives up.
+fn edit_config() -> Result<()> {
+    let config_path = get_config_path();
+    if !config_path.exists() {
+        if let Some(parent) = config_path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let toml_str = toml::to_string_pretty(&BashletConfig::default())
+            .map_err(|e| BashletError::Config(e.to_string()))?;
+        std::fs::write(&config_path, toml_str)?;
+        eprintln!("Created configuration at: {}", config_path.display());
+    }
+
+    loop {
+        run_editor(&config_path)?;
+
+        let content = std::fs::read_to_string(&config_path)?;
+        match toml::from_str::<BashletConfig>(&content) {
+            Ok(_) => return Ok(()),
+            Err(e) => {
+                eprintln!("Warning: {} is not valid:\n{}", config_path.display(), e);
+                if !confirm("Reopen the editor?")? {
+                    return Ok(());
+                }
+            }
+        }
+    }
+}
+
+/// Run $EDITOR (which may include arguments) on `path`, or the first fallback editor found.
+fn run_editor(path: &Path) -> Result<()> {
+    let editor = std::env::var("EDITOR")
+        .ok()
+        .filter(|e| !e.trim().is_empty());
+    let candidates: Vec<String> = match editor {
+        Some(editor) => vec![editor],
+        None => FALLBACK_EDITORS.iter().map(|e| e.to_string()).collect(),
+    };
+
+    for candidate in &candidates {
+        let mut parts = candidate.split_whitespace();
+        let Some(program) = parts.next() else {
+            continue;
+        };
+
+        match std::process::Command::new(program)
+            .args(parts)
+            .arg(path)
+            .status()
+        {
+            Ok(status) if status.success() => return Ok(()),
+            Ok(status) => {
+                return Err(BashletError::Config(format!(
+                    "Editor '{}' exited with {}",
+                    candidate, status
+                )))
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => continue,
+            Err(e) => return Err(e.into()),
+        }
+    }
+
+    Err(BashletError::Config(format!(
+        "No editor found (tried {}); set $EDITOR",
+        candidates.join(", ")
+    )))
+}
+
+/// Show or clear the download cache
+pub async fn cache(args: CacheArgs, format: OutputFormat) -> Result<()> {
+    match args.action {
+        CacheAction::Info => {
+            let usage = cache::cache_usage();
+            let total: u64 = usage.iter().map(|u| u.bytes).sum();
+            let cache_dir = get_cache_dir();
+
+            match format {
+                OutputFormat::Text => {
+                    println!("Cache directory: {}", cache_dir.display());
+                    for entry in &usage {
+                        println!("  {:<24} {:>10}", entry.category, format_bytes(entry.bytes));
+                    }
+                    println!("  {:<24} {:>10}", "total", format_bytes(total));
+                }
+                OutputFormat::Json => {
+                    let categories: serde_json::Map<String, serde_json::Value> = usage
+                        .iter()
+                        .map(|u| (u.category.to_string(), u.bytes.into()))
+                        .collect();
+                    println!(
+                        "{}",
+                        serde_json::json!({
+                            "path": cache_dir,
+                            "total_bytes": total,
+                            "categories": categories,
+                        })
+                    );
+                }
+            }
+        }
+        CacheAction::Clear {
+            assets,
+            instances,
+            all,
+        } => {
+            let everything = all || (!assets && !instances);
+            let mut freed = 0;
+            let mut removed_instances = 0;
+
+            if everything || assets {
+                freed += cache::clear_assets()?;
+            }
+            if everything || instances {
+                let (count, bytes) = cache::remove_orphaned_instances()?;
+                removed_instances = count;
+                freed += bytes;
+            }
+
+            match format {
+                OutputFormat::Text => {
+                    println!(
+                        "Freed {} ({} orphaned instance rootfs copies removed)",
+                        format_bytes(freed),
+                        removed_instances
+                    );
+                }
+                OutputFormat::Json => {
+                    println!(
+                        "{}",
+                        serde_json::json!({
+                            "freed_bytes": freed,
+                            "removed_instances": removed_instances,
+                        })
+                    );
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Show the bashlet version and, with `--backends`, the host's sandbox tooling
+pub async fn version(args: VersionArgs, format: OutputFormat) -> Result<()> {
+    let version = env!("CARGO_PKG_VERSION");
+
+    if !args.backends {
+        match format {
+            OutputFormat::Text => println!("bashlet {}", version),
+            OutputFormat::Json => println!("{}", serde_json::json!({ "version": version })),
+        }
+        return Ok(());
     }
+
+    let docker = probe_version("docker", "--version").await;
+    // ssh prints its version to stderr
+    let ssh = probe_version("ssh", "-V").await;
+
+    #[cfg(feature = "wasmer")]
+    let (wasmer, wasmer_source, wasmer_pinned) = {
+        let cached = crate::sandbox::cached_wasmer_path();
+        match probe_version("wasmer", "--version").await {
+            Some(v) => (
+                Some(v),
+                Some("system"),
+                Some(crate::sandbox::WASMER_VERSION),
+            ),
+            None => match probe_version(&cached.to_string_lossy(), "--version").await {
+                Some(v) => (
+                    Some(v),
+                    Some("cached"),
+                    Some(crate::sandbox::WASMER_VERSION),
+                ),
+                None => (None, None, Some(crate::sandbox::WASMER_VERSION)),
+            },
+        }
+    };
+    #[cfg(not(feature = "wasmer"))]
+    let (wasmer, wasmer_source, wasmer_pinned): (Option<String>, Option<&str>, Option<&str>) =
+        (None, None, None);
+
+    #[cfg(all(feature = "firecracker", target_os = "linux"))]
+    let firecracker_pinned = Some(crate::sandbox::FIRECRACKER_VERSION);
+    #[cfg(not(all(feature = "firecracker", target_os = "linux")))]
+    let firecracker_pinned: Option<&str> = None;
+
+    let kvm = Path::new("/dev/kvm").exists();
+
+    match format {
+        OutputFormat::Text => {
+            let or_missing = |v: &Option<String>| v.clone().unwrap_or_else(|| "not found".into());
+            println!("bashlet {}", version);
+            println!("  {:<12} {}", "docker", or_missing(&docker));
+            println!("  {:<12} {}", "ssh", or_missing(&ssh));
+            match wasmer_pinned {
+                Some(pinned) => println!(
+                    "  {:<12} {}{} (pinned {})",
+                    "wasmer",
+                    or_missing(&wasmer),
+                    wasmer_source
+                        .map(|s| format!(", {}", s))
+                        .unwrap_or_default(),
+                    pinned
+                ),
+                None => println!("  {:<12} not built with this backend", "wasmer"),
+            }
+            match firecracker_pinned {
+                Some(pinned) => println!("  {:<12} pinned {}", "firecracker", pinned),
+                None => println!("  {:<12} not built with this backend", "firecracker"),
+            }
+            println!(
+                "  {:<12} {}",
+                "kvm",
+                if kvm {
+                    "/dev/kvm present"
+                } else {
+                    "/dev/kvm not found"
+                }
+            );
+        }
+        OutputFormat::Json => {
+            println!(
+                "{}",
+                serde_json::to_string_pretty(&serde_json::json!({
+                    "version": version,
+                    "backends": {
+                        "docker": { "version": docker },
+                        "ssh": { "version": ssh },
+                        "wasmer": {
+                            "built": wasmer_pinned.is_some(),
+                            "version": wasmer,
+                            "source": wasmer_source,
+                            "pinned": wasmer_pinned,
+                        },
+                        "firecracker": {
+                            "built": firecracker_pinned.is_some(),
+                            "pinned": firecracker_pinned,
+                            "kvm": kvm,
+                        },
+                    },
+                }))?
+            );
+        }
+    }
+
     Ok(())
 }
 
+/// Run `program flag` and return the first line it prints, if it succeeds.
+async fn probe_version(program: &str, flag: &str) -> Option<String> {
+    let output = tokio::process::Command::new(program)
+        .arg(flag)
+        .stdin(std::process::Stdio::null())
+        .output()
+        .await
+        .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+
+    let first_line = |bytes: &[u8]| {
+        String::from_utf8_lossy(bytes)
+            .lines()
+            .map(str::trim)
+            .find(|l| !l.is_empty())
+            .map(str::to_string)
+    };
+    first_line(&output.stdout).or_else(|| first_line(&output.stderr))
+}
+
+// ============================================================================
+// Doctor
+// ============================================================================
+
+/// Check the environment for common setup problems and print a report.
+///
+/// Exits with status 1 if any check fails; warnings only affect some backends.
+pub async fn doctor(config_path: Option<&Path>, offline: bool, format: OutputFormat) -> Result<()> {
+    let mut checks = Vec::new();
+
+    let config_file = config_path
+        .map(PathBuf::from)
+        .unwrap_or_else(get_config_path);
+    let config = match load_config(config_path) {
+        Ok(config) => {
+            let detail = if config_file.exists() {
+                format!("Loaded {}", config_file.display())
+            } else {
+                format!("No file at {}, using defaults", config_file.display())
+            };
+            checks.push(DoctorCheckJson::new("config", CheckStatus::Pass, detail));
+            config
+        }
+        Err(e) => {
+            checks.push(
+                DoctorCheckJson::new("config", CheckStatus::Fail, e.to_string()).with_hint(
+                    format!(
+                        "Fix {} or move it aside to use defaults",
+                        config_file.display()
+                    ),
+                ),
+            );
+            BashletConfig::default()
+        }
+    };
+    let offline = offline || config.sandbox.offline;
+
+    checks.extend(check_backends(&config.sandbox));
+    #[cfg(target_os = "linux")]
+    checks.push(check_kvm());
+    checks.push(check_docker_daemon().await);
+    #[cfg(feature = "wasmer")]
+    checks.push(check_wasmer(offline).await);
+    #[cfg(not(feature = "wasmer"))]
+    let _ = offline;
+    if let Some(check) = check_ssh_config(&config.sandbox) {
+        checks.push(check);
+    }
+    checks.push(check_writable(
+        "cache dir",
+        &get_cache_dir(),
+        "BASHLET_CACHE_DIR",
+    ));
+    checks.push(check_writable(
+        "data dir",
+        &get_data_dir(),
+        "BASHLET_DATA_DIR",
+    ));
+
+    let count = |status| checks.iter().filter(|c| c.status == status).count();
+    let failed = count(CheckStatus::Fail);
+
+    match format {
+        OutputFormat::Text => {
+            for check in &checks {
+                let label = match check.status {
+                    CheckStatus::Pass => "ok",
+                    CheckStatus::Warn => "warn",
+                    CheckStatus::Fail => "FAIL",
+                };
+                println!("[{:<4}] {:<20} {}", label, check.name, check.detail);
+                if let Some(ref hint) = check.hint {
+                    println!("       {:<20} hint: {}", "", hint);
+                }
+            }
+            println!(
+                "\n{} passed, {} warnings, {} failed",
+                count(CheckStatus::Pass),
+                count(CheckStatus::Warn),
+                failed
+            );
+        }
+        OutputFormat::Json => {
+            let report = DoctorReportJson {
+                schema_version: SCHEMA_VERSION,
+                ok: failed == 0,
+                checks: &checks,
+            };
+            println!("{}", serde_json::to_string(&report)?);
+        }
+    }
+
+    if failed > 0 {
+        std::process::exit(1);
+    }
+    Ok(())
+}
+
+/// Report each backend, failing if the configured one (or, for `auto`, every
+/// one) is unavailable.
+fn check_backends(config: &SandboxConfig) -> Vec<DoctorCheckJson> {
+    let backends = available_backends();
+    let configured = serde_json::to_value(&config.backend)
+        .ok()
+        .and_then(|v| v.as_str().map(str::to_string))
+        .unwrap_or_default();
+
+    let mut checks: Vec<DoctorCheckJson> = backends
+        .iter()
+        .map(|info| {
+            let name = format!("backend {}", info.name);
+            if info.available {
+                return DoctorCheckJson::new(name, CheckStatus::Pass, info.description);
+            }
+            let status = if info.name == configured {
+                CheckStatus::Fail
+            } else {
+                CheckStatus::Warn
+            };
+            let reason = info.unavailable_reason.unwrap_or("Not available");
+            let mut check = DoctorCheckJson::new(name, status, reason);
+            if status == CheckStatus::Fail {
+                check = check.with_hint(format!(
+                    "sandbox.backend is \"{}\"; fix the problem above or choose another backend",
+                    configured
+                ));
+            }
+            check
+        })
+        .collect();
+
+    if config.backend == BackendType::Auto {
+        if let Err(e) = validate_auto_priority(&config.auto_priority) {
+            checks.push(
+                DoctorCheckJson::new("backend auto", CheckStatus::Fail, e.to_string())
+                    .with_hint("Remove duplicates and auto from sandbox.auto_priority"),
+            );
+        }
+    }
+    if config.backend == BackendType::Auto && !backends.iter().any(|b| b.available) {
+        checks.push(
+            DoctorCheckJson::new("backend auto", CheckStatus::Fail, "No backend is available")
+                .with_hint("Install Docker, or build with the wasmer or firecracker feature"),
+        );
+    }
+    checks
+}
+
+/// Whether `/dev/kvm` exists and can be opened read-write, as Firecracker needs.
+#[cfg(target_os = "linux")]
+fn check_kvm() -> DoctorCheckJson {
+    match std::fs::OpenOptions::new()
+        .read(true)
+        .write(true)
+        .open("/dev/kvm")
+    {
+        Ok(_) => DoctorCheckJson::new("kvm", CheckStatus::Pass, "/dev/kvm is accessible"),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+            DoctorCheckJson::new("kvm", CheckStatus::Warn, "/dev/kvm not found").with_hint(
+                "Needed for Firecracker: enable virtualization and load kvm_intel or kvm_amd",
+            )
+        }
+        Err(e) if e.kind() == std::io::ErrorKind::PermissionDenied => {
+            DoctorCheckJson::new("kvm", CheckStatus::Warn, "No permission to open /dev/kvm")
+                .with_hint("Run `sudo usermod -aG kvm $USER` and log in again")
+        }
+        Err(e) => DoctorCheckJson::new(
+            "kvm",
+            CheckStatus::Warn,
+            format!("Cannot open /dev/kvm: {}", e),
+        ),
+    }
+}
+
+/// Whether the docker CLI is installed and its daemon answers.
+async fn check_docker_daemon() -> DoctorCheckJson {
+    let output = tokio::process::Command::new("docker")
+        .args(["info", "--format", "{{.ServerVersion}}"])
+        .stdin(std::process::Stdio::null())
+        .output()
+        .await;
+
+    match output {
+        Ok(output) if output.status.success() => DoctorCheckJson::new(
+            "docker daemon",
+            CheckStatus::Pass,
+            format!(
+                "Docker {} is reachable",
+                String::from_utf8_lossy(&output.stdout).trim()
+            ),
+        ),
+        Ok(output) => {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            let reason = stderr
+                .lines()
+                .map(str::trim)
+                .find(|l| !l.is_empty())
+                .unwrap_or("docker info failed");
+            DoctorCheckJson::new(
+                "docker daemon",
+                CheckStatus::Warn,
+                format!("Not reachable: {}", reason),
+            )
+            .with_hint(
+                "Start the daemon (e.g. `sudo systemctl start docker`) or add your user to the docker group",
+            )
+        }
+        Err(_) => DoctorCheckJson::new("docker daemon", CheckStatus::Warn, "docker not found")
+            .with_hint("Install Docker to use the docker backend"),
+    }
+}
+
+/// Whether a wasmer binary is installed or cached, or can be downloaded.
+#[cfg(feature = "wasmer")]
+async fn check_wasmer(offline: bool) -> DoctorCheckJson {
+    let cached = crate::sandbox::cached_wasmer_path();
+    if let Some(version) = probe_version("wasmer", "--version").await {
+        return DoctorCheckJson::new("wasmer", CheckStatus::Pass, format!("{} (system)", version));
+    }
+    if let Some(version) = probe_version(&cached.to_string_lossy(), "--version").await {
+        return DoctorCheckJson::new("wasmer", CheckStatus::Pass, format!("{} (cached)", version));
+    }
+
+    if offline {
+        DoctorCheckJson::new(
+            "wasmer",
+            CheckStatus::Warn,
+            "Not installed, and offline mode prevents downloading it",
+        )
+        .with_hint("Install wasmer, or run once without --offline to cache it")
+    } else {
+        DoctorCheckJson::new(
+            "wasmer",
+            CheckStatus::Pass,
+            format!(
+                "Not installed; {} will be downloaded on first use",
+                crate::sandbox::WASMER_VERSION
+            ),
+        )
+    }
+}
+
+/// Check the SSH settings when the SSH backend is configured or a host is set.
+fn check_ssh_config(config: &SandboxConfig) -> Option<DoctorCheckJson> {
+    let ssh = &config.ssh;
+    let selected = config.backend == BackendType::Ssh;
+    if !selected && ssh.host.is_empty() {
+        return None;
+    }
+
+    let mut problems = Vec::new();
+    if ssh.host.is_empty() {
+        problems.push("sandbox.ssh.host is not set".to_string());
+    }
+    if ssh.user.is_empty() {
+        problems.push("sandbox.ssh.user is not set".to_string());
+    }
+    if let Some(ref key_file) = ssh.key_file {
+        if !key_file.exists() {
+            problems.push(format!("key file {} not found", key_file.display()));
+        }
+    }
+    if !SshBackend::is_available() {
+        problems.push("ssh client not found".to_string());
+    }
+
+    Some(if problems.is_empty() {
+        DoctorCheckJson::new(
+            "ssh config",
+            CheckStatus::Pass,
+            format!("{}@{}:{}", ssh.user, ssh.host, ssh.port),
+        )
+    } else {
+        let status = if selected {
+            CheckStatus::Fail
+        } else {
+            CheckStatus::Warn
+        };
+        DoctorCheckJson::new("ssh config", status, problems.join("; "))
+            .with_hint("Fix the [sandbox.ssh] section of the config file")
+    })
+}
+
+/// Whether `dir` can be created and written to.
+fn check_writable(name: &str, dir: &Path, env_var: &str) -> DoctorCheckJson {
+    let probe = dir.join(".bashlet-doctor");
+    let result = std::fs::create_dir_all(dir)
+        .and_then(|_| std::fs::write(&probe, b"ok"))
+        .and_then(|_| std::fs::remove_file(&probe));
+
+    match result {
+        Ok(()) => DoctorCheckJson::new(name, CheckStatus::Pass, dir.display().to_string()),
+        Err(e) => DoctorCheckJson::new(
+            name,
+            CheckStatus::Fail,
+            format!("{} is not writable: {}", dir.display(), e),
+        )
+        .with_hint(format!(
+            "Fix its permissions, or point {} at a writable directory",
+            env_var
+        )),
+    }
+}
+
 // ============================================================================
 // Helper Functions
 // ============================================================================
 
-fn output_command_result(result: &CommandResult, format: OutputFormat) {
+fn format_bytes(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["KB", "MB", "GB", "TB"];
+    if bytes < 1024 {
+        return format!("{} B", bytes);
+    }
+    let mut value = bytes as f64 / 1024.0;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    format!("{:.1} {}", value, UNITS[unit])
+}
+
+/// How much of a command's output to show, from --head/--tail.
+#[derive(Debug, Clone, Copy)]
+struct OutputLines {
+    head: Option<usize>,
+    tail: Option<usize>,
+    /// Also cut down JSON output, which is otherwise left whole
+    truncate_json: bool,
+}
+
+impl OutputLines {
+    fn new(head: Option<usize>, tail: Option<usize>, truncate_json: bool) -> Self {
+        Self {
+            head,
+            tail,
+            truncate_json,
+        }
+    }
+
+    /// Keep the first or last lines of `text`, noting how many were omitted.
+    fn apply<'a>(&self, text: &'a str) -> Cow<'a, str> {
+        let lines: Vec<&str> = text.split_inclusive('\n').collect();
+        let (kept, omitted) = match (self.head, self.tail) {
+            (Some(n), _) if lines.len() > n => (&lines[..n], lines.len() - n),
+            (_, Some(n)) if lines.len() > n => (&lines[lines.len() - n..], lines.len() - n),
+            _ => return Cow::Borrowed(text),
+        };
+
+        let marker = format!("[{} lines omitted]\n", omitted);
+        let mut output = String::new();
+        if self.tail.is_some() {
+            output.push_str(&marker);
+        }
+        output.extend(kept.iter().copied());
+        if self.head.is_some() {
+            if !output.is_empty() && !output.ends_with('\n') {
+                output.push('\n');
+            }
+            output.push_str(&marker);
+        }
+        Cow::Owned(output)
+    }
+}
+
+/// Print a command result, and write its JSON form to `json_out` if given.
+fn output_command_result(
+    result: &CommandResultJson,
+    format: OutputFormat,
+    json_out: Option<&Path>,
+    lines: OutputLines,
+) -> Result<()> {
+    let stdout = lines.apply(result.stdout);
+    let stderr = lines.apply(result.stderr);
+    let truncated = CommandResultJson {
+        stdout: &stdout,
+        stderr: &stderr,
+        ..*result
+    };
+    let json = if lines.truncate_json {
+        &truncated
+    } else {
+        result
+    };
+
     match format {
         OutputFormat::Text => {
-            if !result.stdout.is_empty() {
-                print!("{}", result.stdout);
+            if !stdout.is_empty() {
+                print!("{}", stdout);
             }
-            if !result.stderr.is_empty() {
-                eprint!("{}", result.stderr);
+            if !stderr.is_empty() {
+                eprint!("{}", stderr);
             }
+            write_json_out(json_out, json)?;
             // Exit with the command's exit code
             if result.exit_code != 0 {
                 std::process::exit(result.exit_code);
             }
         }
         OutputFormat::Json => {
-            println!(
-                "{}",
-                serde_json::json!({
-                    "stdout": result.stdout,
-                    "stderr": result.stderr,
-                    "exit_code": result.exit_code,
-                })
-            );
+            println!("{}", serde_json::to_string(json)?);
+            write_json_out(json_out, json)?;
         }
     }
+    Ok(())
+}
+
+/// Write `value` as JSON to the --json-out file, if one was given.
+fn write_json_out(json_out: Option<&Path>, value: &impl serde::Serialize) -> Result<()> {
+    let Some(path) = json_out else {
+        return Ok(());
+    };
+    let json = serde_json::to_string_pretty(value)?;
+    std::fs::write(path, json + "\n").map_err(|e| {
+        anyhow::anyhow!("Failed to write --json-out file {}: {}", path.display(), e).into()
+    })
 }
 
 fn format_timestamp(timestamp: u64) -> String {
@@ -547,6 +2847,14 @@ fn format_timestamp(timestamp: u64) -> String {
     local.format("%Y-%m-%d %H:%M").to_string()
 }
 
+/// Current time as Unix epoch seconds.
+fn unix_now() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
+
 fn format_duration(seconds: u64) -> String {
     if seconds < 60 {
         format!("{}s", seconds)
@@ -558,3 +2866,111 @@ fn format_duration(seconds: u64) -> String {
         format!("{}d", seconds / 86400)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn mount(host_path: impl Into<PathBuf>, readonly: bool) -> Mount {
+        Mount {
+            host_path: host_path.into(),
+            guest_path: "/data".to_string(),
+            readonly,
+            explicit_access: true,
+            options: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_check_mount_safety() {
+        let denied = SandboxConfig::default().denied_mount_paths;
+        let check = |m: Mount, allow_unsafe| check_mount_safety(&[m], &denied, allow_unsafe);
+
+        // `/` is denied, but doesn't deny everything beneath it
+        assert!(matches!(
+            check(mount("/", false), false),
+            Err(BashletError::UnsafeMount { .. })
+        ));
+        assert!(check(mount("/srv", false), false).is_ok());
+
+        // Denied paths cover their subdirectories, unless mounted read-only
+        assert!(check(mount("/etc/ssl", false), false).is_err());
+        assert!(check(mount("/etc/ssl", true), false).is_ok());
+
+        // --allow-unsafe-mount only warns
+        assert!(check(mount("/etc", false), true).is_ok());
+    }
+
+    #[test]
+    fn test_is_protected_path() {
+        for path in ["/", "/usr", "/usr/", ".", "./", "~", "..", "*", ""] {
+            assert!(is_protected_path(path), "{:?} should be protected", path);
+        }
+        for path in ["/usr/lib", "/workspace/build", "build", "./build"] {
+            assert!(!is_protected_path(path), "{:?} is not protected", path);
+        }
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_check_mount_safety_resolves_symlinks() {
+        let dir = tempfile::tempdir().unwrap();
+        let link = dir.path().join("config");
+        std::os::unix::fs::symlink("/etc", &link).unwrap();
+
+        let denied = SandboxConfig::default().denied_mount_paths;
+        match check_mount_safety(&[mount(&link, false)], &denied, false) {
+            Err(BashletError::UnsafeMount { path }) => assert_eq!(path, "/etc"),
+            other => panic!("expected UnsafeMount, got {:?}", other),
+        }
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_session_backend_not_tracked() {
+        use std::os::unix::fs::PermissionsExt;
+
+        // A stub cached wasmer is enough to construct a Wasmer backend
+        let cache_dir = tempfile::tempdir().unwrap();
+        let wasmer = cache_dir.path().join("wasmer").join("wasmer");
+        std::fs::create_dir_all(wasmer.parent().unwrap()).unwrap();
+        std::fs::write(&wasmer, "#!/bin/sh\necho wasmer 6.0.0\n").unwrap();
+        std::fs::set_permissions(&wasmer, std::fs::Permissions::from_mode(0o755)).unwrap();
+        let webc = cache_dir.path().join("bash.webc");
+        std::fs::write(&webc, b"").unwrap();
+        let _cache_dir = crate::config::loader::CacheDirOverride::new(cache_dir.path());
+
+        let mut config = BashletConfig::default();
+        config.sandbox.backend = BackendType::Wasmer;
+        let mut session = Session::new(
+            None,
+            Vec::new(),
+            Vec::new(),
+            "/workspace".to_string(),
+            Some(webc),
+            None,
+            None,
+        );
+
+        let is_active = |backend: &Arc<dyn SandboxBackend>| {
+            ACTIVE_BACKENDS
+                .lock()
+                .unwrap()
+                .iter()
+                .any(|b| Arc::ptr_eq(b, backend))
+        };
+
+        let (sandbox_config, runtime) = session_runtime(&session, &config);
+        let one_shot = create_tracked_backend(&sandbox_config, runtime)
+            .await
+            .unwrap();
+        assert!(is_active(&one_shot));
+        untrack_backend(one_shot.as_ref());
+        assert!(!is_active(&one_shot));
+
+        let backend = session_backend(&SessionManager::new(), &mut session, &config)
+            .await
+            .unwrap();
+        assert!(!is_active(&backend));
+    }
+}
@@ -0,0 +1,279 @@
+//! Typed shapes for `--format json` output.
+//!
+//! Integrations parse these, so fields are only ever added within a schema
+//! version. Renaming or removing a field, or changing its meaning, requires
+//! bumping [`SCHEMA_VERSION`].
+
+use serde::Serialize;
+
+use crate::config::types::{BackendType, PresetConfig, SandboxConfig};
+use crate::sandbox::{BackendCapabilities, CommandResult, CommandTiming, RuntimeConfig};
+use crate::session::{SerializableMount, Session};
+
+/// Version of the JSON output schema.
+pub const SCHEMA_VERSION: u32 = 1;
+
+/// Result of `exec` or `run`.
+#[derive(Debug, Serialize)]
+pub struct CommandResultJson<'a> {
+    pub schema_version: u32,
+    /// Session kept by `exec --detach`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub session: Option<&'a str>,
+    pub stdout: &'a str,
+    pub stderr: &'a str,
+    pub exit_code: i32,
+    /// Signal that killed the command, inferred from a shell-style exit code above 128
+    pub signal: Option<i32>,
+    /// Whether stdout or stderr exceeded the output limit and was truncated
+    pub truncated: bool,
+    /// Wall-clock duration and backend phases, when measured
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub timing: Option<&'a CommandTiming>,
+}
+
+impl<'a> CommandResultJson<'a> {
+    pub fn new(result: &'a CommandResult) -> Self {
+        Self {
+            schema_version: SCHEMA_VERSION,
+            session: None,
+            stdout: &result.stdout,
+            stderr: &result.stderr,
+            exit_code: result.exit_code,
+            signal: (129..=192)
+                .contains(&result.exit_code)
+                .then_some(result.exit_code - 128),
+            truncated: result.truncated,
+            timing: result.timing.as_ref(),
+        }
+    }
+}
+
+/// Result of `run --script`.
+#[derive(Debug, Serialize)]
+pub struct ScriptResultJson<'a> {
+    pub schema_version: u32,
+    /// Commands that ran, in order
+    pub steps: Vec<ScriptStepJson<'a>>,
+    /// Exit code of the first failed command, or 0
+    pub exit_code: i32,
+}
+
+/// One command of a script.
+#[derive(Debug, Serialize)]
+pub struct ScriptStepJson<'a> {
+    /// Line number in the script file
+    pub line: usize,
+    pub command: &'a str,
+    pub result: CommandResultJson<'a>,
+}
+
+/// Output of `--print-config`: what a command would run with, once presets,
+/// flags, and environment overrides are merged.
+#[derive(Debug, Serialize)]
+pub struct EffectiveConfigJson<'a> {
+    pub schema_version: u32,
+    pub sandbox: &'a SandboxConfig,
+    pub mounts: Vec<SerializableMount>,
+    pub env_vars: &'a [(String, String)],
+    /// Names only; secret values are never printed
+    pub secret_env_vars: Vec<&'a str>,
+    pub workdir: &'a str,
+    /// Session container that would be reused
+    pub instance_id: Option<&'a str>,
+    pub dns: &'a [String],
+    pub extra_hosts: &'a [(String, String)],
+    /// Capabilities the backend must provide
+    pub required: &'a BackendCapabilities,
+    /// Preset setup commands that would run first
+    pub setup_commands: &'a [String],
+}
+
+impl<'a> EffectiveConfigJson<'a> {
+    pub fn new(
+        sandbox: &'a SandboxConfig,
+        runtime: &'a RuntimeConfig,
+        setup_commands: &'a [String],
+    ) -> Self {
+        Self {
+            schema_version: SCHEMA_VERSION,
+            sandbox,
+            mounts: runtime.mounts.iter().map(SerializableMount::from).collect(),
+            env_vars: &runtime.env_vars,
+            secret_env_vars: runtime
+                .secret_env_vars
+                .iter()
+                .map(|(key, _)| key.as_str())
+                .collect(),
+            workdir: &runtime.workdir,
+            instance_id: runtime.instance_id.as_deref(),
+            dns: &runtime.network.dns,
+            extra_hosts: &runtime.network.extra_hosts,
+            required: &runtime.required,
+            setup_commands,
+        }
+    }
+}
+
+/// Output of `doctor`.
+#[derive(Debug, Serialize)]
+pub struct DoctorReportJson<'a> {
+    pub schema_version: u32,
+    /// Whether no check failed
+    pub ok: bool,
+    pub checks: &'a [DoctorCheckJson],
+}
+
+/// Outcome of one `doctor` check.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CheckStatus {
+    Pass,
+    /// Something that only matters for some backends or features
+    Warn,
+    Fail,
+}
+
+/// One `doctor` check.
+#[derive(Debug, Serialize)]
+pub struct DoctorCheckJson {
+    pub name: String,
+    pub status: CheckStatus,
+    pub detail: String,
+    /// How to fix a warning or failure
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub hint: Option<String>,
+}
+
+impl DoctorCheckJson {
+    pub fn new(name: impl Into<String>, status: CheckStatus, detail: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            status,
+            detail: detail.into(),
+            hint: None,
+        }
+    }
+
+    pub fn with_hint(mut self, hint: impl Into<String>) -> Self {
+        self.hint = Some(hint.into());
+        self
+    }
+}
+
+/// Output of `list`.
+#[derive(Debug, Serialize)]
+pub struct SessionListJson<'a> {
+    pub schema_version: u32,
+    pub sessions: Vec<SessionJson<'a>>,
+}
+
+impl<'a> SessionListJson<'a> {
+    pub fn new(sessions: &'a [Session]) -> Self {
+        Self {
+            schema_version: SCHEMA_VERSION,
+            sessions: sessions.iter().map(SessionJson::new).collect(),
+        }
+    }
+}
+
+/// One session in `list` output.
+#[derive(Debug, Serialize)]
+pub struct SessionJson<'a> {
+    pub id: &'a str,
+    pub name: Option<&'a str>,
+    /// Preset the session was created from
+    pub preset: Option<&'a str>,
+    pub created_at: u64,
+    pub last_activity: u64,
+    pub ttl_seconds: Option<u64>,
+    pub expired: bool,
+    pub mounts: &'a [SerializableMount],
+    pub workdir: &'a str,
+    /// Whether the container was stopped for being idle
+    pub idle_stopped: bool,
+}
+
+impl<'a> SessionJson<'a> {
+    pub fn new(session: &'a Session) -> Self {
+        Self {
+            id: &session.id,
+            name: session.name.as_deref(),
+            preset: session.preset.as_deref(),
+            created_at: session.created_at,
+            last_activity: session.last_activity,
+            ttl_seconds: session.ttl_seconds,
+            expired: session.is_expired(),
+            mounts: &session.mounts,
+            workdir: &session.workdir,
+            idle_stopped: session.idle_stopped,
+        }
+    }
+}
+
+/// Output of `presets list`.
+#[derive(Debug, Serialize)]
+pub struct PresetListJson<'a> {
+    pub schema_version: u32,
+    pub presets: Vec<PresetSummaryJson<'a>>,
+}
+
+/// One preset in `presets list` output.
+#[derive(Debug, Serialize)]
+pub struct PresetSummaryJson<'a> {
+    pub name: &'a str,
+    /// Backend override, if the preset sets one
+    pub backend: Option<&'a BackendType>,
+    pub mounts: usize,
+    pub env_vars: usize,
+    pub setup_commands: usize,
+}
+
+impl<'a> PresetSummaryJson<'a> {
+    pub fn new(name: &'a str, preset: &'a PresetConfig) -> Self {
+        Self {
+            name,
+            backend: preset.backend.as_ref(),
+            mounts: preset.mounts.len(),
+            env_vars: preset.env_vars.len(),
+            setup_commands: preset.setup_commands.len(),
+        }
+    }
+}
+
+/// Output of `presets show`.
+#[derive(Debug, Serialize)]
+pub struct PresetJson<'a> {
+    pub schema_version: u32,
+    pub name: &'a str,
+    pub preset: &'a PresetConfig,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_command_result_json() {
+        let result = CommandResult {
+            stdout: "out".to_string(),
+            stderr: String::new(),
+            exit_code: 137,
+            truncated: false,
+            timing: None,
+        };
+
+        let json = serde_json::to_value(CommandResultJson::new(&result)).unwrap();
+        assert_eq!(
+            json,
+            serde_json::json!({
+                "schema_version": 1,
+                "stdout": "out",
+                "stderr": "",
+                "exit_code": 137,
+                "signal": 9,
+                "truncated": false,
+            })
+        );
+    }
+}
@@ -1,7 +1,12 @@
 use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
 
-use crate::config::types::BashletConfig;
+use serde_json::Value;
+use tracing::warn;
+
+use crate::config::types::{BashletConfig, PathsConfig};
 use crate::error::{BashletError, Result};
+use crate::session::parse_ttl;
 
 /// Get the default configuration file path
 pub fn get_config_path() -> PathBuf {
@@ -20,25 +25,156 @@ fn dirs_fallback() -> PathBuf {
 }
 
 /// Load configuration from file, with defaults for missing values
+///
+/// `BASHLET_<SECTION>_<FIELD>` environment variables are applied on top.
 pub fn load_config(config_path: Option<&Path>) -> Result<BashletConfig> {
     let path = config_path
         .map(PathBuf::from)
         .unwrap_or_else(get_config_path);
 
-    if !path.exists() {
-        // Return defaults if no config file exists
-        return Ok(BashletConfig::default());
+    // Use defaults if no config file exists
+    let config = if path.exists() {
+        let content = std::fs::read_to_string(&path)?;
+        toml::from_str(&content).map_err(|e| BashletError::TomlParse(e.to_string()))?
+    } else {
+        BashletConfig::default()
+    };
+    let config = apply_env_overrides(config, std::env::vars())?;
+    validate_config(&config)?;
+
+    // Directory lookups happen deep inside backends, so remember the overrides here
+    let _ = PATH_OVERRIDES.set(config.paths.clone());
+
+    Ok(config)
+}
+
+/// Check values that are otherwise only parsed when a command uses them.
+fn validate_config(config: &BashletConfig) -> Result<()> {
+    if let Some(ref idle_shutdown) = config.sandbox.idle_shutdown {
+        parse_ttl(idle_shutdown).map_err(|_| {
+            BashletError::Config(format!(
+                "sandbox.idle_shutdown: invalid duration '{}'",
+                idle_shutdown
+            ))
+        })?;
     }
+    Ok(())
+}
+
+/// Config sections settable from the environment, by variable name prefix.
+const ENV_SECTIONS: &[(&str, &str)] = &[
+    ("SANDBOX", "/sandbox"),
+    ("WASMER", "/sandbox/wasmer"),
+    ("FIRECRACKER", "/sandbox/firecracker"),
+    ("DOCKER", "/sandbox/docker"),
+    ("SSH", "/sandbox/ssh"),
+    ("PATHS", "/paths"),
+];
+
+/// Overlay `BASHLET_<SECTION>_<FIELD>` variables, e.g. `BASHLET_SANDBOX_BACKEND`
+/// or `BASHLET_SSH_HOST`, on the configuration.
+///
+/// Values are read as the field's type: strings verbatim, booleans as
+/// true/false/1/0, and lists as JSON arrays. Variables naming a field
+/// that doesn't exist are ignored with a warning.
+fn apply_env_overrides(
+    config: BashletConfig,
+    vars: impl IntoIterator<Item = (String, String)>,
+) -> Result<BashletConfig> {
+    let mut tree = serde_json::to_value(&config)
+        .map_err(|e| BashletError::Config(format!("Failed to serialize config: {}", e)))?;
+    let mut config = config;
+
+    for (name, raw) in vars {
+        let Some(pointer) = name.strip_prefix("BASHLET_").and_then(|rest| {
+            ENV_SECTIONS.iter().find_map(|(section, path)| {
+                let field = rest.strip_prefix(section)?.strip_prefix('_')?;
+                Some(format!("{}/{}", path, field.to_lowercase()))
+            })
+        }) else {
+            continue;
+        };
+        let Some(current) = tree.pointer(&pointer) else {
+            warn!(variable = %name, "Ignoring unknown configuration variable");
+            continue;
+        };
 
-    let content = std::fs::read_to_string(&path)?;
-    let config: BashletConfig =
-        toml::from_str(&content).map_err(|e| BashletError::TomlParse(e.to_string()))?;
+        let candidates = env_value_candidates(current, &raw)
+            .map_err(|reason| BashletError::Config(format!("{}: {}", name, reason)))?;
+        let mut last_error = None;
+        for candidate in candidates {
+            let mut updated = tree.clone();
+            if let Some(slot) = updated.pointer_mut(&pointer) {
+                *slot = candidate;
+            }
+            match serde_json::from_value(updated.clone()) {
+                Ok(parsed) => {
+                    tree = updated;
+                    config = parsed;
+                    last_error = None;
+                    break;
+                }
+                Err(e) => last_error = Some(e),
+            }
+        }
+        if let Some(e) = last_error {
+            return Err(BashletError::Config(format!("{}: {}", name, e)));
+        }
+    }
 
     Ok(config)
 }
 
+/// Values to try for an environment variable, given the field's current value.
+///
+/// An unset optional field has no type to go by, so the value is tried as
+/// JSON (a number or list) before falling back to a string.
+fn env_value_candidates(current: &Value, raw: &str) -> std::result::Result<Vec<Value>, String> {
+    match current {
+        Value::String(_) => Ok(vec![Value::String(raw.to_string())]),
+        Value::Bool(_) => match raw {
+            "true" | "1" => Ok(vec![Value::Bool(true)]),
+            "false" | "0" => Ok(vec![Value::Bool(false)]),
+            _ => Err(format!("expected true or false, got '{}'", raw)),
+        },
+        Value::Number(_) => match serde_json::from_str(raw) {
+            Ok(value @ Value::Number(_)) => Ok(vec![value]),
+            _ => Err(format!("expected a number, got '{}'", raw)),
+        },
+        Value::Array(_) => match serde_json::from_str(raw) {
+            Ok(value @ Value::Array(_)) => Ok(vec![value]),
+            _ => Err(format!("expected a JSON array, got '{}'", raw)),
+        },
+        Value::Null => Ok(serde_json::from_str(raw)
+            .into_iter()
+            .chain([Value::String(raw.to_string())])
+            .collect()),
+        Value::Object(_) => Err("a whole section can't be set from one variable".to_string()),
+    }
+}
+
+/// Directory overrides from the loaded configuration file.
+static PATH_OVERRIDES: OnceLock<PathsConfig> = OnceLock::new();
+
+/// Resolve a directory override: environment variable first, then config.
+fn dir_override(
+    env_var: &str,
+    from_config: fn(&PathsConfig) -> Option<&PathBuf>,
+) -> Option<PathBuf> {
+    std::env::var_os(env_var)
+        .filter(|value| !value.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| PATH_OVERRIDES.get().and_then(from_config).cloned())
+}
+
 /// Get the cache directory for WASM binaries
+///
+/// Overridable with BASHLET_CACHE_DIR or `paths.cache_dir`.
 pub fn get_cache_dir() -> PathBuf {
+    if let Some(dir) = dir_override("BASHLET_CACHE_DIR", |p| p.cache_dir.as_ref()) {
+        return dir;
+    }
+
     if let Some(proj_dirs) = directories::ProjectDirs::from("com", "bashlet", "bashlet") {
         proj_dirs.cache_dir().to_path_buf()
     } else {
@@ -47,10 +183,111 @@ pub fn get_cache_dir() -> PathBuf {
 }
 
 /// Get the data directory for session storage
+///
+/// Overridable with BASHLET_DATA_DIR or `paths.data_dir`.
 pub fn get_data_dir() -> PathBuf {
+    if let Some(dir) = dir_override("BASHLET_DATA_DIR", |p| p.data_dir.as_ref()) {
+        return dir;
+    }
+
     if let Some(proj_dirs) = directories::ProjectDirs::from("com", "bashlet", "bashlet") {
         proj_dirs.data_dir().to_path_buf()
     } else {
         dirs_fallback().join(".local").join("share").join("bashlet")
     }
 }
+
+/// Serializes tests that point `BASHLET_CACHE_DIR` elsewhere.
+#[cfg(test)]
+static CACHE_DIR_TEST_LOCK: std::sync::Mutex<()> = std::sync::Mutex::new(());
+
+/// Points `BASHLET_CACHE_DIR` at a test directory until dropped, then
+/// restores the previous value.
+#[cfg(test)]
+pub(crate) struct CacheDirOverride {
+    previous: Option<std::ffi::OsString>,
+    _serial: std::sync::MutexGuard<'static, ()>,
+}
+
+#[cfg(test)]
+impl CacheDirOverride {
+    pub(crate) fn new(dir: &Path) -> Self {
+        let serial = CACHE_DIR_TEST_LOCK
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        let previous = std::env::var_os("BASHLET_CACHE_DIR");
+        std::env::set_var("BASHLET_CACHE_DIR", dir);
+        Self {
+            previous,
+            _serial: serial,
+        }
+    }
+}
+
+#[cfg(test)]
+impl Drop for CacheDirOverride {
+    fn drop(&mut self) {
+        match self.previous.take() {
+            Some(value) => std::env::set_var("BASHLET_CACHE_DIR", value),
+            None => std::env::remove_var("BASHLET_CACHE_DIR"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::types::BackendType;
+
+    fn vars(pairs: &[(&str, &str)]) -> Vec<(String, String)> {
+        pairs
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn test_apply_env_overrides() {
+        let config = apply_env_overrides(
+            BashletConfig::default(),
+            vars(&[
+                ("BASHLET_SANDBOX_BACKEND", "docker"),
+                ("BASHLET_SANDBOX_MEMORY_LIMIT_MB", "512"),
+                ("BASHLET_SANDBOX_COMMAND_DENYLIST", r#"["rm -rf /"]"#),
+                ("BASHLET_DOCKER_ENABLE_NETWORKING", "1"),
+                ("BASHLET_DOCKER_IMAGE", "python:3.12"),
+                ("BASHLET_SSH_HOST", "10.0.0.1"),
+                ("BASHLET_SSH_USER", "1000"),
+                ("BASHLET_CACHE_DIR", "/ignored"),
+                ("BASHLET_SANDBOX_NO_SUCH_FIELD", "x"),
+            ]),
+        )
+        .unwrap();
+
+        assert_eq!(config.sandbox.backend, BackendType::Docker);
+        assert_eq!(config.sandbox.memory_limit_mb, 512);
+        assert_eq!(config.sandbox.command_denylist, vec!["rm -rf /"]);
+        assert!(config.sandbox.docker.enable_networking);
+        assert_eq!(config.sandbox.docker.image.as_deref(), Some("python:3.12"));
+        assert_eq!(config.sandbox.ssh.host, "10.0.0.1");
+        assert_eq!(config.sandbox.ssh.user, "1000");
+
+        let err = apply_env_overrides(
+            BashletConfig::default(),
+            vars(&[("BASHLET_SANDBOX_MEMORY_LIMIT_MB", "lots")]),
+        )
+        .unwrap_err();
+        assert!(err.to_string().contains("BASHLET_SANDBOX_MEMORY_LIMIT_MB"));
+    }
+
+    #[test]
+    fn test_validate_config() {
+        let mut config = BashletConfig::default();
+        config.sandbox.idle_shutdown = Some("10m".to_string());
+        assert!(validate_config(&config).is_ok());
+
+        config.sandbox.idle_shutdown = Some("ten minutes".to_string());
+        let err = validate_config(&config).unwrap_err();
+        assert!(err.to_string().contains("sandbox.idle_shutdown"));
+    }
+}
@@ -3,11 +3,27 @@ use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::path::PathBuf;
 
+use crate::sandbox::DEFAULT_MAX_OUTPUT_BYTES;
+
 #[derive(Debug, Clone, Default, Serialize, Deserialize)]
 #[serde(default)]
 pub struct BashletConfig {
     pub sandbox: SandboxConfig,
     pub presets: HashMap<String, PresetConfig>,
+    pub paths: PathsConfig,
+}
+
+/// Overrides for where bashlet stores downloads and sessions.
+///
+/// The BASHLET_CACHE_DIR and BASHLET_DATA_DIR environment variables take
+/// precedence over these.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PathsConfig {
+    /// Directory for downloaded runtimes, kernels, and rootfs images
+    pub cache_dir: Option<PathBuf>,
+    /// Directory for session state
+    pub data_dir: Option<PathBuf>,
 }
 
 /// Configuration for a preset environment.
@@ -18,6 +34,8 @@ pub struct PresetConfig {
     pub backend: Option<BackendType>,
     /// Setup commands to run on session creation
     pub setup_commands: Vec<String>,
+    /// Commands to run before the sandbox is torn down (on terminate, or after exec)
+    pub teardown_commands: Vec<String>,
     /// Environment variables [(KEY, VALUE), ...]
     pub env_vars: Vec<(String, String)>,
     /// Mount specifications [(host_path, guest_path, readonly), ...]
@@ -50,16 +68,49 @@ pub enum BackendType {
 pub struct SandboxConfig {
     /// Which backend to use
     pub backend: BackendType,
+    /// Order in which `auto` tries backends, taking the first available one.
+    /// Backends left out are never picked by `auto`.
+    pub auto_priority: Vec<BackendType>,
     /// Default working directory
     pub default_workdir: String,
-    /// Memory limit in MB
+    /// Memory limit in MB (a hard cap for Firecracker VMs, which have no swap)
     pub memory_limit_mb: u64,
     /// Command timeout in seconds
     pub timeout_seconds: u64,
+    /// Maximum bytes of stdout/stderr captured per command (default: 10 MiB).
+    /// Output beyond this is discarded and replaced with a truncation marker.
+    pub max_output_bytes: u64,
+    /// Never download runtimes, kernels, or images (also --offline / BASHLET_OFFLINE)
+    pub offline: bool,
+    /// DNS servers for networked backends (e.g., ["10.0.0.2"])
+    pub dns: Vec<String>,
+    /// Extra /etc/hosts entries for networked backends [(hostname, ip), ...]
+    pub extra_hosts: Vec<(String, String)>,
+    /// Warn when a command consumes more than this many MB of free space on a
+    /// writable mount's host filesystem (also --mount-quota). Not a hard limit.
+    pub mount_quota_mb: Option<u64>,
+    /// Host paths that can't be mounted read-write without --allow-unsafe-mount.
+    /// Paths inside them are covered too, except for `/`, which only matches itself.
+    pub denied_mount_paths: Vec<PathBuf>,
+    /// Make mounts read-only unless given with `:rw`, instead of read-write
+    /// unless given with `:ro`
+    pub mounts_readonly_by_default: bool,
+    /// Regexes a command must not match (checked by exec, run, and shell).
+    /// A guardrail against mistakes, not a security boundary.
+    pub command_denylist: Vec<String>,
+    /// If non-empty, regexes of which a command must match at least one
+    pub command_allowlist: Vec<String>,
+    /// Interpreter commands run with, e.g. "/bin/bash" (also --shell). Each
+    /// backend's own default (`sh`, or bash for Wasmer) when unset.
+    pub shell: Option<String>,
     /// Default idle timeout for sessions (e.g., "30m", "1h", "2d")
     /// Sessions will automatically expire after this duration of no command execution.
     /// If not set, sessions have no expiration unless --ttl is specified.
     pub default_idle_timeout: Option<String>,
+    /// Stop a session's container after this long without commands (e.g., "10m").
+    /// The session is kept, and its next command starts the container again.
+    /// Enforced by `bashlet reap`, so run it periodically.
+    pub idle_shutdown: Option<String>,
     /// Wasmer-specific configuration
     pub wasmer: WasmerConfig,
     /// Firecracker-specific configuration
@@ -74,10 +125,30 @@ impl Default for SandboxConfig {
     fn default() -> Self {
         Self {
             backend: BackendType::default(),
+            auto_priority: vec![
+                BackendType::Firecracker,
+                BackendType::Docker,
+                BackendType::Wasmer,
+                BackendType::Ssh,
+            ],
             default_workdir: "/workspace".to_string(),
             memory_limit_mb: 256,
             timeout_seconds: 300,
+            max_output_bytes: DEFAULT_MAX_OUTPUT_BYTES,
+            offline: false,
+            dns: Vec::new(),
+            extra_hosts: Vec::new(),
+            mount_quota_mb: None,
+            denied_mount_paths: ["/", "/etc", "/boot", "/dev", "/proc", "/sys"]
+                .into_iter()
+                .map(PathBuf::from)
+                .collect(),
+            mounts_readonly_by_default: false,
+            command_denylist: Vec::new(),
+            command_allowlist: Vec::new(),
+            shell: None,
             default_idle_timeout: None,
+            idle_shutdown: None,
             wasmer: WasmerConfig::default(),
             firecracker: FirecrackerConfig::default(),
             docker: DockerConfig::default(),
@@ -92,6 +163,10 @@ impl Default for SandboxConfig {
 pub struct WasmerConfig {
     /// Path to custom WASM binary (bash.wasm)
     pub wasm_binary: Option<PathBuf>,
+    /// Wasmer registry package to run commands with instead of the pinned
+    /// bash WEBC, as `namespace/name[@version]`. It must accept `-c COMMAND`
+    /// like bash does.
+    pub package: Option<String>,
 }
 
 /// Firecracker-specific configuration.
@@ -108,6 +183,24 @@ pub struct FirecrackerConfig {
     pub vcpu_count: u8,
     /// Enable networking in the microVM
     pub enable_networking: bool,
+    /// Seconds to wait for the VM to boot before giving up (default: 10)
+    pub boot_timeout_secs: u64,
+    /// Seconds to wait for the guest agent to accept a connection once the VM
+    /// has booted (default: 10). Gives up early if the VM exits.
+    pub agent_connect_timeout_secs: u64,
+    /// Number of times to retry a failed VM boot (default: 0)
+    pub boot_retries: u32,
+    /// When the backend is auto-selected, fall back to Docker/Wasmer if the VM
+    /// fails to boot (default: true)
+    pub fallback_on_boot_failure: bool,
+    /// Boot from `rootfs_path` read-only with a per-VM writable overlay drive
+    /// instead of using the image directly (default: false). Requires
+    /// `rootfs_path` set to an image that provides `/sbin/overlay-init`; if
+    /// the VM fails to boot, a copy of the image is used instead.
+    pub use_overlay: bool,
+    /// Keep a session's VM running between commands, so later `run`s reconnect
+    /// to it instead of booting a fresh VM (default: false)
+    pub session_mode: bool,
 }
 
 impl Default for FirecrackerConfig {
@@ -118,10 +211,28 @@ impl Default for FirecrackerConfig {
             rootfs_path: None,
             vcpu_count: 1,
             enable_networking: false,
+            boot_timeout_secs: 10,
+            agent_connect_timeout_secs: 10,
+            boot_retries: 0,
+            fallback_on_boot_failure: true,
+            use_overlay: false,
+            session_mode: false,
         }
     }
 }
 
+/// When to `docker pull` the sandbox image before using it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum PullPolicy {
+    /// Pull every time, refreshing a cached image
+    Always,
+    /// Pull only if the image isn't available locally
+    Missing,
+    /// Never pull; the image must exist locally or be built
+    Never,
+}
+
 /// Docker-specific configuration.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(default)]
@@ -130,8 +241,23 @@ pub struct DockerConfig {
     pub image: Option<String>,
     /// Automatically build the image if it doesn't exist (default: true)
     pub build_image: bool,
+    /// When to pull the image (also --pull-policy). Defaults to "missing"
+    /// for custom images and "never" for the locally built bashlet image.
+    /// Pulling happens before the image is checked or built.
+    pub pull_policy: Option<PullPolicy>,
     /// Enable networking in the container (default: false)
     pub enable_networking: bool,
+    /// Total memory plus swap in MB, passed as `--memory-swap`.
+    /// Set equal to `memory_limit_mb` to disable swap. Docker allows swap when unset.
+    pub memory_swap_mb: Option<u64>,
+    /// Seccomp profile JSON file (`--security-opt seccomp=<file>`)
+    pub seccomp_profile: Option<PathBuf>,
+    /// AppArmor profile name (`--security-opt apparmor=<profile>`)
+    pub apparmor_profile: Option<String>,
+    /// Extra arguments passed verbatim to `docker run` before the image
+    /// (also --docker-arg), e.g. `["--gpus=all", "--shm-size=1g"]`. bashlet
+    /// does not validate them, so they can loosen the sandbox.
+    pub extra_run_args: Vec<String>,
     /// Enable session mode for persistent container (default: false)
     /// When enabled, a single container stays running and commands are executed via docker exec.
     /// The container is only terminated when shutdown() is called.
@@ -143,7 +269,12 @@ impl Default for DockerConfig {
         Self {
             image: None,
             build_image: true,
+            pull_policy: None,
             enable_networking: false,
+            memory_swap_mb: None,
+            seccomp_profile: None,
+            apparmor_profile: None,
+            extra_run_args: Vec::new(),
             session_mode: false,
         }
     }
@@ -165,6 +296,9 @@ pub struct SshConfig {
     pub use_control_master: bool,
     /// Timeout for SSH connection in seconds (default: 30)
     pub connect_timeout: u64,
+    /// Create the remote working directory if it doesn't exist (default: true).
+    /// When false, commands fail if the directory is missing.
+    pub ensure_workdir: bool,
 }
 
 impl Default for SshConfig {
@@ -176,6 +310,7 @@ impl Default for SshConfig {
             key_file: None,
             use_control_master: true,
             connect_timeout: 30,
+            ensure_workdir: true,
         }
     }
 }
@@ -35,12 +35,29 @@ pub enum BashletError {
     #[error("Command execution failed in sandbox: {0}")]
     SandboxExecution(String),
 
+    #[error("Command rejected by filter ({reason}): {command}")]
+    CommandRejected { command: String, reason: String },
+
+    #[error("Setup command failed with exit code {exit_code}: {command}\n{output}")]
+    SetupCommandFailed {
+        command: String,
+        exit_code: i32,
+        /// Tail of the command's stderr (or stdout, if stderr was empty)
+        output: String,
+    },
+
     #[error("Sandbox timeout after {seconds} seconds")]
     SandboxTimeout { seconds: u64 },
 
     #[error("Mount path does not exist: {path}")]
     MountPathNotFound { path: String },
 
+    #[error("Refusing to mount sensitive host path read-write: {path} (use --allow-unsafe-mount to override)")]
+    UnsafeMount { path: String },
+
+    #[error("Failed to clone {url}: {reason}")]
+    GitClone { url: String, reason: String },
+
     #[error("WASM binary not found: {path}")]
     WasmNotFound { path: String },
 
@@ -48,6 +65,9 @@ pub enum BashletError {
     #[error("Backend '{backend}' is not available: {reason}")]
     BackendNotAvailable { backend: String, reason: String },
 
+    #[error("Docker image '{image}' is not available: {reason}")]
+    DockerImageUnavailable { image: String, reason: String },
+
     #[error("Firecracker API error: {message}")]
     FirecrackerApi {
         message: String,
@@ -63,6 +83,9 @@ pub enum BashletError {
     #[error("Asset download failed: {url}")]
     AssetDownload { url: String },
 
+    #[error("Operation '{operation}' is not supported: {reason}")]
+    Unsupported { operation: String, reason: String },
+
     // Session errors
     #[error("Session not found: {id}")]
     SessionNotFound { id: String },
@@ -73,9 +96,15 @@ pub enum BashletError {
     #[error("Session expired: {id}")]
     SessionExpired { id: String },
 
+    #[error("Session file for '{id}' is corrupt and was moved to {path}")]
+    SessionCorrupt { id: String, path: String },
+
     #[error("Session name already exists: {name}")]
     SessionNameExists { name: String },
 
+    #[error("Session is being started by another bashlet process: {id}")]
+    SessionLocked { id: String },
+
     // IO errors
     #[error("IO error: {0}")]
     Io(#[from] std::io::Error),
@@ -100,6 +129,107 @@ impl BashletError {
     pub fn is_retryable(&self) -> bool {
         matches!(self, Self::RateLimited { .. } | Self::SandboxTimeout { .. })
     }
+
+    /// Variant name, stable for programmatic matching.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Self::Config(_) => "Config",
+            Self::ConfigNotFound { .. } => "ConfigNotFound",
+            Self::ProviderNotFound { .. } => "ProviderNotFound",
+            Self::ApiKeyMissing { .. } => "ApiKeyMissing",
+            Self::ProviderApi { .. } => "ProviderApi",
+            Self::RateLimited { .. } => "RateLimited",
+            Self::SandboxInit(_) => "SandboxInit",
+            Self::WasmCompilation(_) => "WasmCompilation",
+            Self::SandboxExecution(_) => "SandboxExecution",
+            Self::CommandRejected { .. } => "CommandRejected",
+            Self::SetupCommandFailed { .. } => "SetupCommandFailed",
+            Self::SandboxTimeout { .. } => "SandboxTimeout",
+            Self::MountPathNotFound { .. } => "MountPathNotFound",
+            Self::UnsafeMount { .. } => "UnsafeMount",
+            Self::GitClone { .. } => "GitClone",
+            Self::WasmNotFound { .. } => "WasmNotFound",
+            Self::BackendNotAvailable { .. } => "BackendNotAvailable",
+            Self::DockerImageUnavailable { .. } => "DockerImageUnavailable",
+            Self::FirecrackerApi { .. } => "FirecrackerApi",
+            Self::VMBootFailed(_) => "VMBootFailed",
+            Self::VMCommunication(_) => "VMCommunication",
+            Self::AssetDownload { .. } => "AssetDownload",
+            Self::Unsupported { .. } => "Unsupported",
+            Self::SessionNotFound { .. } => "SessionNotFound",
+            Self::PresetNotFound { .. } => "PresetNotFound",
+            Self::SessionExpired { .. } => "SessionExpired",
+            Self::SessionCorrupt { .. } => "SessionCorrupt",
+            Self::SessionNameExists { .. } => "SessionNameExists",
+            Self::SessionLocked { .. } => "SessionLocked",
+            Self::Io(_) => "Io",
+            Self::Json(_) => "Json",
+            Self::TomlParse(_) => "TomlParse",
+            Self::Http(_) => "Http",
+            Self::Other(_) => "Other",
+        }
+    }
+
+    /// Structured fields of the error; empty for message-only variants.
+    pub fn details(&self) -> serde_json::Value {
+        use serde_json::json;
+
+        match self {
+            Self::ConfigNotFound { path }
+            | Self::MountPathNotFound { path }
+            | Self::UnsafeMount { path }
+            | Self::WasmNotFound { path } => json!({ "path": path }),
+            Self::ProviderNotFound { provider } => json!({ "provider": provider }),
+            Self::ApiKeyMissing { provider, env_var } => {
+                json!({ "provider": provider, "env_var": env_var })
+            }
+            Self::ProviderApi { status, .. } | Self::FirecrackerApi { status, .. } => {
+                json!({ "status": status })
+            }
+            Self::RateLimited { retry_after } => json!({ "retry_after": retry_after }),
+            Self::SetupCommandFailed {
+                command,
+                exit_code,
+                output,
+            } => json!({ "command": command, "exit_code": exit_code, "output": output }),
+            Self::CommandRejected { command, reason } => {
+                json!({ "command": command, "reason": reason })
+            }
+            Self::SandboxTimeout { seconds } => json!({ "seconds": seconds }),
+            Self::BackendNotAvailable { backend, reason } => {
+                json!({ "backend": backend, "reason": reason })
+            }
+            Self::DockerImageUnavailable { image, reason } => {
+                json!({ "image": image, "reason": reason })
+            }
+            Self::AssetDownload { url } => json!({ "url": url }),
+            Self::GitClone { url, reason } => json!({ "url": url, "reason": reason }),
+            Self::Unsupported { operation, reason } => {
+                json!({ "operation": operation, "reason": reason })
+            }
+            Self::SessionNotFound { id }
+            | Self::SessionExpired { id }
+            | Self::SessionLocked { id } => json!({ "id": id }),
+            Self::SessionCorrupt { id, path } => json!({ "id": id, "path": path }),
+            Self::PresetNotFound { name } | Self::SessionNameExists { name } => {
+                json!({ "name": name })
+            }
+            Self::Io(e) => json!({ "io_kind": format!("{:?}", e.kind()) }),
+            _ => json!({}),
+        }
+    }
+
+    /// JSON representation printed for `--format json`:
+    /// `{"error": {"kind": ..., "message": ..., "details": {...}}}`.
+    pub fn to_json(&self) -> serde_json::Value {
+        serde_json::json!({
+            "error": {
+                "kind": self.kind(),
+                "message": self.to_string(),
+                "details": self.details(),
+            }
+        })
+    }
 }
 
 pub type Result<T> = std::result::Result<T, BashletError>;
@@ -1,23 +1,79 @@
+use std::path::Path;
+use std::sync::Mutex;
+
 use clap::Parser;
+use tracing_subscriber::prelude::*;
+use tracing_subscriber::{EnvFilter, Layer, Registry};
 
-use bashlet::cli::args::{Cli, Commands};
+use bashlet::cli::args::{Cli, Commands, OutputFormat};
 use bashlet::cli::commands;
 use bashlet::config::loader::load_config;
-use bashlet::error::Result;
+use bashlet::config::BashletConfig;
+use bashlet::error::{BashletError, Result};
 
 #[tokio::main]
-async fn main() -> Result<()> {
+async fn main() {
     let cli = Cli::parse();
 
+    let json_errors =
+        cli.global_opts.json_errors || matches!(cli.global_opts.format, OutputFormat::Json);
+
     // Initialize logging based on verbosity
-    init_logging(cli.global_opts.verbose);
+    let logging = init_logging(
+        cli.global_opts.verbose,
+        &cli.global_opts.log_format,
+        cli.global_opts.log_file.as_deref(),
+    );
+
+    if let Err(e) = match logging {
+        Ok(()) => run(cli).await,
+        Err(e) => Err(e),
+    } {
+        if json_errors {
+            eprintln!("{}", e.to_json());
+        } else {
+            eprintln!("Error: {}", e);
+        }
+        std::process::exit(1);
+    }
+}
+
+async fn run(cli: Cli) -> Result<()> {
+    // `doctor` reports a broken config file instead of failing on it
+    if let Commands::Doctor = cli.command {
+        return commands::doctor(
+            cli.global_opts.config.as_deref(),
+            cli.global_opts.offline,
+            cli.global_opts.format,
+        )
+        .await;
+    }
 
     // Load configuration (file + CLI overrides)
-    let config = load_config(cli.global_opts.config.as_deref())?;
+    let mut config = load_config(cli.global_opts.config.as_deref())?;
+    if cli.global_opts.offline {
+        config.sandbox.offline = true;
+    }
     let format = cli.global_opts.format.clone();
 
-    // Dispatch to subcommand handler
-    match cli.command {
+    // Dispatch to subcommand handler, shutting down any live sandbox on Ctrl-C
+    tokio::select! {
+        result = dispatch(cli.command, config, cli.global_opts.config.as_deref(), format) => result,
+        _ = tokio::signal::ctrl_c() => {
+            eprintln!("Interrupted, shutting down sandbox...");
+            commands::shutdown_active_backends().await;
+            std::process::exit(130);
+        }
+    }
+}
+
+async fn dispatch(
+    command: Commands,
+    config: BashletConfig,
+    config_path: Option<&Path>,
+    format: OutputFormat,
+) -> Result<()> {
+    match command {
         Commands::Create(args) => {
             commands::create(args, config, format).await?;
         }
@@ -25,7 +81,22 @@ async fn main() -> Result<()> {
             commands::run(args, config, format).await?;
         }
         Commands::Terminate(args) => {
-            commands::terminate(args, format).await?;
+            commands::terminate(args, config, format).await?;
+        }
+        Commands::Restart(args) => {
+            commands::restart(args, config, format).await?;
+        }
+        Commands::Commit(args) => {
+            commands::commit(args, format).await?;
+        }
+        Commands::Logs(args) => {
+            commands::logs(args).await?;
+        }
+        Commands::Shell(args) => {
+            commands::shell(args, config).await?;
+        }
+        Commands::Rm(args) => {
+            commands::rm(args, config, format).await?;
         }
         Commands::Exec(args) => {
             commands::exec(args, config, format).await?;
@@ -33,20 +104,35 @@ async fn main() -> Result<()> {
         Commands::List(args) => {
             commands::list(args, format).await?;
         }
+        Commands::Reap => {
+            commands::reap(config, format).await?;
+        }
         Commands::Init(args) => {
             commands::init(args).await?;
         }
         Commands::Config(args) => {
             commands::config(args, config).await?;
         }
+        Commands::Presets(args) => {
+            commands::presets(args, config, format).await?;
+        }
+        Commands::Cache(args) => {
+            commands::cache(args, format).await?;
+        }
+        Commands::Version(args) => {
+            commands::version(args, format).await?;
+        }
+        // Normally handled before the config is loaded, so a broken one is reported
+        Commands::Doctor => {
+            commands::doctor(config_path, config.sandbox.offline, format).await?;
+        }
     }
 
     Ok(())
 }
 
-fn init_logging(verbosity: u8) {
-    use tracing_subscriber::EnvFilter;
-
+/// Set up logging to stderr, or to `log_file` with only warnings on stderr.
+fn init_logging(verbosity: u8, format: &OutputFormat, log_file: Option<&Path>) -> Result<()> {
     let level = match verbosity {
         0 => "warn",
         1 => "info",
@@ -56,8 +142,50 @@ fn init_logging(verbosity: u8) {
 
     let filter = EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new(level));
 
-    tracing_subscriber::fmt()
-        .with_env_filter(filter)
-        .with_target(false)
-        .init();
+    let layers = match log_file {
+        None => vec![log_layer(format, std::io::stderr, true)
+            .with_filter(filter)
+            .boxed()],
+        Some(path) => {
+            let file = std::fs::OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(path)
+                .map_err(|e| {
+                    BashletError::Config(format!("Cannot open log file {}: {}", path.display(), e))
+                })?;
+
+            vec![
+                log_layer(format, std::io::stderr, true)
+                    .with_filter(EnvFilter::new("warn"))
+                    .boxed(),
+                log_layer(format, Mutex::new(file), false)
+                    .with_filter(filter)
+                    .boxed(),
+            ]
+        }
+    };
+
+    tracing_subscriber::registry().with(layers).init();
+    Ok(())
+}
+
+/// A text or JSON formatting layer writing to `writer`.
+fn log_layer<W>(
+    format: &OutputFormat,
+    writer: W,
+    ansi: bool,
+) -> Box<dyn Layer<Registry> + Send + Sync>
+where
+    W: for<'a> tracing_subscriber::fmt::MakeWriter<'a> + Send + Sync + 'static,
+{
+    let layer = tracing_subscriber::fmt::layer()
+        .with_writer(writer)
+        .with_ansi(ansi)
+        .with_target(false);
+
+    match format {
+        OutputFormat::Text => layer.boxed(),
+        OutputFormat::Json => layer.json().boxed(),
+    }
 }
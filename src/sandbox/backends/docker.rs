@@ -1,17 +1,25 @@
 use std::collections::HashMap;
-use std::path::PathBuf;
+use std::ffi::OsStr;
+use std::path::{Path, PathBuf};
 use std::process::Stdio;
 use std::sync::Mutex;
+use std::time::{Duration, Instant};
 
 use async_trait::async_trait;
 use tokio::process::Command;
 use tracing::{debug, info, warn};
 
 use crate::cli::args::Mount;
-use crate::config::types::DockerConfig;
+use crate::config::types::{DockerConfig, PullPolicy};
 use crate::error::{BashletError, Result};
-use crate::sandbox::traits::{BackendCapabilities, SandboxBackend, SandboxInfo};
-use crate::sandbox::CommandResult;
+use crate::sandbox::traits::{
+    BackendCapabilities, LastCommand, SandboxBackend, SandboxInfo, StartupTiming,
+};
+use crate::sandbox::output::capture_output;
+use crate::sandbox::{
+    resolve_path, shell_join, shell_quote, CommandResult, CommandTiming, NetworkSettings,
+    OutputFiles, TimingPhase,
+};
 
 /// Default Docker image name for the sandbox
 const DEFAULT_IMAGE: &str = "bashlet-sandbox:latest";
@@ -19,6 +27,28 @@ const DEFAULT_IMAGE: &str = "bashlet-sandbox:latest";
 /// Path to the Dockerfile relative to the crate root
 const DOCKERFILE_PATH: &str = "docker/Dockerfile.sandbox";
 
+/// Exit code of a process killed by SIGKILL, which is how the OOM killer ends it
+const SIGKILL_EXIT_CODE: i32 = 137;
+
+/// Exit code `docker run` uses when the failure is Docker's rather than the command's
+const DOCKER_ERROR_EXIT_CODE: i32 = 125;
+
+/// Attempts made for a `docker` invocation that fails with a transient error
+const DOCKER_RETRY_ATTEMPTS: u32 = 3;
+
+/// Delay before the first retry; doubled after each attempt
+const DOCKER_RETRY_DELAY: Duration = Duration::from_millis(500);
+
+/// Daemon errors that are worth retrying, e.g. while dockerd restarts
+const TRANSIENT_DOCKER_ERRORS: &[&str] = &[
+    "Cannot connect to the Docker daemon",
+    "error during connect",
+    "is already in use by container",
+    "connection reset by peer",
+    "i/o timeout",
+    "TLS handshake timeout",
+];
+
 /// Docker sandbox backend.
 ///
 /// Uses Docker containers to execute commands in an isolated environment.
@@ -29,23 +59,50 @@ pub struct DockerBackend {
     image: String,
     mounts: Vec<Mount>,
     env_vars: Vec<(String, String)>,
+    /// Passed through Docker's environment rather than its arguments
+    secret_env_vars: Vec<(String, String)>,
     workdir: String,
     memory_limit_mb: u64,
+    /// Total memory plus swap in MB (`--memory-swap`)
+    memory_swap_mb: Option<u64>,
+    /// Seccomp profile file applied with `--security-opt`
+    seccomp_profile: Option<PathBuf>,
+    /// AppArmor profile applied with `--security-opt`
+    apparmor_profile: Option<String>,
+    /// Unvalidated `docker run` arguments, added just before the image
+    extra_run_args: Vec<String>,
+    max_output_bytes: u64,
     enable_networking: bool,
+    /// DNS and host overrides (only applied with networking enabled)
+    network: NetworkSettings,
+    /// Interpreter for commands (`sh` in session mode, the image's entrypoint otherwise)
+    shell: Option<String>,
     /// Whether session mode is enabled (persistent container)
     session_mode: bool,
     /// Container ID when running in session mode (protected by Mutex for interior mutability)
     container_id: Mutex<Option<String>>,
+    /// Name of the in-flight stateless container, so it can be killed on shutdown
+    stateless_container: Mutex<Option<String>>,
+    last_command: LastCommand,
+    /// Container start time, reported with the first command
+    startup: StartupTiming,
 }
 
 impl DockerBackend {
     /// Create a new Docker backend.
+    #[allow(clippy::too_many_arguments)]
     pub async fn new(
         config: DockerConfig,
         mounts: Vec<Mount>,
         env_vars: Vec<(String, String)>,
+        secret_env_vars: Vec<(String, String)>,
         workdir: String,
         memory_limit_mb: u64,
+        max_output_bytes: u64,
+        container_id: Option<String>,
+        network: NetworkSettings,
+        offline: bool,
+        shell: Option<String>,
     ) -> Result<Self> {
         // Verify Docker is available
         if !Self::is_available() {
@@ -58,34 +115,79 @@ impl DockerBackend {
 
         let image = config.image.unwrap_or_else(|| DEFAULT_IMAGE.to_string());
 
+        // The bashlet image is built locally, so there is nothing to pull by default
+        let pull_policy = config.pull_policy.unwrap_or(if image == DEFAULT_IMAGE {
+            PullPolicy::Never
+        } else {
+            PullPolicy::Missing
+        });
+        Self::pull_image(&image, pull_policy, offline).await?;
+
         // Check if image exists, build if configured to do so
         if config.build_image && !Self::image_exists(&image).await {
             Self::build_image(&image).await?;
         } else if !Self::image_exists(&image).await {
-            return Err(BashletError::BackendNotAvailable {
-                backend: "docker".to_string(),
+            return Err(BashletError::DockerImageUnavailable {
                 reason: format!(
-                    "Docker image '{}' not found. Set build_image=true to auto-build, or build manually with: docker build -t {} -f {} .",
-                    image, image, DOCKERFILE_PATH
+                    "not found locally. Set build_image=true to auto-build, or build manually with: docker build -t {} -f {} .",
+                    image, DOCKERFILE_PATH
                 ),
+                image,
             });
         }
 
+        if let Some(ref profile) = config.seccomp_profile {
+            if !profile.is_file() {
+                return Err(BashletError::Config(format!(
+                    "Seccomp profile not found: {}",
+                    profile.display()
+                )));
+            }
+        }
+
+        if !config.enable_networking && !network.is_empty() {
+            warn!("DNS and host overrides are ignored because Docker networking is disabled");
+        }
+
         let session_mode = config.session_mode;
         let backend = Self {
             image,
             mounts,
             env_vars,
+            secret_env_vars,
             workdir,
             memory_limit_mb,
+            memory_swap_mb: config.memory_swap_mb,
+            seccomp_profile: config.seccomp_profile,
+            apparmor_profile: config.apparmor_profile,
+            extra_run_args: config.extra_run_args,
+            max_output_bytes,
             enable_networking: config.enable_networking,
+            network,
+            shell,
             session_mode,
             container_id: Mutex::new(None),
+            stateless_container: Mutex::new(None),
+            last_command: LastCommand::default(),
+            startup: StartupTiming::default(),
         };
 
-        // If session mode is enabled, start a persistent container
+        // If session mode is enabled, reattach to or start a persistent container
         if session_mode {
-            backend.start_session().await?;
+            match container_id {
+                Some(id) if Self::container_running(&id).await => {
+                    info!(container_id = %id, "Reattaching to Docker session container");
+                    backend.set_container_id(Some(id));
+                }
+                Some(id) if backend.restart_container(&id).await => {
+                    info!(container_id = %id, "Restarted stopped Docker session container");
+                }
+                Some(id) => {
+                    warn!(container_id = %id, "Session container is gone, starting a new one");
+                    backend.start_session().await?;
+                }
+                None => backend.start_session().await?,
+            }
         }
 
         info!(
@@ -100,17 +202,19 @@ impl DockerBackend {
     /// Start a persistent container for session mode.
     async fn start_session(&self) -> Result<()> {
         info!("Starting Docker session container...");
+        let started = Instant::now();
 
         let mut cmd = Command::new("docker");
         cmd.args(["run", "-d"]); // detached mode
 
-        // Network isolation
-        if !self.enable_networking {
-            cmd.arg("--network=none");
-        }
+        // Network isolation, or DNS/host overrides when networking is enabled
+        self.add_network_args(&mut cmd);
 
-        // Memory limit
-        cmd.arg(format!("--memory={}m", self.memory_limit_mb));
+        // Memory and swap limits
+        self.add_memory_args(&mut cmd);
+
+        // Seccomp/AppArmor profiles
+        self.add_security_args(&mut cmd);
 
         // Mount directories
         for mount in &self.mounts {
@@ -119,7 +223,7 @@ impl DockerBackend {
                     path: mount.host_path.display().to_string(),
                 });
             }
-            let mode = if mount.readonly { "ro" } else { "rw" };
+            let mode = mount_mode(mount);
             cmd.arg("-v");
             cmd.arg(format!(
                 "{}:{}:{}",
@@ -130,14 +234,14 @@ impl DockerBackend {
         }
 
         // Environment variables
-        for (key, value) in &self.env_vars {
-            cmd.arg("-e");
-            cmd.arg(format!("{}={}", key, value));
-        }
+        self.add_env_args(&mut cmd);
 
         // Working directory
         cmd.args(["-w", &self.workdir]);
 
+        // User-supplied flags bashlet doesn't model
+        cmd.args(&self.extra_run_args);
+
         // Image and command to keep container running
         cmd.arg(&self.image);
         cmd.args(["tail", "-f", "/dev/null"]);
@@ -145,17 +249,27 @@ impl DockerBackend {
         cmd.stdout(Stdio::piped());
         cmd.stderr(Stdio::piped());
 
-        let output = cmd.output().await.map_err(|e| {
-            BashletError::SandboxInit(format!("Failed to start Docker session: {}", e))
-        })?;
+        let mut attempt = 1;
+        let output = loop {
+            let output = cmd.output().await.map_err(|e| {
+                BashletError::SandboxInit(format!("Failed to start Docker session: {}", e))
+            })?;
+
+            if output.status.success() {
+                break output;
+            }
 
-        if !output.status.success() {
             let stderr = String::from_utf8_lossy(&output.stderr);
-            return Err(BashletError::SandboxInit(format!(
-                "Failed to start Docker session: {}",
-                stderr
-            )));
-        }
+            if attempt >= DOCKER_RETRY_ATTEMPTS || !is_transient_docker_error(&stderr) {
+                return Err(BashletError::SandboxInit(format!(
+                    "Failed to start Docker session: {}",
+                    stderr
+                )));
+            }
+
+            retry_delay(attempt, &stderr).await;
+            attempt += 1;
+        };
 
         let container_id = String::from_utf8_lossy(&output.stdout).trim().to_string();
         info!(container_id = %container_id, "Docker session container started");
@@ -165,6 +279,144 @@ impl DockerBackend {
             BashletError::SandboxInit(format!("Failed to acquire lock: {}", e))
         })?;
         *id_lock = Some(container_id);
+        self.startup.record(TimingPhase::since("container_start", started));
+
+        Ok(())
+    }
+
+    /// Check whether a container exists and is running.
+    async fn container_running(container_id: &str) -> bool {
+        match Command::new("docker")
+            .args(["inspect", "-f", "{{.State.Running}}", container_id])
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .output()
+            .await
+        {
+            Ok(output) => {
+                output.status.success() && String::from_utf8_lossy(&output.stdout).trim() == "true"
+            }
+            Err(_) => false,
+        }
+    }
+
+    /// Start a stopped session container again, keeping its filesystem.
+    ///
+    /// Returns false if it can't be started, e.g. because it was removed.
+    async fn restart_container(&self, container_id: &str) -> bool {
+        let started = Instant::now();
+        let restarted = Command::new("docker")
+            .args(["start", container_id])
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .status()
+            .await
+            .is_ok_and(|status| status.success());
+
+        if restarted {
+            self.set_container_id(Some(container_id.to_string()));
+            self.startup
+                .record(TimingPhase::since("container_start", started));
+        }
+        restarted
+    }
+
+    /// Stop a session container by ID, keeping it so it can be restarted.
+    pub async fn stop_idle_container(container_id: &str) -> Result<()> {
+        info!(container_id = %container_id, "Stopping idle Docker session container");
+
+        let output = Command::new("docker")
+            .args(["stop", container_id])
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .output()
+            .await
+            .map_err(|e| {
+                BashletError::SandboxExecution(format!("Failed to stop container: {}", e))
+            })?;
+
+        if !output.status.success() {
+            return Err(BashletError::SandboxExecution(format!(
+                "Failed to stop container: {}",
+                String::from_utf8_lossy(&output.stderr).trim()
+            )));
+        }
+
+        Ok(())
+    }
+
+    /// Force-remove a session container by ID.
+    ///
+    /// Used when terminating a session whose container outlived the process
+    /// that created it. A container that is already gone is not an error.
+    pub async fn remove_container(container_id: &str) -> Result<()> {
+        info!(container_id = %container_id, "Removing Docker session container");
+
+        let output = Command::new("docker")
+            .args(["rm", "-f", container_id])
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .output()
+            .await
+            .map_err(|e| {
+                BashletError::SandboxExecution(format!("Failed to remove container: {}", e))
+            })?;
+
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            warn!(container_id = %container_id, error = %stderr, "Failed to remove container");
+        }
+
+        Ok(())
+    }
+
+    /// Snapshot a container's filesystem as a new image via `docker commit`.
+    ///
+    /// Returns the ID of the created image.
+    pub async fn commit_container(container_id: &str, image: &str) -> Result<String> {
+        info!(container_id = %container_id, image = %image, "Committing Docker container");
+
+        let output = Command::new("docker")
+            .args(["commit", container_id, image])
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .output()
+            .await
+            .map_err(|e| {
+                BashletError::SandboxExecution(format!("Failed to run docker commit: {}", e))
+            })?;
+
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            return Err(BashletError::SandboxExecution(format!(
+                "Failed to commit container {}: {}",
+                container_id,
+                stderr.trim()
+            )));
+        }
+
+        Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+    }
+
+    /// Stream a container's logs to this process's stdout/stderr via `docker logs`.
+    pub async fn stream_logs(container_id: &str, follow: bool) -> Result<()> {
+        let mut cmd = Command::new("docker");
+        cmd.arg("logs");
+        if follow {
+            cmd.arg("--follow");
+        }
+        cmd.arg(container_id);
+
+        let status = cmd.status().await.map_err(|e| {
+            BashletError::SandboxExecution(format!("Failed to run docker logs: {}", e))
+        })?;
+
+        if !status.success() {
+            return Err(BashletError::SandboxExecution(format!(
+                "docker logs failed for container {}",
+                container_id
+            )));
+        }
 
         Ok(())
     }
@@ -199,12 +451,60 @@ impl DockerBackend {
         }
     }
 
+    /// Pull the image if the pull policy asks for it.
+    ///
+    /// A failed pull under `Missing` is left to the image check that follows,
+    /// which may still build the image; under `Always` it is an error.
+    async fn pull_image(image: &str, policy: PullPolicy, offline: bool) -> Result<()> {
+        let pull = match policy {
+            PullPolicy::Always => true,
+            PullPolicy::Missing => !Self::image_exists(image).await,
+            PullPolicy::Never => false,
+        };
+        if !pull {
+            return Ok(());
+        }
+        if offline {
+            debug!(image = %image, "Not pulling Docker image in offline mode");
+            return Ok(());
+        }
+
+        info!(image = %image, "Pulling Docker image...");
+        let output = Command::new("docker")
+            .args(["pull", image])
+            .stdout(Stdio::null())
+            .stderr(Stdio::piped())
+            .output()
+            .await
+            .map_err(|e| BashletError::SandboxInit(format!("Failed to run docker pull: {}", e)))?;
+
+        if output.status.success() {
+            return Ok(());
+        }
+
+        let stderr = String::from_utf8_lossy(&output.stderr).trim().to_string();
+        match policy {
+            PullPolicy::Always => Err(BashletError::DockerImageUnavailable {
+                image: image.to_string(),
+                reason: format!("docker pull failed: {}", stderr),
+            }),
+            _ => {
+                warn!(image = %image, error = %stderr, "Failed to pull Docker image");
+                Ok(())
+            }
+        }
+    }
+
     /// Build the Docker image from the Dockerfile.
     async fn build_image(image: &str) -> Result<()> {
         info!(image = %image, "Building Docker sandbox image...");
 
         // Find the Dockerfile and determine the build context directory
-        let dockerfile = Self::find_dockerfile()?;
+        let dockerfile =
+            Self::find_dockerfile().map_err(|reason| BashletError::DockerImageUnavailable {
+                image: image.to_string(),
+                reason,
+            })?;
 
         // Canonicalize to get absolute path, then determine project root
         let dockerfile_abs = dockerfile.canonicalize().map_err(|e| {
@@ -248,7 +548,7 @@ impl DockerBackend {
     }
 
     /// Find the Dockerfile in common locations.
-    fn find_dockerfile() -> Result<PathBuf> {
+    fn find_dockerfile() -> std::result::Result<PathBuf, String> {
         // Check relative to current directory
         let local_path = PathBuf::from(DOCKERFILE_PATH);
         if local_path.exists() {
@@ -288,43 +588,37 @@ impl DockerBackend {
             }
         }
 
-        Err(BashletError::SandboxInit(format!(
+        Err(format!(
             "Dockerfile not found at {}. Build the image manually with: docker build -t {} -f {} .",
             DOCKERFILE_PATH, DEFAULT_IMAGE, DOCKERFILE_PATH
-        )))
+        ))
     }
 
-    /// Execute a command in session mode using docker exec.
-    async fn execute_in_session(&self, container_id: &str, command: &str) -> Result<CommandResult> {
-        debug!(container_id = %container_id, command = %command, "Executing via docker exec");
+    /// Execute a program in session mode using docker exec.
+    async fn execute_in_session(
+        &self,
+        container_id: &str,
+        argv: &[&str],
+        stdin: Option<&[u8]>,
+        files: &OutputFiles,
+    ) -> Result<CommandResult> {
+        debug!(container_id = %container_id, argv = ?argv, "Executing via docker exec");
+        let started = Instant::now();
 
-        let mut cmd = Command::new("docker");
-        cmd.args(["exec"]);
-
-        // Working directory
-        cmd.args(["-w", &self.workdir]);
-
-        // Environment variables
-        for (key, value) in &self.env_vars {
-            cmd.arg("-e");
-            cmd.arg(format!("{}={}", key, value));
-        }
-
-        // Container ID and command
-        cmd.arg(container_id);
-        cmd.args(["sh", "-c", command]);
+        let mut cmd = self.exec_command(container_id, Attach::from_stdin(stdin));
+        cmd.args(argv);
 
         cmd.stdin(Stdio::null());
-        cmd.stdout(Stdio::piped());
-        cmd.stderr(Stdio::piped());
 
-        let output = cmd.output().await.map_err(|e| {
-            BashletError::SandboxExecution(format!("Failed to execute docker exec: {}", e))
-        })?;
+        let output = capture_output(&mut cmd, self.max_output_bytes, stdin, files)
+            .await
+            .map_err(|e| {
+                BashletError::SandboxExecution(format!("Failed to execute docker exec: {}", e))
+            })?;
 
-        let stdout = String::from_utf8_lossy(&output.stdout).to_string();
-        let stderr = String::from_utf8_lossy(&output.stderr).to_string();
+        let stdout = output.stdout;
         let exit_code = output.status.code().unwrap_or(1);
+        let stderr = self.annotate_oom(output.stderr, exit_code);
 
         debug!(
             exit_code = exit_code,
@@ -337,21 +631,161 @@ impl DockerBackend {
             stdout,
             stderr,
             exit_code,
+            truncated: output.truncated,
+            timing: Some(CommandTiming::since(started, self.startup.take())),
         })
     }
 
     /// Execute a command in stateless mode using docker run --rm.
-    async fn execute_stateless(&self, command: &str) -> Result<CommandResult> {
-        let mut cmd = Command::new("docker");
-        cmd.args(["run", "--rm"]);
+    async fn execute_stateless(
+        &self,
+        command: &str,
+        stdin: Option<&[u8]>,
+        files: &OutputFiles,
+    ) -> Result<CommandResult> {
+        let started = Instant::now();
+        let mut attempt = 1;
+        let output = loop {
+            // A fresh name each attempt, in case the last one is still registered
+            let container_name = generate_container_name();
+
+            let mut cmd = self.run_command(&container_name, Attach::from_stdin(stdin))?;
+            cmd.arg(command);
+
+            cmd.stdin(Stdio::null());
+
+            if let Ok(mut lock) = self.stateless_container.lock() {
+                *lock = Some(container_name);
+            }
 
-        // Network isolation
-        if !self.enable_networking {
-            cmd.arg("--network=none");
+            let output = capture_output(&mut cmd, self.max_output_bytes, stdin, files).await;
+
+            if let Ok(mut lock) = self.stateless_container.lock() {
+                *lock = None;
+            }
+
+            let output = output.map_err(|e| {
+                BashletError::SandboxExecution(format!("Failed to execute docker run: {}", e))
+            })?;
+
+            // Only retry failures reported by Docker itself, never the command's own exit
+            if attempt >= DOCKER_RETRY_ATTEMPTS
+                || output.status.code() != Some(DOCKER_ERROR_EXIT_CODE)
+                || !is_transient_docker_error(&output.stderr)
+            {
+                break output;
+            }
+
+            retry_delay(attempt, &output.stderr).await;
+            attempt += 1;
+        };
+
+        let stdout = output.stdout;
+        let exit_code = output.status.code().unwrap_or(1);
+        let stderr = self.annotate_oom(output.stderr, exit_code);
+
+        debug!(
+            exit_code = exit_code,
+            stdout_len = stdout.len(),
+            stderr_len = stderr.len(),
+            "Command completed (stateless mode)"
+        );
+
+        Ok(CommandResult {
+            stdout,
+            stderr,
+            exit_code,
+            truncated: output.truncated,
+            timing: Some(CommandTiming::since(started, Vec::new())),
+        })
+    }
+
+    /// Add `-e` flags for the environment variables.
+    ///
+    /// Secrets are passed as a bare `-e KEY`, which makes Docker copy the value
+    /// from its own environment, so it never appears in the process arguments.
+    fn add_env_args(&self, cmd: &mut Command) {
+        for (key, value) in &self.env_vars {
+            cmd.arg("-e");
+            cmd.arg(format!("{}={}", key, value));
+        }
+        for (key, value) in &self.secret_env_vars {
+            cmd.env(key, value);
+            cmd.args(["-e", key]);
         }
+    }
 
-        // Memory limit
+    /// Add `--memory` and, if configured, `--memory-swap`.
+    fn add_memory_args(&self, cmd: &mut Command) {
         cmd.arg(format!("--memory={}m", self.memory_limit_mb));
+        if let Some(swap) = self.memory_swap_mb {
+            cmd.arg(format!("--memory-swap={}m", swap));
+        }
+    }
+
+    /// Add `--security-opt` flags for configured seccomp and AppArmor profiles.
+    fn add_security_args(&self, cmd: &mut Command) {
+        if let Some(ref profile) = self.seccomp_profile {
+            cmd.arg("--security-opt");
+            cmd.arg(format!("seccomp={}", profile.display()));
+        }
+        if let Some(ref profile) = self.apparmor_profile {
+            cmd.arg("--security-opt");
+            cmd.arg(format!("apparmor={}", profile));
+        }
+    }
+
+    /// Explain a SIGKILL exit, which otherwise shows up as 137 with no output.
+    fn annotate_oom(&self, mut stderr: String, exit_code: i32) -> String {
+        if exit_code == SIGKILL_EXIT_CODE {
+            if !stderr.is_empty() && !stderr.ends_with('\n') {
+                stderr.push('\n');
+            }
+            stderr.push_str(&format!(
+                "[bashlet: exit 137, likely out-of-memory (killed); memory limit is {} MB]\n",
+                self.memory_limit_mb
+            ));
+        }
+        stderr
+    }
+
+    /// Build a `docker exec` invocation for the session container, up to the command.
+    fn exec_command(&self, container_id: &str, attach: Attach) -> Command {
+        let mut cmd = Command::new("docker");
+        cmd.args(["exec"]);
+        attach.add_args(&mut cmd);
+
+        // Working directory
+        cmd.args(["-w", &self.workdir]);
+
+        // Environment variables
+        self.add_env_args(&mut cmd);
+
+        cmd.arg(container_id);
+        cmd
+    }
+
+    /// Shell `docker exec` runs commands with.
+    fn session_shell(&self) -> &str {
+        self.shell.as_deref().unwrap_or("sh")
+    }
+
+    /// Build a stateless `docker run --rm` invocation, up to the command.
+    ///
+    /// The image's entrypoint takes the command, unless `shell` replaces it.
+    fn run_command(&self, container_name: &str, attach: Attach) -> Result<Command> {
+        let mut cmd = Command::new("docker");
+        cmd.args(["run", "--rm", "--name", container_name]);
+        attach.add_args(&mut cmd);
+
+        // Network isolation, or DNS/host overrides when networking is enabled
+        self.add_network_args(&mut cmd);
+
+        // Memory and swap limits
+        self.add_memory_args(&mut cmd);
+
+        // Seccomp/AppArmor profiles
+        self.add_security_args(&mut cmd);
 
         // Mount directories
         for mount in &self.mounts {
@@ -360,7 +794,7 @@ impl DockerBackend {
                     path: mount.host_path.display().to_string(),
                 });
             }
-            let mode = if mount.readonly { "ro" } else { "rw" };
+            let mode = mount_mode(mount);
             cmd.arg("-v");
             cmd.arg(format!(
                 "{}:{}:{}",
@@ -371,42 +805,37 @@ impl DockerBackend {
         }
 
         // Environment variables
-        for (key, value) in &self.env_vars {
-            cmd.arg("-e");
-            cmd.arg(format!("{}={}", key, value));
-        }
+        self.add_env_args(&mut cmd);
 
         // Working directory
         cmd.args(["-w", &self.workdir]);
 
-        // Image and command
-        cmd.arg(&self.image);
-        cmd.arg(command);
-
-        cmd.stdin(Stdio::null());
-        cmd.stdout(Stdio::piped());
-        cmd.stderr(Stdio::piped());
+        // User-supplied flags bashlet doesn't model
+        cmd.args(&self.extra_run_args);
 
-        let output = cmd.output().await.map_err(|e| {
-            BashletError::SandboxExecution(format!("Failed to execute docker run: {}", e))
-        })?;
-
-        let stdout = String::from_utf8_lossy(&output.stdout).to_string();
-        let stderr = String::from_utf8_lossy(&output.stderr).to_string();
-        let exit_code = output.status.code().unwrap_or(1);
-
-        debug!(
-            exit_code = exit_code,
-            stdout_len = stdout.len(),
-            stderr_len = stderr.len(),
-            "Command completed (stateless mode)"
-        );
+        if let Some(ref shell) = self.shell {
+            cmd.args(["--entrypoint", shell]);
+            cmd.arg(&self.image);
+            cmd.arg("-c");
+        } else {
+            cmd.arg(&self.image);
+        }
+        Ok(cmd)
+    }
 
-        Ok(CommandResult {
-            stdout,
-            stderr,
-            exit_code,
-        })
+    /// Add `docker run` networking flags.
+    fn add_network_args(&self, cmd: &mut Command) {
+        if !self.enable_networking {
+            cmd.arg("--network=none");
+            return;
+        }
+        for dns in &self.network.dns {
+            cmd.args(["--dns", dns]);
+        }
+        for (host, ip) in &self.network.extra_hosts {
+            cmd.arg("--add-host");
+            cmd.arg(format!("{}:{}", host, ip));
+        }
     }
 
     /// Get the current container ID if in session mode.
@@ -414,6 +843,47 @@ impl DockerBackend {
         self.container_id.lock().ok().and_then(|guard| guard.clone())
     }
 
+    /// Session container to copy files to or from.
+    ///
+    /// Stateless containers are removed after each command, so copying needs a session.
+    fn copy_container_id(&self) -> Result<String> {
+        self.get_container_id()
+            .ok_or_else(|| BashletError::Unsupported {
+                operation: "copy".to_string(),
+                reason: "files can only be copied to or from a session container".to_string(),
+            })
+    }
+
+    /// Run `docker cp SOURCE DEST`.
+    async fn docker_cp(source: impl AsRef<OsStr>, dest: impl AsRef<OsStr>) -> Result<()> {
+        let output = Command::new("docker")
+            .arg("cp")
+            .arg(source)
+            .arg(dest)
+            .stdin(Stdio::null())
+            .output()
+            .await
+            .map_err(|e| {
+                BashletError::SandboxExecution(format!("Failed to run docker cp: {}", e))
+            })?;
+
+        if !output.status.success() {
+            return Err(BashletError::SandboxExecution(format!(
+                "docker cp failed: {}",
+                String::from_utf8_lossy(&output.stderr).trim()
+            )));
+        }
+
+        Ok(())
+    }
+
+    /// Set the session container ID.
+    fn set_container_id(&self, container_id: Option<String>) {
+        if let Ok(mut lock) = self.container_id.lock() {
+            *lock = container_id;
+        }
+    }
+
     /// Stop and remove the session container.
     async fn stop_container(&self, container_id: &str) -> Result<()> {
         info!(container_id = %container_id, "Stopping Docker session container...");
@@ -471,22 +941,97 @@ impl SandboxBackend for DockerBackend {
 
     async fn execute(&self, command: &str) -> Result<CommandResult> {
         debug!(command = %command, "Executing command in Docker sandbox");
+        self.last_command.record();
 
         // Check if we're in session mode with an active container
         let container_id = self.get_container_id();
 
         if let Some(ref cid) = container_id {
             // Session mode: use docker exec
-            self.execute_in_session(cid, command).await
+            self.execute_in_session(
+                cid,
+                &[self.session_shell(), "-c", command],
+                None,
+                &OutputFiles::default(),
+            )
+            .await
         } else {
             // Stateless mode: use docker run --rm
-            self.execute_stateless(command).await
+            self.execute_stateless(command, None, &OutputFiles::default())
+                .await
+        }
+    }
+
+    async fn execute_argv(&self, argv: &[String]) -> Result<CommandResult> {
+        // Stateless containers go through the image's shell entrypoint, so
+        // only session mode can skip the shell
+        match self.get_container_id() {
+            Some(cid) => {
+                self.last_command.record();
+                let argv: Vec<&str> = argv.iter().map(String::as_str).collect();
+                self.execute_in_session(&cid, &argv, None, &OutputFiles::default())
+                    .await
+            }
+            None => self.execute(&shell_join(argv)).await,
         }
     }
 
+    async fn execute_with_stdin(&self, command: &str, stdin: &[u8]) -> Result<CommandResult> {
+        self.execute_to_files(command, Some(stdin), &OutputFiles::default())
+            .await
+    }
+
+    async fn execute_to_files(
+        &self,
+        command: &str,
+        stdin: Option<&[u8]>,
+        files: &OutputFiles,
+    ) -> Result<CommandResult> {
+        self.last_command.record();
+        match self.get_container_id() {
+            Some(cid) => {
+                self.execute_in_session(&cid, &[self.session_shell(), "-c", command], stdin, files)
+                    .await
+            }
+            None => self.execute_stateless(command, stdin, files).await,
+        }
+    }
+
+    async fn execute_interactive(&self, command: &str) -> Result<i32> {
+        self.last_command.record();
+        let status = match self.get_container_id() {
+            Some(container_id) => {
+                let mut cmd = self.exec_command(&container_id, Attach::Tty);
+                cmd.args([self.session_shell(), "-c", command]);
+                cmd.status().await
+            }
+            None => {
+                let container_name = generate_container_name();
+                let mut cmd = self.run_command(&container_name, Attach::Tty)?;
+                cmd.arg(command);
+
+                if let Ok(mut lock) = self.stateless_container.lock() {
+                    *lock = Some(container_name);
+                }
+                let status = cmd.status().await;
+                if let Ok(mut lock) = self.stateless_container.lock() {
+                    *lock = None;
+                }
+                status
+            }
+        }
+        .map_err(|e| BashletError::SandboxExecution(format!("Failed to run docker: {}", e)))?;
+
+        Ok(status.code().unwrap_or(1))
+    }
+
     async fn write_file(&self, path: &str, content: &str) -> Result<()> {
-        let escaped = content.replace('\\', "\\\\").replace('\'', "'\"'\"'");
-        let cmd = format!("printf '%s' '{}' > '{}'", escaped, path);
+        let path = &resolve_path(&self.workdir, path);
+        let cmd = format!(
+            "printf '%s' {} > {}",
+            shell_quote(content),
+            shell_quote(path)
+        );
         let result = self.execute(&cmd).await?;
 
         if result.exit_code != 0 {
@@ -500,7 +1045,8 @@ impl SandboxBackend for DockerBackend {
     }
 
     async fn read_file(&self, path: &str) -> Result<String> {
-        let result = self.execute(&format!("cat '{}'", path)).await?;
+        let path = &resolve_path(&self.workdir, path);
+        let result = self.execute(&format!("cat {}", shell_quote(path))).await?;
 
         if result.exit_code != 0 {
             return Err(BashletError::SandboxExecution(format!(
@@ -513,7 +1059,8 @@ impl SandboxBackend for DockerBackend {
     }
 
     async fn list_dir(&self, path: &str) -> Result<String> {
-        let result = self.execute(&format!("ls -la '{}'", path)).await?;
+        let path = &resolve_path(&self.workdir, path);
+        let result = self.execute(&format!("ls -la {}", shell_quote(path))).await?;
 
         if result.exit_code != 0 {
             return Err(BashletError::SandboxExecution(format!(
@@ -525,12 +1072,30 @@ impl SandboxBackend for DockerBackend {
         Ok(result.stdout)
     }
 
+    async fn copy_in(&self, host: &Path, guest: &str) -> Result<()> {
+        let container_id = self.copy_container_id()?;
+        let guest = resolve_path(&self.workdir, guest);
+        Self::docker_cp(host, format!("{}:{}", container_id, guest)).await
+    }
+
+    async fn copy_out(&self, guest: &str, host: &Path) -> Result<()> {
+        let container_id = self.copy_container_id()?;
+        let guest = resolve_path(&self.workdir, guest);
+        Self::docker_cp(format!("{}:{}", container_id, guest), host).await
+    }
+
+    fn persistent_instance_id(&self) -> Option<String> {
+        self.get_container_id()
+    }
+
     fn info(&self) -> SandboxInfo {
         let container_id = self.get_container_id();
         SandboxInfo {
             backend_type: "docker".to_string(),
             instance_id: container_id.clone(),
             running: !self.session_mode || container_id.is_some(),
+            last_command_at: self.last_command.get(),
+            last_health_check: None,
             metadata: HashMap::from([
                 ("image".to_string(), self.image.clone()),
                 ("workdir".to_string(), self.workdir.clone()),
@@ -544,6 +1109,22 @@ impl SandboxBackend for DockerBackend {
     }
 
     async fn shutdown(&self) -> Result<()> {
+        // Kill a stateless container that is still running (e.g. on Ctrl-C)
+        let stateless = self
+            .stateless_container
+            .lock()
+            .ok()
+            .and_then(|mut guard| guard.take());
+        if let Some(name) = stateless {
+            info!(container = %name, "Killing running Docker container");
+            let _ = Command::new("docker")
+                .args(["rm", "-f", &name])
+                .stdout(Stdio::null())
+                .stderr(Stdio::null())
+                .status()
+                .await;
+        }
+
         if let Some(container_id) = self.get_container_id() {
             self.stop_container(&container_id).await?;
 
@@ -554,4 +1135,112 @@ impl SandboxBackend for DockerBackend {
         }
         Ok(())
     }
+
+    async fn health_check(&self) -> Result<bool> {
+        // A session container can be checked without running anything in it
+        match self.get_container_id() {
+            Some(container_id) => Ok(Self::container_running(&container_id).await),
+            None => match self
+                .execute_stateless("echo ok", None, &OutputFiles::default())
+                .await
+            {
+                Ok(result) => Ok(result.exit_code == 0),
+                Err(_) => Ok(false),
+            },
+        }
+    }
+}
+
+/// How a `docker exec`/`docker run` invocation is attached to our stdin.
+#[derive(Debug, Clone, Copy)]
+enum Attach {
+    /// No stdin
+    None,
+    /// Piped input (`-i`)
+    Stdin,
+    /// Interactive terminal (`-it`)
+    Tty,
+}
+
+impl Attach {
+    fn from_stdin(stdin: Option<&[u8]>) -> Self {
+        if stdin.is_some() {
+            Attach::Stdin
+        } else {
+            Attach::None
+        }
+    }
+
+    fn add_args(self, cmd: &mut Command) {
+        match self {
+            Attach::None => {}
+            Attach::Stdin => {
+                cmd.arg("-i");
+            }
+            Attach::Tty => {
+                cmd.arg("-it");
+            }
+        }
+    }
+}
+
+/// Whether a `docker` error message describes a transient daemon failure.
+fn is_transient_docker_error(stderr: &str) -> bool {
+    TRANSIENT_DOCKER_ERRORS
+        .iter()
+        .any(|pattern| stderr.contains(pattern))
+}
+
+/// Log a transient failure and wait before the next attempt.
+async fn retry_delay(attempt: u32, stderr: &str) {
+    let delay = DOCKER_RETRY_DELAY * 2u32.pow(attempt - 1);
+    warn!(
+        attempt = attempt,
+        delay_ms = delay.as_millis() as u64,
+        error = %stderr.trim(),
+        "Transient Docker error, retrying"
+    );
+    tokio::time::sleep(delay).await;
+}
+
+/// Generate a unique name for a stateless container.
+fn generate_container_name() -> String {
+    use std::sync::atomic::{AtomicU32, Ordering};
+    static COUNTER: AtomicU32 = AtomicU32::new(0);
+
+    format!(
+        "bashlet-{}-{}",
+        std::process::id(),
+        COUNTER.fetch_add(1, Ordering::SeqCst)
+    )
+}
+
+/// Build the `-v` mode string for a mount, e.g. `ro,z`.
+fn mount_mode(mount: &Mount) -> String {
+    let access = if mount.readonly { "ro" } else { "rw" };
+    std::iter::once(access)
+        .chain(mount.options.iter().map(String::as_str))
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_transient_docker_error() {
+        assert!(is_transient_docker_error(
+            "docker: Cannot connect to the Docker daemon at unix:///var/run/docker.sock. \
+             Is the docker daemon running?"
+        ));
+        assert!(is_transient_docker_error(
+            "docker: Error response from daemon: Conflict. The container name \"/bashlet-1-0\" \
+             is already in use by container \"abc\"."
+        ));
+        assert!(!is_transient_docker_error(
+            "docker: Error response from daemon: pull access denied for nope"
+        ));
+        assert!(!is_transient_docker_error(""));
+    }
 }
@@ -1,11 +1,11 @@
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
 use http_body_util::{BodyExt, Full};
 use hyper::body::Bytes;
-use hyper::{Method, Request, StatusCode};
+use hyper::{Method, Request};
 use hyper_util::client::legacy::Client;
 use hyper_util::rt::TokioExecutor;
-use serde::{Deserialize, Serialize};
+use serde::Serialize;
 use tracing::debug;
 
 use crate::error::{BashletError, Result};
@@ -23,17 +23,17 @@ pub struct FirecrackerApiClient {
 impl FirecrackerApiClient {
     /// Create a new API client connected to the Firecracker socket.
     #[cfg(target_os = "linux")]
-    pub async fn new(socket_path: &PathBuf) -> Result<Self> {
+    pub async fn new(socket_path: &Path) -> Result<Self> {
         let client = Client::builder(TokioExecutor::new()).build(hyperlocal::UnixConnector);
 
         Ok(Self {
-            socket_path: socket_path.clone(),
+            socket_path: socket_path.to_path_buf(),
             client,
         })
     }
 
     #[cfg(not(target_os = "linux"))]
-    pub async fn new(_socket_path: &PathBuf) -> Result<Self> {
+    pub async fn new(_socket_path: &Path) -> Result<Self> {
         Err(BashletError::BackendNotAvailable {
             backend: "firecracker".to_string(),
             reason: "Firecracker is only available on Linux".to_string(),
@@ -62,7 +62,7 @@ impl FirecrackerApiClient {
 
         let req = Request::builder()
             .method(method)
-            .uri(uri.to_string())
+            .uri(hyper::Uri::from(uri))
             .header("Content-Type", "application/json")
             .header("Accept", "application/json")
             .body(Full::new(body_bytes))
@@ -91,7 +91,8 @@ impl FirecrackerApiClient {
                     message: format!("Failed to read error response: {}", e),
                     status: Some(status.as_u16()),
                 })?;
-            let error_body = String::from_utf8_lossy(&body.to_bytes());
+            let body_bytes = body.to_bytes();
+            let error_body = String::from_utf8_lossy(&body_bytes);
             return Err(BashletError::FirecrackerApi {
                 message: format!("API error: {}", error_body),
                 status: Some(status.as_u16()),
@@ -115,7 +116,7 @@ impl FirecrackerApiClient {
     }
 
     /// Configure the boot source (kernel image).
-    pub async fn put_boot_source(&self, kernel_path: &PathBuf, boot_args: &str) -> Result<()> {
+    pub async fn put_boot_source(&self, kernel_path: &Path, boot_args: &str) -> Result<()> {
         #[derive(Serialize)]
         struct BootSource {
             kernel_image_path: String,
@@ -153,12 +154,7 @@ impl FirecrackerApiClient {
     }
 
     /// Add a drive (rootfs or data disk).
-    pub async fn put_drive(
-        &self,
-        drive_id: &str,
-        path: &PathBuf,
-        is_read_only: bool,
-    ) -> Result<()> {
+    pub async fn put_drive(&self, drive_id: &str, path: &Path, is_read_only: bool) -> Result<()> {
         #[derive(Serialize)]
         struct Drive {
             drive_id: String,
@@ -181,7 +177,7 @@ impl FirecrackerApiClient {
     }
 
     /// Configure vsock device for guest-host communication.
-    pub async fn put_vsock(&self, guest_cid: u32, uds_path: &PathBuf) -> Result<()> {
+    pub async fn put_vsock(&self, guest_cid: u32, uds_path: &Path) -> Result<()> {
         #[derive(Serialize)]
         struct Vsock {
             guest_cid: u32,
@@ -215,58 +211,4 @@ impl FirecrackerApiClient {
         )
         .await
     }
-
-    /// Get instance information.
-    #[cfg(target_os = "linux")]
-    pub async fn get_info(&self) -> Result<InstanceInfo> {
-        let uri = hyperlocal::Uri::new(&self.socket_path, "/");
-
-        let req = Request::builder()
-            .method(Method::GET)
-            .uri(uri.to_string())
-            .header("Accept", "application/json")
-            .body(Full::new(Bytes::new()))
-            .map_err(|e| BashletError::FirecrackerApi {
-                message: format!("Failed to build request: {}", e),
-                status: None,
-            })?;
-
-        let response =
-            self.client
-                .request(req)
-                .await
-                .map_err(|e| BashletError::FirecrackerApi {
-                    message: format!("Request failed: {}", e),
-                    status: None,
-                })?;
-
-        let body = response
-            .collect()
-            .await
-            .map_err(|e| BashletError::FirecrackerApi {
-                message: format!("Failed to read response: {}", e),
-                status: None,
-            })?;
-
-        serde_json::from_slice(&body.to_bytes()).map_err(|e| BashletError::FirecrackerApi {
-            message: format!("Failed to parse response: {}", e),
-            status: None,
-        })
-    }
-
-    #[cfg(not(target_os = "linux"))]
-    pub async fn get_info(&self) -> Result<InstanceInfo> {
-        Err(BashletError::BackendNotAvailable {
-            backend: "firecracker".to_string(),
-            reason: "Firecracker is only available on Linux".to_string(),
-        })
-    }
-}
-
-/// Instance information from Firecracker.
-#[derive(Debug, Deserialize)]
-pub struct InstanceInfo {
-    pub id: String,
-    pub state: String,
-    pub vmm_version: String,
 }
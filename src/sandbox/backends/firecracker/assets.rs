@@ -1,12 +1,15 @@
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use std::sync::OnceLock;
 
-use tracing::{info, warn};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tracing::{debug, info, warn};
 
-use crate::config::loader::get_cache_dir;
 use crate::error::{BashletError, Result};
+use crate::sandbox::cache;
 
 /// Firecracker release version to download.
-const FIRECRACKER_VERSION: &str = "v1.10.1";
+pub(crate) const FIRECRACKER_VERSION: &str = "v1.10.1";
 
 /// Default URLs for Firecracker quickstart assets.
 /// These are the official AWS-provided minimal images.
@@ -15,6 +18,79 @@ const DEFAULT_KERNEL_URL: &str =
 const DEFAULT_ROOTFS_URL: &str =
     "https://s3.amazonaws.com/spec.ccfc.min/img/quickstart_guide/x86_64/rootfs/bionic.rootfs.ext4";
 
+/// Files at least this large get progress logs while being copied.
+const PROGRESS_MIN_BYTES: u64 = 64 * 1024 * 1024;
+
+/// Size of the sparse per-VM overlay drive; only written blocks use disk.
+const OVERLAY_SIZE_BYTES: u64 = 1024 * 1024 * 1024;
+
+/// Whether `cp --reflink=always` works in the instances directory, probed
+/// once per process.
+static REFLINK_SUPPORTED: OnceLock<bool> = OnceLock::new();
+
+/// Check whether `cp` can reflink files within `dir`.
+///
+/// Fails quietly when `cp` lacks `--reflink` (e.g. busybox) or the
+/// filesystem can't share extents (e.g. ext4).
+fn probe_reflink(dir: &Path) -> bool {
+    let probe = dir.join(".reflink-probe");
+    let probe_copy = dir.join(".reflink-probe.copy");
+
+    let supported = std::fs::write(&probe, b"bashlet").is_ok()
+        && std::process::Command::new("cp")
+            .arg("--reflink=always")
+            .arg(&probe)
+            .arg(&probe_copy)
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .status()
+            .map(|status| status.success())
+            .unwrap_or(false);
+
+    let _ = std::fs::remove_file(&probe);
+    let _ = std::fs::remove_file(&probe_copy);
+
+    debug!(dir = %dir.display(), supported, "Probed reflink support");
+    supported
+}
+
+/// Copy a file in chunks, logging progress for large files.
+async fn copy_with_progress(source: &Path, dest: &Path) -> Result<()> {
+    let mut reader = tokio::fs::File::open(source).await?;
+    let total = reader.metadata().await?.len();
+    let mut writer = tokio::fs::File::create(dest).await?;
+
+    let report = total >= PROGRESS_MIN_BYTES;
+    if report {
+        info!(
+            source = %source.display(),
+            size_mb = total / (1024 * 1024),
+            "Copying rootfs (copy-on-write not available)"
+        );
+    }
+
+    let mut buf = vec![0u8; 1024 * 1024];
+    let mut copied = 0u64;
+    let mut last_percent = 0;
+    loop {
+        let n = reader.read(&mut buf).await?;
+        if n == 0 {
+            break;
+        }
+        writer.write_all(&buf[..n]).await?;
+        copied += n as u64;
+
+        let percent = copied * 100 / total.max(1);
+        if report && percent >= last_percent + 10 {
+            last_percent = percent - percent % 10;
+            info!(percent = last_percent, "Copying rootfs...");
+        }
+    }
+    writer.flush().await?;
+
+    Ok(())
+}
+
 /// Get the Firecracker binary download URL for the current architecture.
 fn get_firecracker_url() -> Result<String> {
     let arch = std::env::consts::ARCH;
@@ -38,13 +114,16 @@ fn get_firecracker_url() -> Result<String> {
 /// Manages kernel and rootfs assets for Firecracker VMs.
 pub struct AssetManager {
     cache_dir: PathBuf,
+    /// Fail instead of downloading missing assets
+    offline: bool,
 }
 
 impl AssetManager {
     /// Create a new asset manager.
-    pub fn new() -> Self {
+    pub fn new(offline: bool) -> Self {
         Self {
-            cache_dir: get_cache_dir().join("firecracker"),
+            cache_dir: cache::firecracker_dir(),
+            offline,
         }
     }
 
@@ -158,28 +237,31 @@ impl AssetManager {
     /// Create a writable copy of the rootfs for a VM instance.
     ///
     /// Each VM needs its own copy of the rootfs to allow writes.
-    /// We use copy-on-write where supported.
-    pub async fn create_rootfs_copy(&self, instance_id: &str) -> Result<PathBuf> {
-        let source = self.get_rootfs(None).await?;
-        let instances_dir = self.cache_dir.join("instances");
+    /// We use copy-on-write where supported. `custom_path` is copied instead
+    /// of the default image when given.
+    pub async fn create_rootfs_copy(
+        &self,
+        instance_id: &str,
+        custom_path: Option<&PathBuf>,
+    ) -> Result<PathBuf> {
+        let source = self.get_rootfs(custom_path).await?;
+        let instances_dir = cache::instances_dir();
         let dest = instances_dir.join(format!("{}.rootfs.ext4", instance_id));
 
         tokio::fs::create_dir_all(&instances_dir).await?;
 
-        // Try reflink copy first (copy-on-write), fall back to regular copy
-        #[cfg(target_os = "linux")]
-        {
-            use std::process::Command;
-
-            // Try cp --reflink=auto for COW copy
-            let result = Command::new("cp")
-                .arg("--reflink=auto")
+        // Reflink copy (copy-on-write) is near-instant, but only if both `cp`
+        // and the filesystem support it
+        if *REFLINK_SUPPORTED.get_or_init(|| probe_reflink(&instances_dir)) {
+            let status = std::process::Command::new("cp")
+                .arg("--reflink=always")
                 .arg(&source)
                 .arg(&dest)
-                .output();
+                .stderr(Stdio::null())
+                .status();
 
-            match result {
-                Ok(output) if output.status.success() => {
+            match status {
+                Ok(status) if status.success() => {
                     info!(
                         source = %source.display(),
                         dest = %dest.display(),
@@ -193,8 +275,7 @@ impl AssetManager {
             }
         }
 
-        // Regular copy
-        tokio::fs::copy(&source, &dest).await?;
+        copy_with_progress(&source, &dest).await?;
         info!(
             source = %source.display(),
             dest = %dest.display(),
@@ -204,8 +285,54 @@ impl AssetManager {
         Ok(dest)
     }
 
+    /// Create an empty writable overlay drive for a VM instance.
+    ///
+    /// The VM boots from the custom rootfs read-only and keeps its writes on
+    /// this drive, so nothing is copied. Needs `mkfs.ext4` on the host.
+    pub async fn create_overlay(&self, instance_id: &str) -> Result<PathBuf> {
+        let instances_dir = cache::instances_dir();
+        let dest = instances_dir.join(format!("{}.overlay.ext4", instance_id));
+
+        tokio::fs::create_dir_all(&instances_dir).await?;
+
+        let file = tokio::fs::File::create(&dest).await?;
+        file.set_len(OVERLAY_SIZE_BYTES).await?;
+        drop(file);
+
+        let output = tokio::process::Command::new("mkfs.ext4")
+            .arg("-q")
+            .arg("-F")
+            .arg(&dest)
+            .output()
+            .await;
+
+        match output {
+            Ok(output) if output.status.success() => {
+                info!(path = %dest.display(), "Created rootfs overlay");
+                Ok(dest)
+            }
+            result => {
+                let _ = tokio::fs::remove_file(&dest).await;
+                let reason = match result {
+                    Ok(output) => String::from_utf8_lossy(&output.stderr).trim().to_string(),
+                    Err(e) => e.to_string(),
+                };
+                Err(BashletError::VMBootFailed(format!(
+                    "mkfs.ext4 failed for overlay: {}",
+                    reason
+                )))
+            }
+        }
+    }
+
     /// Download an asset from a URL.
     async fn download_asset(&self, url: &str, dest: &PathBuf) -> Result<()> {
+        if self.offline {
+            return Err(BashletError::AssetDownload {
+                url: format!("offline mode: {} not cached at {}", url, dest.display()),
+            });
+        }
+
         tokio::fs::create_dir_all(&self.cache_dir).await?;
 
         let client =
@@ -249,13 +376,15 @@ impl AssetManager {
         Ok(())
     }
 
-    /// Clean up instance rootfs copies.
+    /// Clean up instance rootfs copies and overlays.
     pub async fn cleanup_instance(&self, instance_id: &str) -> Result<()> {
-        let instances_dir = self.cache_dir.join("instances");
-        let rootfs_path = instances_dir.join(format!("{}.rootfs.ext4", instance_id));
+        let instances_dir = cache::instances_dir();
 
-        if rootfs_path.exists() {
-            tokio::fs::remove_file(&rootfs_path).await?;
+        for suffix in ["rootfs", "overlay"] {
+            let path = instances_dir.join(format!("{}.{}.ext4", instance_id, suffix));
+            if path.exists() {
+                tokio::fs::remove_file(&path).await?;
+            }
         }
 
         Ok(())
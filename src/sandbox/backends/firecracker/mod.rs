@@ -4,23 +4,40 @@ mod vm;
 mod vsock;
 
 use std::collections::HashMap;
-use std::path::PathBuf;
+use std::path::{Component, Path, PathBuf};
 use std::sync::Arc;
+use std::time::{Duration, Instant};
 
 use async_trait::async_trait;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
 use tokio::sync::Mutex;
-use tracing::{debug, info};
+use tracing::{debug, info, warn};
 
 use crate::cli::args::Mount;
 use crate::config::types::FirecrackerConfig;
 use crate::error::{BashletError, Result};
-use crate::sandbox::traits::{BackendCapabilities, SandboxBackend, SandboxInfo};
-use crate::sandbox::CommandResult;
+use crate::sandbox::traits::{
+    BackendCapabilities, LastCommand, SandboxBackend, SandboxInfo, StartupTiming,
+};
+use crate::sandbox::output::truncate_output;
+use crate::sandbox::{
+    cache, resolve_path, shell_command, shell_join, shell_quote, CommandResult, CommandTiming,
+    NetworkSettings, TimingPhase,
+};
+
+pub(crate) use self::assets::FIRECRACKER_VERSION;
 
 use self::assets::AssetManager;
 use self::vm::FirecrackerVM;
 use self::vsock::VsockClient;
 
+/// Bytes per command when copying files to or from the guest. The agent only
+/// runs shell commands, so each chunk travels base64-encoded inside one, and
+/// must stay well under Linux's 128 KiB limit on a single argument.
+const COPY_CHUNK_BYTES: usize = 48 * 1024;
+
 /// Firecracker microVM sandbox backend.
 ///
 /// Uses Firecracker to run commands in a lightweight microVM, providing
@@ -36,24 +53,77 @@ pub struct FirecrackerBackend {
     instance_id: String,
     /// Working directory inside the VM
     workdir: String,
+    /// Interpreter for commands instead of the guest agent's `/bin/sh`
+    shell: Option<String>,
+    /// Maximum bytes of stdout/stderr kept per command
+    max_output_bytes: u64,
+    /// Asset manager, used to remove this instance's rootfs copy on shutdown
+    assets: AssetManager,
+    last_command: LastCommand,
+    /// VM boot time, reported with the first command
+    startup: StartupTiming,
+    /// Whether the VM is kept running for a session between commands
+    session_mode: bool,
 }
 
 impl FirecrackerBackend {
     /// Create a new Firecracker backend.
+    ///
+    /// If `existing_instance` names a session VM that is still running, the
+    /// backend reconnects to it instead of booting a new one.
+    #[allow(clippy::too_many_arguments)]
     pub async fn new(
         config: FirecrackerConfig,
         mounts: Vec<Mount>,
         env_vars: Vec<(String, String)>,
         workdir: String,
         memory_mb: u64,
+        max_output_bytes: u64,
+        existing_instance: Option<String>,
+        network: NetworkSettings,
+        offline: bool,
+        shell: Option<String>,
     ) -> Result<Self> {
         // Check platform availability
         Self::check_availability()?;
 
+        // The downloaded default image has no /sbin/overlay-init to boot into
+        if config.use_overlay && config.rootfs_path.is_none() {
+            return Err(BashletError::Config(
+                "firecracker.use_overlay requires firecracker.rootfs_path to be set \
+                 to an image that provides /sbin/overlay-init"
+                    .to_string(),
+            ));
+        }
+
+        if let Some(instance_id) = existing_instance {
+            if cache::instance_running(&instance_id) {
+                return Self::attach(
+                    config,
+                    instance_id,
+                    workdir,
+                    max_output_bytes,
+                    offline,
+                    shell,
+                )
+                .await;
+            }
+            warn!(instance_id = %instance_id, "Session VM is no longer running, booting a new one");
+        }
+
         let instance_id = generate_instance_id();
         info!(instance_id = %instance_id, "Starting Firecracker VM");
 
-        let assets = AssetManager::new();
+        let assets = AssetManager::new(offline);
+
+        // Reclaim rootfs copies leaked by VMs whose bashlet process was killed
+        match cache::remove_orphaned_instances() {
+            Ok((removed, bytes)) if removed > 0 => {
+                info!(removed, bytes, "Removed orphaned instance rootfs copies");
+            }
+            Ok(_) => {}
+            Err(e) => warn!(error = %e, "Failed to sweep orphaned instance rootfs copies"),
+        }
 
         // Get or download kernel and firecracker binary
         let kernel_path = assets.get_kernel(config.kernel_path.as_ref()).await?;
@@ -61,44 +131,113 @@ impl FirecrackerBackend {
             .get_firecracker_binary(config.binary_path.as_ref())
             .await?;
 
-        // Use custom rootfs if provided (persistent), otherwise create a copy (ephemeral)
-        let rootfs_path = if let Some(ref custom_rootfs) = config.rootfs_path {
-            if !custom_rootfs.exists() {
-                return Err(BashletError::AssetDownload {
-                    url: format!("Rootfs image not found: {}", custom_rootfs.display()),
-                });
+        // Use custom rootfs if provided (persistent, or read-only beneath an
+        // overlay), otherwise a copy of the default image (ephemeral)
+        let mut overlay_path = None;
+        let rootfs_path = match config.rootfs_path {
+            Some(ref custom_rootfs) => {
+                if !custom_rootfs.exists() {
+                    return Err(BashletError::AssetDownload {
+                        url: format!("Rootfs image not found: {}", custom_rootfs.display()),
+                    });
+                }
+                if config.use_overlay {
+                    match assets.create_overlay(&instance_id).await {
+                        Ok(path) => {
+                            info!(path = %custom_rootfs.display(), "Using rootfs image beneath an overlay");
+                            overlay_path = Some(path);
+                            custom_rootfs.clone()
+                        }
+                        Err(e) => {
+                            warn!(error = %e, "Failed to create rootfs overlay, copying rootfs instead");
+                            assets
+                                .create_rootfs_copy(&instance_id, Some(custom_rootfs))
+                                .await?
+                        }
+                    }
+                } else {
+                    info!(path = %custom_rootfs.display(), "Using persistent rootfs image");
+                    custom_rootfs.clone()
+                }
             }
-            info!(path = %custom_rootfs.display(), "Using persistent rootfs image");
-            custom_rootfs.clone()
-        } else {
-            assets.create_rootfs_copy(&instance_id).await?
+            None => assets.create_rootfs_copy(&instance_id, None).await?,
         };
 
         // Generate socket path
-        let socket_path = std::env::temp_dir().join(format!("firecracker-{}.sock", instance_id));
-
-        // Spawn and configure VM
-        let mut vm = FirecrackerVM::spawn(&binary_path, &socket_path).await?;
+        let socket_path = cache::instance_socket_path(&instance_id);
 
-        vm.configure(vm::VMConfig {
+        let boot_args = Self::boot_args(overlay_path.is_some());
+        let mut vm_config = vm::VMConfig {
             kernel_path,
-            rootfs_path: rootfs_path.clone(),
+            rootfs_path,
+            overlay_path,
             vcpu_count: config.vcpu_count,
             memory_mb,
-            boot_args: "console=ttyS0 reboot=k panic=1 pci=off".to_string(),
-        })
-        .await?;
-
-        // Configure mounts and environment (will be handled by guest agent)
-        vm.set_mounts(mounts);
-        vm.set_env_vars(env_vars);
-
-        // Start the VM
-        vm.start().await?;
+            boot_args,
+        };
+        let boot_timeout = Duration::from_secs(config.boot_timeout_secs);
+
+        // Boot the VM, retrying on boot failures if configured
+        let boot_started = Instant::now();
+        let mut attempt = 0;
+        let mut vm = loop {
+            attempt += 1;
+            match Self::boot_vm(
+                &binary_path,
+                &socket_path,
+                config.session_mode,
+                vm_config.clone(),
+                &mounts,
+                &env_vars,
+                boot_timeout,
+            )
+            .await
+            {
+                Ok(vm) => break vm,
+                Err(BashletError::VMBootFailed(message)) if vm_config.overlay_path.is_some() => {
+                    warn!(
+                        error = %message,
+                        "VM failed to boot from the rootfs overlay, copying rootfs instead"
+                    );
+                    if let Some(overlay) = vm_config.overlay_path.take() {
+                        let _ = tokio::fs::remove_file(overlay).await;
+                    }
+                    vm_config.rootfs_path = assets
+                        .create_rootfs_copy(&instance_id, config.rootfs_path.as_ref())
+                        .await?;
+                    vm_config.boot_args = Self::boot_args(false);
+                    // The copy gets the full number of retries
+                    attempt = 0;
+                }
+                Err(BashletError::VMBootFailed(message)) if attempt <= config.boot_retries => {
+                    warn!(
+                        attempt = attempt,
+                        retries = config.boot_retries,
+                        error = %message,
+                        "VM boot failed, retrying"
+                    );
+                }
+                Err(e) => return Err(e),
+            }
+        };
 
         // Connect to guest agent via vsock
         let vsock_path = socket_path.with_extension("vsock");
-        let client = VsockClient::connect(&vsock_path).await?;
+        let connect_timeout = Duration::from_secs(config.agent_connect_timeout_secs);
+        let mut client =
+            VsockClient::connect(&vsock_path, connect_timeout, || vm.check_running()).await?;
+        let startup = StartupTiming::default();
+        startup.record(TimingPhase::since("vm_boot", boot_started));
+
+        if config.enable_networking {
+            Self::seed_name_resolution(&mut client, &network).await?;
+        } else if !network.is_empty() {
+            warn!("DNS and host overrides are ignored because VM networking is disabled");
+        }
+
+        // Only now that the VM is usable; a failure above drops and kills it
+        vm.keep_running();
+        let session_mode = config.session_mode;
 
         Ok(Self {
             vm: Arc::new(Mutex::new(vm)),
@@ -106,9 +245,109 @@ impl FirecrackerBackend {
             config,
             instance_id,
             workdir,
+            shell,
+            max_output_bytes,
+            assets,
+            last_command: LastCommand::default(),
+            startup,
+            session_mode,
         })
     }
 
+    /// Reconnect to a session VM left running by an earlier command.
+    async fn attach(
+        config: FirecrackerConfig,
+        instance_id: String,
+        workdir: String,
+        max_output_bytes: u64,
+        offline: bool,
+        shell: Option<String>,
+    ) -> Result<Self> {
+        info!(instance_id = %instance_id, "Reattaching to Firecracker VM");
+        let vm = FirecrackerVM::attach(&cache::instance_socket_path(&instance_id)).await?;
+        let connect_timeout = Duration::from_secs(config.agent_connect_timeout_secs);
+        let client = VsockClient::connect(vm.vsock_path(), connect_timeout, || Ok(())).await?;
+
+        Ok(Self {
+            vm: Arc::new(Mutex::new(vm)),
+            client: Arc::new(Mutex::new(client)),
+            config,
+            instance_id,
+            workdir,
+            shell,
+            max_output_bytes,
+            assets: AssetManager::new(offline),
+            last_command: LastCommand::default(),
+            startup: StartupTiming::default(),
+            session_mode: true,
+        })
+    }
+
+    /// Kernel command line; with an overlay, the guest's overlay-init mounts
+    /// the overlay drive over the read-only rootfs.
+    fn boot_args(overlay: bool) -> String {
+        let mut boot_args = "console=ttyS0 reboot=k panic=1 pci=off".to_string();
+        if overlay {
+            boot_args.push_str(" ro init=/sbin/overlay-init overlay_root=vdb");
+        }
+        boot_args
+    }
+
+    /// Spawn, configure, and start a single VM instance.
+    ///
+    /// On failure the partially started VM is dropped, which kills the
+    /// Firecracker process and removes its sockets.
+    async fn boot_vm(
+        binary_path: &PathBuf,
+        socket_path: &PathBuf,
+        persistent: bool,
+        vm_config: vm::VMConfig,
+        mounts: &[Mount],
+        env_vars: &[(String, String)],
+        boot_timeout: Duration,
+    ) -> Result<FirecrackerVM> {
+        let mut vm = FirecrackerVM::spawn(binary_path, socket_path, persistent).await?;
+
+        vm.configure(vm_config).await?;
+
+        // Configure mounts and environment (will be handled by guest agent)
+        vm.set_mounts(mounts.to_vec());
+        vm.set_env_vars(env_vars.to_vec());
+
+        // Start the VM
+        vm.start(boot_timeout).await?;
+
+        Ok(vm)
+    }
+
+    /// Write DNS servers and host entries into the guest via the agent.
+    async fn seed_name_resolution(
+        client: &mut VsockClient,
+        network: &NetworkSettings,
+    ) -> Result<()> {
+        if !network.dns.is_empty() {
+            let resolv_conf: String = network
+                .dns
+                .iter()
+                .map(|ip| format!("nameserver {}\n", ip))
+                .collect();
+            client.write_file("/etc/resolv.conf", &resolv_conf).await?;
+        }
+
+        if !network.extra_hosts.is_empty() {
+            let mut hosts = client.read_file("/etc/hosts").await.unwrap_or_default();
+            if !hosts.is_empty() && !hosts.ends_with('\n') {
+                hosts.push('\n');
+            }
+            for (host, ip) in &network.extra_hosts {
+                hosts.push_str(&format!("{}\t{}\n", ip, host));
+            }
+            client.write_file("/etc/hosts", &hosts).await?;
+        }
+
+        Ok(())
+    }
+
     /// Check if Firecracker is available on this system.
     pub fn is_available() -> bool {
         Self::check_availability().is_ok()
@@ -165,6 +404,92 @@ impl FirecrackerBackend {
             Ok(())
         }
     }
+
+    /// Upload a host file to the guest in chunks, each decoded by
+    /// `base64 -d` in the guest and appended to the file.
+    async fn upload_file(&self, host: &Path, guest: &str) -> Result<()> {
+        let mut file = tokio::fs::File::open(host).await?;
+        let mut buf = vec![0u8; COPY_CHUNK_BYTES];
+        let quoted = shell_quote(guest);
+        let mut first = true;
+        let mut client = self.client.lock().await;
+
+        loop {
+            let n = read_full(&mut file, &mut buf).await?;
+            // An empty file still takes one write to create it
+            if n == 0 && !first {
+                break;
+            }
+            let command = format!(
+                "printf '%s' '{}' | base64 -d {} {}",
+                BASE64.encode(&buf[..n]),
+                if first { ">" } else { ">>" },
+                quoted
+            );
+            let result = client.execute(&command, &self.workdir).await?;
+            if result.exit_code != 0 {
+                return Err(BashletError::SandboxExecution(format!(
+                    "Failed to write {}: {}",
+                    guest,
+                    result.stderr.trim()
+                )));
+            }
+            first = false;
+            if n < COPY_CHUNK_BYTES {
+                break;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Download a guest file to the host in chunks, each read with `dd` and
+    /// returned base64-encoded on stdout.
+    async fn download_file(&self, guest: &str, host: &Path) -> Result<()> {
+        let mut file = tokio::fs::File::create(host).await?;
+        let quoted = shell_quote(guest);
+        let mut client = self.client.lock().await;
+
+        for index in 0.. {
+            let command = format!(
+                "test -r {0} && dd if={0} bs={1} skip={2} count=1 2>/dev/null | base64",
+                quoted, COPY_CHUNK_BYTES, index
+            );
+            let result = client.execute(&command, &self.workdir).await?;
+            if result.exit_code != 0 {
+                return Err(BashletError::SandboxExecution(format!(
+                    "Failed to read {}: {}",
+                    guest,
+                    result.stderr.trim()
+                )));
+            }
+
+            let encoded: String = result.stdout.split_whitespace().collect();
+            let data = BASE64.decode(encoded).map_err(|e| {
+                BashletError::VMCommunication(format!("Invalid base64 from guest: {}", e))
+            })?;
+            file.write_all(&data).await?;
+            if data.len() < COPY_CHUNK_BYTES {
+                break;
+            }
+        }
+        file.flush().await?;
+
+        Ok(())
+    }
+
+    /// Run a command in the guest and return its stdout, failing on a non-zero exit.
+    async fn run_checked(&self, command: &str, context: &str) -> Result<String> {
+        let result = self.execute(command).await?;
+        if result.exit_code != 0 {
+            return Err(BashletError::SandboxExecution(format!(
+                "{}: {}",
+                context,
+                result.stderr.trim()
+            )));
+        }
+        Ok(result.stdout)
+    }
 }
 
 #[async_trait]
@@ -183,34 +508,160 @@ impl SandboxBackend for FirecrackerBackend {
 
     async fn execute(&self, command: &str) -> Result<CommandResult> {
         debug!(command = %command, "Executing command in Firecracker VM");
+        self.last_command.record();
+        let started = Instant::now();
+        let command = match self.shell {
+            Some(ref shell) => shell_command(shell, command),
+            None => command.to_string(),
+        };
         let mut client = self.client.lock().await;
-        client.execute(command, &self.workdir).await
+        let result = client.execute(&command, &self.workdir).await?;
+
+        let (stdout, stdout_truncated) = truncate_output(result.stdout, self.max_output_bytes);
+        let (stderr, stderr_truncated) = truncate_output(result.stderr, self.max_output_bytes);
+
+        Ok(CommandResult {
+            stdout,
+            stderr,
+            exit_code: result.exit_code,
+            truncated: stdout_truncated || stderr_truncated,
+            timing: Some(CommandTiming::since(started, self.startup.take())),
+        })
     }
 
     async fn write_file(&self, path: &str, content: &str) -> Result<()> {
+        let path = resolve_path(&self.workdir, path);
         let mut client = self.client.lock().await;
-        client.write_file(path, content).await
+        client.write_file(&path, content).await
     }
 
     async fn read_file(&self, path: &str) -> Result<String> {
+        let path = resolve_path(&self.workdir, path);
         let mut client = self.client.lock().await;
-        client.read_file(path).await
+        client.read_file(&path).await
     }
 
     async fn list_dir(&self, path: &str) -> Result<String> {
         // Use execute to run ls command
-        self.execute(&format!("ls -la '{}'", path))
+        let path = resolve_path(&self.workdir, path);
+        self.execute(&format!("ls -la {}", shell_quote(&path)))
             .await
             .map(|r| r.stdout)
     }
 
+    async fn copy_in(&self, host: &Path, guest: &str) -> Result<()> {
+        let mut dest = resolve_path(&self.workdir, guest);
+        if let Some(name) = host.file_name() {
+            if self.stat(&dest).await?.is_some_and(|stat| stat.is_dir) {
+                dest = format!("{}/{}", dest.trim_end_matches('/'), name.to_string_lossy());
+            }
+        }
+
+        if !host.is_dir() {
+            return self.upload_file(host, &dest).await;
+        }
+
+        let entries = host_tree(host)?;
+        let guest_path = |relative: &Path| format!("{}/{}", dest, relative.to_string_lossy());
+
+        let mut dirs = vec![dest.clone()];
+        dirs.extend(
+            entries
+                .iter()
+                .filter(|(_, is_dir)| *is_dir)
+                .map(|(relative, _)| guest_path(relative)),
+        );
+        self.run_checked(
+            &format!("mkdir -p -- {}", shell_join(&dirs)),
+            "Failed to create directories",
+        )
+        .await?;
+
+        for (relative, is_dir) in &entries {
+            if !is_dir {
+                self.upload_file(&host.join(relative), &guest_path(relative))
+                    .await?;
+            }
+        }
+
+        Ok(())
+    }
+
+    async fn copy_out(&self, guest: &str, host: &Path) -> Result<()> {
+        let source = resolve_path(&self.workdir, guest);
+        let stat = self.stat(&source).await?.ok_or_else(|| {
+            BashletError::SandboxExecution(format!("{} does not exist in the VM", source))
+        })?;
+
+        let name = source
+            .trim_end_matches('/')
+            .rsplit('/')
+            .next()
+            .unwrap_or("");
+        let dest = if host.is_dir() && !name.is_empty() {
+            host.join(name)
+        } else {
+            host.to_path_buf()
+        };
+
+        if !stat.is_dir {
+            return self.download_file(&source, &dest).await;
+        }
+
+        let quoted = shell_quote(&source);
+        let dirs = self
+            .run_checked(
+                &format!("cd {} && find . -mindepth 1 -type d", quoted),
+                "Failed to list directory",
+            )
+            .await?;
+        let files = self
+            .run_checked(
+                &format!("cd {} && find . -type f", quoted),
+                "Failed to list directory",
+            )
+            .await?;
+
+        tokio::fs::create_dir_all(&dest).await?;
+        for (line, is_dir) in dirs
+            .lines()
+            .map(|l| (l, true))
+            .chain(files.lines().map(|l| (l, false)))
+        {
+            let relative = Path::new(line.trim_start_matches("./"));
+            // Never let a listing from the guest write outside the destination
+            if relative
+                .components()
+                .any(|c| !matches!(c, Component::Normal(_)))
+            {
+                return Err(BashletError::SandboxExecution(format!(
+                    "Refusing to copy unexpected path from the VM: {}",
+                    line
+                )));
+            }
+
+            if is_dir {
+                tokio::fs::create_dir_all(dest.join(relative)).await?;
+            } else {
+                let guest_file = format!("{}/{}", source.trim_end_matches('/'), relative.display());
+                self.download_file(&guest_file, &dest.join(relative))
+                    .await?;
+            }
+        }
+
+        Ok(())
+    }
+
     fn info(&self) -> SandboxInfo {
         SandboxInfo {
             backend_type: "firecracker".to_string(),
             instance_id: Some(self.instance_id.clone()),
             running: true,
+            last_command_at: self.last_command.get(),
+            last_health_check: None,
             metadata: HashMap::from([
                 ("vcpu_count".to_string(), self.config.vcpu_count.to_string()),
+                ("session_mode".to_string(), self.session_mode.to_string()),
                 (
                     "networking".to_string(),
                     self.config.enable_networking.to_string(),
@@ -219,10 +670,33 @@ impl SandboxBackend for FirecrackerBackend {
         }
     }
 
+    fn persistent_instance_id(&self) -> Option<String> {
+        self.session_mode.then(|| self.instance_id.clone())
+    }
+
+    async fn health_check(&self) -> Result<bool> {
+        // Probe the guest agent directly so the check doesn't count as a command
+        let mut client = self.client.lock().await;
+        match client.execute("echo ok", &self.workdir).await {
+            Ok(result) => Ok(result.exit_code == 0),
+            Err(_) => Ok(false),
+        }
+    }
+
     async fn shutdown(&self) -> Result<()> {
+        // A session's VM outlives this handle; terminate and restart stop it
+        if self.session_mode {
+            info!(instance_id = %self.instance_id, "Detaching from Firecracker session VM");
+            self.client.lock().await.disconnect();
+            return Ok(());
+        }
+
         info!(instance_id = %self.instance_id, "Shutting down Firecracker VM");
         let mut vm = self.vm.lock().await;
-        vm.shutdown().await
+        vm.shutdown().await?;
+
+        // Only ephemeral copies live in the instances dir; custom rootfs images are kept
+        self.assets.cleanup_instance(&self.instance_id).await
     }
 }
 
@@ -233,11 +707,44 @@ impl Drop for FirecrackerBackend {
     }
 }
 
+/// Paths under `root`, relative to it, each flagged as a directory or not.
+/// Directories come before their contents.
+fn host_tree(root: &Path) -> std::io::Result<Vec<(PathBuf, bool)>> {
+    let mut entries = Vec::new();
+    let mut pending = vec![PathBuf::new()];
+
+    while let Some(dir) = pending.pop() {
+        for entry in std::fs::read_dir(root.join(&dir))? {
+            let entry = entry?;
+            let relative = dir.join(entry.file_name());
+            let is_dir = entry.path().is_dir();
+            if is_dir {
+                pending.push(relative.clone());
+            }
+            entries.push((relative, is_dir));
+        }
+    }
+
+    Ok(entries)
+}
+
+/// Fill `buf` from `file`, returning fewer bytes only at end of file.
+async fn read_full(file: &mut tokio::fs::File, buf: &mut [u8]) -> std::io::Result<usize> {
+    let mut filled = 0;
+    while filled < buf.len() {
+        match file.read(&mut buf[filled..]).await? {
+            0 => break,
+            n => filled += n,
+        }
+    }
+    Ok(filled)
+}
+
 fn generate_instance_id() -> String {
     use std::time::{SystemTime, UNIX_EPOCH};
     let timestamp = SystemTime::now()
         .duration_since(UNIX_EPOCH)
         .unwrap_or_default()
         .as_millis();
-    format!("fc-{:x}", timestamp)
+    format!("{}{:x}", cache::VM_INSTANCE_PREFIX, timestamp)
 }
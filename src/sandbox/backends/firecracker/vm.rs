@@ -1,8 +1,12 @@
-use std::path::PathBuf;
-use std::process::{Child, Stdio};
+use std::collections::VecDeque;
+use std::fs::File;
+use std::io::{BufRead, BufReader, Read};
+use std::os::unix::process::CommandExt;
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use std::sync::{Arc, Mutex};
 use std::time::Duration;
 
-use tokio::process::Command;
 use tracing::{debug, info, warn};
 
 use crate::cli::args::Mount;
@@ -10,10 +14,16 @@ use crate::error::{BashletError, Result};
 
 use super::api::FirecrackerApiClient;
 
+/// Number of trailing console lines kept for boot failure diagnostics.
+const CONSOLE_TAIL_LINES: usize = 50;
+
 /// Configuration for a Firecracker VM.
+#[derive(Clone)]
 pub struct VMConfig {
     pub kernel_path: PathBuf,
     pub rootfs_path: PathBuf,
+    /// Writable overlay drive; when set, the rootfs is attached read-only
+    pub overlay_path: Option<PathBuf>,
     pub vcpu_count: u8,
     pub memory_mb: u64,
     pub boot_args: String,
@@ -21,8 +31,14 @@ pub struct VMConfig {
 
 /// Manages the lifecycle of a Firecracker microVM.
 pub struct FirecrackerVM {
-    /// Handle to the Firecracker process
-    process: std::process::Child,
+    /// Handle to the Firecracker process; None when attached to a VM
+    /// started by an earlier bashlet process
+    process: Option<std::process::Child>,
+    /// Whether the VM can outlive this process (session mode), logging to a
+    /// file instead of a pipe
+    persistent: bool,
+    /// Leave the VM running when this handle is dropped
+    keep_running: bool,
     /// Path to the API socket
     socket_path: PathBuf,
     /// Path to the vsock UDS
@@ -35,27 +51,55 @@ pub struct FirecrackerVM {
     mounts: Vec<Mount>,
     /// Environment variables to pass to guest agent
     env_vars: Vec<(String, String)>,
+    /// Trailing lines of the Firecracker process stdout/stderr
+    console: Arc<Mutex<VecDeque<String>>>,
 }
 
 impl FirecrackerVM {
     /// Spawn a new Firecracker process.
-    pub async fn spawn(binary_path: &PathBuf, socket_path: &PathBuf) -> Result<Self> {
+    ///
+    /// A `persistent` VM runs in its own process group with its output in a
+    /// log file, so it survives this process exiting or being interrupted.
+    pub async fn spawn(
+        binary_path: &PathBuf,
+        socket_path: &PathBuf,
+        persistent: bool,
+    ) -> Result<Self> {
         info!(socket = %socket_path.display(), "Spawning Firecracker process");
 
         // Remove existing socket if present
         let _ = std::fs::remove_file(socket_path);
 
         // Spawn firecracker with API socket
-        let process = std::process::Command::new(binary_path)
+        let mut command = std::process::Command::new(binary_path);
+        command
             .arg("--api-sock")
             .arg(socket_path)
-            .stdin(Stdio::null())
-            .stdout(Stdio::piped())
-            .stderr(Stdio::piped())
-            .spawn()
-            .map_err(|e| {
-                BashletError::VMBootFailed(format!("Failed to spawn Firecracker: {}", e))
-            })?;
+            .stdin(Stdio::null());
+        if persistent {
+            let log = File::create(socket_path.with_extension("log"))?;
+            command
+                .stdout(log.try_clone()?)
+                .stderr(log)
+                .process_group(0);
+        } else {
+            command.stdout(Stdio::piped()).stderr(Stdio::piped());
+        }
+        let mut process = command.spawn().map_err(|e| {
+            BashletError::VMBootFailed(format!("Failed to spawn Firecracker: {}", e))
+        })?;
+        if persistent {
+            std::fs::write(socket_path.with_extension("pid"), process.id().to_string())?;
+        }
+
+        // Capture console output so boot failures can be diagnosed
+        let console = Arc::new(Mutex::new(VecDeque::with_capacity(CONSOLE_TAIL_LINES)));
+        if let Some(stdout) = process.stdout.take() {
+            capture_output(stdout, Arc::clone(&console));
+        }
+        if let Some(stderr) = process.stderr.take() {
+            capture_output(stderr, Arc::clone(&console));
+        }
 
         // Wait for socket to be ready
         Self::wait_for_socket(socket_path).await?;
@@ -66,18 +110,40 @@ impl FirecrackerVM {
         let vsock_path = socket_path.with_extension("vsock");
 
         Ok(Self {
-            process,
+            process: Some(process),
+            persistent,
+            keep_running: false,
             socket_path: socket_path.clone(),
             vsock_path,
             api: Some(api),
             started: false,
             mounts: Vec::new(),
             env_vars: Vec::new(),
+            console,
+        })
+    }
+
+    /// Attach to a persistent VM that is already running.
+    pub async fn attach(socket_path: &Path) -> Result<Self> {
+        info!(socket = %socket_path.display(), "Attaching to running Firecracker VM");
+        let api = FirecrackerApiClient::new(socket_path).await?;
+
+        Ok(Self {
+            process: None,
+            persistent: true,
+            keep_running: true,
+            socket_path: socket_path.to_path_buf(),
+            vsock_path: socket_path.with_extension("vsock"),
+            api: Some(api),
+            started: true,
+            mounts: Vec::new(),
+            env_vars: Vec::new(),
+            console: Arc::new(Mutex::new(VecDeque::new())),
         })
     }
 
     /// Wait for the API socket to become available.
-    async fn wait_for_socket(socket_path: &PathBuf) -> Result<()> {
+    async fn wait_for_socket(socket_path: &Path) -> Result<()> {
         let max_attempts = 50;
         let delay = Duration::from_millis(100);
 
@@ -118,8 +184,12 @@ impl FirecrackerVM {
         api.put_machine_config(config.vcpu_count, config.memory_mb)
             .await?;
 
-        // Add root drive
-        api.put_drive("rootfs", &config.rootfs_path, false).await?;
+        // Add root drive, read-only beneath an overlay if there is one
+        api.put_drive("rootfs", &config.rootfs_path, config.overlay_path.is_some())
+            .await?;
+        if let Some(ref overlay_path) = config.overlay_path {
+            api.put_drive("overlay", overlay_path, false).await?;
+        }
 
         // Configure vsock for guest-host communication
         // Guest CID 3 is conventional (0, 1, 2 are reserved)
@@ -138,8 +208,8 @@ impl FirecrackerVM {
         self.env_vars = env_vars;
     }
 
-    /// Start the VM.
-    pub async fn start(&mut self) -> Result<()> {
+    /// Start the VM, waiting up to `boot_timeout` for the guest agent.
+    pub async fn start(&mut self, boot_timeout: Duration) -> Result<()> {
         let api = self
             .api
             .as_ref()
@@ -151,17 +221,20 @@ impl FirecrackerVM {
         self.started = true;
 
         // Wait for VM to boot and agent to be ready
-        self.wait_for_boot().await?;
+        self.wait_for_boot(boot_timeout).await?;
 
         Ok(())
     }
 
     /// Wait for the VM to boot and the guest agent to be ready.
-    async fn wait_for_boot(&self) -> Result<()> {
-        let max_attempts = 100;
+    ///
+    /// Fails early if the Firecracker process exits, and includes the tail of
+    /// its console output in the error so the real cause is visible.
+    async fn wait_for_boot(&mut self, boot_timeout: Duration) -> Result<()> {
         let delay = Duration::from_millis(100);
+        let max_attempts = (boot_timeout.as_millis() / delay.as_millis()).max(1);
 
-        info!("Waiting for VM to boot...");
+        info!(timeout_secs = boot_timeout.as_secs(), "Waiting for VM to boot...");
 
         for attempt in 1..=max_attempts {
             // Check if vsock socket exists (agent will create it)
@@ -169,12 +242,63 @@ impl FirecrackerVM {
                 debug!(attempt = attempt, "VM booted, vsock ready");
                 return Ok(());
             }
+
+            self.check_running()?;
+
             tokio::time::sleep(delay).await;
         }
 
-        Err(BashletError::VMBootFailed(
-            "Timeout waiting for VM to boot".to_string(),
-        ))
+        Err(BashletError::VMBootFailed(self.with_console_tail(format!(
+            "Timeout waiting for VM to boot after {}s",
+            boot_timeout.as_secs()
+        ))))
+    }
+
+    /// Fail if the Firecracker process this VM spawned has exited.
+    ///
+    /// An attached VM has no child to check, so it always passes.
+    pub fn check_running(&mut self) -> Result<()> {
+        let exited = self
+            .process
+            .as_mut()
+            .and_then(|p| p.try_wait().ok().flatten());
+        match exited {
+            Some(status) => Err(BashletError::VMBootFailed(self.with_console_tail(format!(
+                "Firecracker process exited during boot ({})",
+                status
+            )))),
+            None => Ok(()),
+        }
+    }
+
+    /// Append the captured console tail to a boot error message.
+    fn with_console_tail(&self, message: String) -> String {
+        let lines = self.console_tail();
+        if lines.is_empty() {
+            return format!("{} (no output from Firecracker)", message);
+        }
+
+        format!(
+            "{}\n--- last {} lines of Firecracker output ---\n{}",
+            message,
+            lines.len(),
+            lines.join("\n")
+        )
+    }
+
+    /// Get the trailing lines of the Firecracker process output.
+    pub fn console_tail(&self) -> Vec<String> {
+        if self.persistent {
+            let log =
+                std::fs::read_to_string(self.socket_path.with_extension("log")).unwrap_or_default();
+            let lines: Vec<String> = log.lines().map(str::to_string).collect();
+            return lines[lines.len().saturating_sub(CONSOLE_TAIL_LINES)..].to_vec();
+        }
+
+        self.console
+            .lock()
+            .map(|lines| lines.iter().cloned().collect())
+            .unwrap_or_default()
     }
 
     /// Shutdown the VM gracefully.
@@ -192,16 +316,42 @@ impl FirecrackerVM {
         }
 
         // Kill the process if still running
-        let _ = self.process.kill();
-
-        // Cleanup sockets
-        let _ = std::fs::remove_file(&self.socket_path);
-        let _ = std::fs::remove_file(&self.vsock_path);
+        self.kill();
 
         self.started = false;
         Ok(())
     }
 
+    /// Leave a persistent VM running once this handle is dropped, for the
+    /// session's next command to attach to.
+    pub fn keep_running(&mut self) {
+        self.keep_running = self.persistent;
+    }
+
+    /// Kill the Firecracker process and remove its sockets.
+    fn kill(&mut self) {
+        match self.process {
+            Some(ref mut process) => {
+                let _ = process.kill();
+            }
+            None => {
+                if let Ok(pid) = std::fs::read_to_string(self.socket_path.with_extension("pid")) {
+                    let _ = std::process::Command::new("kill")
+                        .arg(pid.trim())
+                        .stderr(Stdio::null())
+                        .status();
+                }
+            }
+        }
+
+        let _ = std::fs::remove_file(&self.socket_path);
+        let _ = std::fs::remove_file(&self.vsock_path);
+        if self.persistent {
+            let _ = std::fs::remove_file(self.socket_path.with_extension("pid"));
+            let _ = std::fs::remove_file(self.socket_path.with_extension("log"));
+        }
+    }
+
     /// Get the vsock path for connecting to the guest agent.
     pub fn vsock_path(&self) -> &PathBuf {
         &self.vsock_path
@@ -211,8 +361,26 @@ impl FirecrackerVM {
 impl Drop for FirecrackerVM {
     fn drop(&mut self) {
         // Best-effort cleanup
-        let _ = self.process.kill();
-        let _ = std::fs::remove_file(&self.socket_path);
-        let _ = std::fs::remove_file(&self.vsock_path);
+        if !self.keep_running {
+            self.kill();
+        }
     }
 }
+
+/// Read lines from a child process stream into a bounded ring buffer.
+///
+/// Runs on a dedicated thread since the Firecracker process is a
+/// `std::process::Child`. Draining the pipe also keeps the child from
+/// blocking once the pipe buffer fills up.
+fn capture_output<R: Read + Send + 'static>(stream: R, console: Arc<Mutex<VecDeque<String>>>) {
+    std::thread::spawn(move || {
+        for line in BufReader::new(stream).lines().map_while(|line| line.ok()) {
+            if let Ok(mut lines) = console.lock() {
+                if lines.len() == CONSOLE_TAIL_LINES {
+                    lines.pop_front();
+                }
+                lines.push_back(line);
+            }
+        }
+    });
+}
@@ -1,4 +1,5 @@
 use std::path::PathBuf;
+use std::time::Duration;
 
 use serde::{Deserialize, Serialize};
 use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
@@ -8,6 +9,9 @@ use tracing::debug;
 use crate::error::{BashletError, Result};
 use crate::sandbox::CommandResult;
 
+/// Vsock port the guest agent listens on.
+const AGENT_PORT: u32 = 5000;
+
 /// Protocol messages for the guest agent.
 #[derive(Debug, Serialize)]
 #[serde(tag = "type", rename_all = "snake_case")]
@@ -42,27 +46,40 @@ enum AgentResponse {
 /// Client for communicating with the guest agent via vsock.
 ///
 /// The guest agent runs inside the Firecracker VM and handles
-/// command execution and file operations.
+/// command execution and file operations. One connection is kept open and
+/// reused across requests, and replaced if the agent stops answering.
 pub struct VsockClient {
     socket_path: PathBuf,
+    stream: Option<BufReader<UnixStream>>,
 }
 
 impl VsockClient {
     /// Connect to the guest agent via the vsock UDS proxy.
-    pub async fn connect(socket_path: &PathBuf) -> Result<Self> {
+    ///
+    /// Waits up to `timeout` until the agent accepts a connection, not just
+    /// until the socket exists, since the proxy only routes once the agent
+    /// listens. `check_alive` is called between attempts so the wait stops
+    /// as soon as the VM has died.
+    pub async fn connect(
+        socket_path: &PathBuf,
+        timeout: Duration,
+        mut check_alive: impl FnMut() -> Result<()>,
+    ) -> Result<Self> {
         // Wait for socket to be available
-        let max_attempts = 100;
-        let delay = std::time::Duration::from_millis(100);
+        let delay = Duration::from_millis(100);
+        let max_attempts = (timeout.as_millis() / delay.as_millis()).max(1);
 
         for attempt in 1..=max_attempts {
+            check_alive()?;
+
             if socket_path.exists() {
                 debug!(attempt = attempt, "Vsock socket ready");
-                // Verify we can connect
-                match UnixStream::connect(socket_path).await {
+                // Verify the agent is reachable through the proxy
+                match Self::open_stream(socket_path).await {
                     Ok(stream) => {
-                        drop(stream);
                         return Ok(Self {
                             socket_path: socket_path.clone(),
+                            stream: Some(stream),
                         });
                     }
                     Err(_) if attempt < max_attempts => {
@@ -80,17 +97,80 @@ impl VsockClient {
             tokio::time::sleep(delay).await;
         }
 
-        Err(BashletError::VMCommunication(
-            "Timeout waiting for vsock socket".to_string(),
-        ))
+        Err(BashletError::VMCommunication(format!(
+            "Timeout waiting for the guest agent after {}s",
+            timeout.as_secs()
+        )))
     }
 
-    /// Send a request and receive a response.
-    async fn send_request(&self, request: &AgentRequest) -> Result<AgentResponse> {
-        let mut stream = UnixStream::connect(&self.socket_path)
+    /// Open a connection to the agent's port.
+    ///
+    /// Firecracker's vsock UDS multiplexes guest ports: a host-initiated
+    /// connection starts with `CONNECT <port>`, which the proxy answers with
+    /// `OK <host port>` once it is routed to a listener in the guest. The
+    /// reader is returned so bytes buffered past that line aren't lost.
+    async fn open_stream(socket_path: &PathBuf) -> Result<BufReader<UnixStream>> {
+        let mut stream = UnixStream::connect(socket_path)
             .await
             .map_err(|e| BashletError::VMCommunication(format!("Connection failed: {}", e)))?;
 
+        stream
+            .write_all(format!("CONNECT {}\n", AGENT_PORT).as_bytes())
+            .await
+            .map_err(|e| BashletError::VMCommunication(format!("Write failed: {}", e)))?;
+
+        let mut reader = BufReader::new(stream);
+        let mut ack = String::new();
+        reader
+            .read_line(&mut ack)
+            .await
+            .map_err(|e| BashletError::VMCommunication(format!("Read failed: {}", e)))?;
+
+        if !ack.starts_with("OK ") {
+            return Err(BashletError::VMCommunication(format!(
+                "Agent port {} refused the connection{}",
+                AGENT_PORT,
+                if ack.is_empty() {
+                    String::new()
+                } else {
+                    format!(": {}", ack.trim())
+                }
+            )));
+        }
+
+        Ok(reader)
+    }
+
+    /// Send a request and receive a response.
+    ///
+    /// The kept connection is pinged before reuse, and replaced if that
+    /// fails. The request itself is never retried, since it may have run.
+    /// A connection that fails mid-request is dropped, so the next request
+    /// starts on a fresh one.
+    async fn send_request(&mut self, request: &AgentRequest) -> Result<AgentResponse> {
+        let mut stream = match self.stream.take() {
+            Some(mut stream) => match Self::exchange(&mut stream, &AgentRequest::Ping).await {
+                Ok(AgentResponse::Pong) => stream,
+                _ => {
+                    debug!("Agent connection went stale, reconnecting");
+                    Self::open_stream(&self.socket_path).await?
+                }
+            },
+            None => Self::open_stream(&self.socket_path).await?,
+        };
+
+        let response = Self::exchange(&mut stream, request).await?;
+        self.stream = Some(stream);
+        Ok(response)
+    }
+
+    /// Write one request line and read one response line.
+    async fn exchange(
+        reader: &mut BufReader<UnixStream>,
+        request: &AgentRequest,
+    ) -> Result<AgentResponse> {
+        let stream = reader.get_mut();
+
         // Serialize and send request
         let request_json = serde_json::to_string(request)
             .map_err(|e| BashletError::VMCommunication(format!("Serialization failed: {}", e)))?;
@@ -111,12 +191,16 @@ impl VsockClient {
             .map_err(|e| BashletError::VMCommunication(format!("Flush failed: {}", e)))?;
 
         // Read response
-        let mut reader = BufReader::new(stream);
         let mut response_line = String::new();
-        reader
+        let read = reader
             .read_line(&mut response_line)
             .await
             .map_err(|e| BashletError::VMCommunication(format!("Read failed: {}", e)))?;
+        if read == 0 {
+            return Err(BashletError::VMCommunication(
+                "Agent closed the connection".to_string(),
+            ));
+        }
 
         // Parse response
         let response: AgentResponse = serde_json::from_str(&response_line).map_err(|e| {
@@ -126,6 +210,11 @@ impl VsockClient {
         Ok(response)
     }
 
+    /// Close the kept connection, leaving the agent and VM running.
+    pub fn disconnect(&mut self) {
+        self.stream = None;
+    }
+
     /// Execute a command in the guest.
     pub async fn execute(&mut self, command: &str, workdir: &str) -> Result<CommandResult> {
         let request = AgentRequest::Execute {
@@ -144,6 +233,8 @@ impl VsockClient {
                 exit_code,
                 stdout,
                 stderr,
+                truncated: false,
+                timing: None,
             }),
             AgentResponse::Error { message } => Err(BashletError::SandboxExecution(format!(
                 "Agent error: {}",
@@ -198,15 +289,4 @@ impl VsockClient {
             )),
         }
     }
-
-    /// Ping the agent to check connectivity.
-    pub async fn ping(&mut self) -> Result<bool> {
-        let request = AgentRequest::Ping;
-
-        match self.send_request(&request).await {
-            Ok(AgentResponse::Pong) => Ok(true),
-            Ok(_) => Ok(false),
-            Err(_) => Ok(false),
-        }
-    }
 }
@@ -4,12 +4,18 @@ mod wasmer;
 #[cfg(feature = "wasmer")]
 pub use wasmer::WasmerBackend;
 
+#[cfg(feature = "wasmer")]
+pub(crate) use wasmer::{cached_wasmer_path, WASMER_VERSION};
+
 #[cfg(all(feature = "firecracker", target_os = "linux"))]
 mod firecracker;
 
 #[cfg(all(feature = "firecracker", target_os = "linux"))]
 pub use firecracker::FirecrackerBackend;
 
+#[cfg(all(feature = "firecracker", target_os = "linux"))]
+pub(crate) use firecracker::FIRECRACKER_VERSION;
+
 mod docker;
 mod ssh;
 
@@ -1,7 +1,8 @@
 use std::collections::HashMap;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::process::Stdio;
 use std::sync::Mutex;
+use std::time::Instant;
 
 use async_trait::async_trait;
 use tokio::process::Command;
@@ -9,8 +10,11 @@ use tracing::{debug, info, warn};
 
 use crate::config::types::SshConfig;
 use crate::error::{BashletError, Result};
-use crate::sandbox::traits::{BackendCapabilities, SandboxBackend, SandboxInfo};
-use crate::sandbox::CommandResult;
+use crate::sandbox::traits::{BackendCapabilities, LastCommand, SandboxBackend, SandboxInfo};
+use crate::sandbox::output::capture_output;
+use crate::sandbox::{
+    resolve_path, shell_command, shell_quote, CommandResult, CommandTiming, OutputFiles,
+};
 
 /// SSH sandbox backend.
 ///
@@ -25,10 +29,16 @@ pub struct SshBackend {
     connect_timeout: u64,
     env_vars: Vec<(String, String)>,
     workdir: String,
+    /// Whether to `mkdir -p` the working directory before entering it
+    ensure_workdir: bool,
+    /// Interpreter for commands instead of the remote login shell
+    shell: Option<String>,
+    max_output_bytes: u64,
     /// Path to the ControlMaster socket
     control_path: Mutex<Option<PathBuf>>,
     /// Whether the ControlMaster connection is established
     connected: Mutex<bool>,
+    last_command: LastCommand,
 }
 
 impl SshBackend {
@@ -37,6 +47,8 @@ impl SshBackend {
         config: SshConfig,
         env_vars: Vec<(String, String)>,
         workdir: String,
+        max_output_bytes: u64,
+        shell: Option<String>,
     ) -> Result<Self> {
         // Validate required configuration
         if config.host.is_empty() {
@@ -55,6 +67,8 @@ impl SshBackend {
             });
         }
 
+        validate_workdir(&workdir)?;
+
         // Verify SSH is available
         if !Self::is_available() {
             return Err(BashletError::BackendNotAvailable {
@@ -72,8 +86,12 @@ impl SshBackend {
             connect_timeout: config.connect_timeout,
             env_vars,
             workdir,
+            ensure_workdir: config.ensure_workdir,
+            shell,
+            max_output_bytes,
             control_path: Mutex::new(None),
             connected: Mutex::new(false),
+            last_command: LastCommand::default(),
         };
 
         // Establish the ControlMaster connection if enabled
@@ -154,7 +172,7 @@ impl SshBackend {
         }
 
         // Destination
-        cmd.arg(&self.destination());
+        cmd.arg(self.destination());
 
         // Run a simple command to establish the connection
         cmd.args(["exit", "0"]);
@@ -192,7 +210,9 @@ impl SshBackend {
 
     /// Test connection without ControlMaster.
     async fn test_connection(&self) -> Result<()> {
-        let result = self.execute_ssh("echo ok").await?;
+        let result = self
+            .execute_ssh("echo ok", None, &OutputFiles::default())
+            .await?;
         if result.exit_code != 0 {
             return Err(BashletError::SandboxInit(format!(
                 "SSH connection test failed: {}",
@@ -213,11 +233,56 @@ impl SshBackend {
         self.control_path.lock().ok().and_then(|guard| guard.clone())
     }
 
-    /// Execute a command via SSH.
-    async fn execute_ssh(&self, command: &str) -> Result<CommandResult> {
+    /// Execute a command via SSH, piping `stdin` to it when given.
+    async fn execute_ssh(
+        &self,
+        command: &str,
+        stdin: Option<&[u8]>,
+        files: &OutputFiles,
+    ) -> Result<CommandResult> {
         debug!(command = %command, "Executing via SSH");
+        let started = Instant::now();
+
+        let mut cmd = self.ssh_command(false);
+
+        // Build the remote command with environment variables and working directory
+        let remote_command = self.build_remote_command(command);
+        cmd.arg(&remote_command);
+
+        cmd.stdin(Stdio::null());
+
+        let output = capture_output(&mut cmd, self.max_output_bytes, stdin, files)
+            .await
+            .map_err(|e| {
+                BashletError::SandboxExecution(format!("Failed to execute SSH command: {}", e))
+            })?;
+
+        let stdout = output.stdout;
+        let stderr = output.stderr;
+        let exit_code = output.status.code().unwrap_or(1);
+
+        debug!(
+            exit_code = exit_code,
+            stdout_len = stdout.len(),
+            stderr_len = stderr.len(),
+            "SSH command completed"
+        );
 
+        Ok(CommandResult {
+            stdout,
+            stderr,
+            exit_code,
+            truncated: output.truncated,
+            timing: Some(CommandTiming::since(started, Vec::new())),
+        })
+    }
+
+    /// Build an `ssh` invocation for the configured host, up to the remote command.
+    fn ssh_command(&self, tty: bool) -> Command {
         let mut cmd = Command::new("ssh");
+        if tty {
+            cmd.arg("-tt");
+        }
 
         // Use ControlMaster socket if available
         if let Some(ref control_path) = self.get_control_path() {
@@ -238,36 +303,63 @@ impl SshBackend {
         }
 
         // Destination
-        cmd.arg(&self.destination());
+        cmd.arg(self.destination());
 
-        // Build the remote command with environment variables and working directory
-        let remote_command = self.build_remote_command(command);
-        cmd.arg(&remote_command);
+        cmd
+    }
 
-        cmd.stdin(Stdio::null());
-        cmd.stdout(Stdio::piped());
-        cmd.stderr(Stdio::piped());
+    /// Build an `scp -r` invocation with the same connection options as `ssh_command`.
+    fn scp_command(&self) -> Command {
+        let mut cmd = Command::new("scp");
+        cmd.args(["-r", "-q", "-p"]);
 
-        let output = cmd.output().await.map_err(|e| {
-            BashletError::SandboxExecution(format!("Failed to execute SSH command: {}", e))
-        })?;
+        // Use ControlMaster socket if available
+        if let Some(ref control_path) = self.get_control_path() {
+            cmd.arg("-o")
+                .arg(format!("ControlPath={}", control_path.display()));
+        }
 
-        let stdout = String::from_utf8_lossy(&output.stdout).to_string();
-        let stderr = String::from_utf8_lossy(&output.stderr).to_string();
-        let exit_code = output.status.code().unwrap_or(1);
+        // Connection options
+        cmd.args(["-o", &format!("ConnectTimeout={}", self.connect_timeout)]);
+        cmd.args(["-o", "BatchMode=yes"]);
+        cmd.args(["-o", "StrictHostKeyChecking=accept-new"]);
 
-        debug!(
-            exit_code = exit_code,
-            stdout_len = stdout.len(),
-            stderr_len = stderr.len(),
-            "SSH command completed"
-        );
+        // Port (scp spells it -P)
+        cmd.args(["-P", &self.port.to_string()]);
 
-        Ok(CommandResult {
-            stdout,
-            stderr,
-            exit_code,
-        })
+        // Key file if specified
+        if let Some(ref key_file) = self.key_file {
+            cmd.args(["-i", key_file.to_str().unwrap_or_default()]);
+        }
+
+        cmd
+    }
+
+    /// `scp` spec for a remote path, bracketing IPv6 hosts.
+    fn remote_spec(&self, path: &str) -> String {
+        if self.host.contains(':') {
+            format!("{}@[{}]:{}", self.user, self.host, path)
+        } else {
+            format!("{}:{}", self.destination(), path)
+        }
+    }
+
+    /// Run an `scp` command built by `scp_command`.
+    async fn run_scp(&self, mut cmd: Command) -> Result<()> {
+        let output = cmd
+            .stdin(Stdio::null())
+            .output()
+            .await
+            .map_err(|e| BashletError::SandboxExecution(format!("Failed to run scp: {}", e)))?;
+
+        if !output.status.success() {
+            return Err(BashletError::SandboxExecution(format!(
+                "scp failed: {}",
+                String::from_utf8_lossy(&output.stderr).trim()
+            )));
+        }
+
+        Ok(())
     }
 
     /// Build the remote command with environment variables and working directory.
@@ -276,16 +368,24 @@ impl SshBackend {
 
         // Set environment variables
         for (key, value) in &self.env_vars {
-            // Escape single quotes in the value
-            let escaped_value = value.replace('\'', "'\"'\"'");
-            parts.push(format!("export {}='{}'", key, escaped_value));
+            parts.push(format!("export {}={}", key, shell_quote(value)));
         }
 
-        // Change to working directory
-        parts.push(format!("cd '{}' 2>/dev/null || true", self.workdir));
+        // Change to working directory, never running the command somewhere else
+        if self.ensure_workdir {
+            parts.push(format!(
+                "mkdir -p {0} && cd {0} || exit 1",
+                shell_quote(&self.workdir)
+            ));
+        } else {
+            parts.push(format!("cd {} || exit 1", shell_quote(&self.workdir)));
+        }
 
         // Execute the actual command
-        parts.push(command.to_string());
+        parts.push(match self.shell {
+            Some(ref shell) => shell_command(shell, command),
+            None => command.to_string(),
+        });
 
         // Join with semicolons
         parts.join("; ")
@@ -306,7 +406,7 @@ impl SshBackend {
         let mut cmd = Command::new("ssh");
         cmd.args(["-S", control_path.to_str().unwrap()]);
         cmd.args(["-O", "exit"]);
-        cmd.arg(&self.destination());
+        cmd.arg(self.destination());
 
         cmd.stdout(Stdio::piped());
         cmd.stderr(Stdio::piped());
@@ -357,13 +457,47 @@ impl SandboxBackend for SshBackend {
     }
 
     async fn execute(&self, command: &str) -> Result<CommandResult> {
-        self.execute_ssh(command).await
+        self.last_command.record();
+        self.execute_ssh(command, None, &OutputFiles::default())
+            .await
+    }
+
+    async fn execute_with_stdin(&self, command: &str, stdin: &[u8]) -> Result<CommandResult> {
+        self.last_command.record();
+        self.execute_ssh(command, Some(stdin), &OutputFiles::default())
+            .await
+    }
+
+    async fn execute_to_files(
+        &self,
+        command: &str,
+        stdin: Option<&[u8]>,
+        files: &OutputFiles,
+    ) -> Result<CommandResult> {
+        self.last_command.record();
+        self.execute_ssh(command, stdin, files).await
+    }
+
+    async fn execute_interactive(&self, command: &str) -> Result<i32> {
+        self.last_command.record();
+        let mut cmd = self.ssh_command(true);
+        cmd.arg(self.build_remote_command(command));
+
+        let status = cmd.status().await.map_err(|e| {
+            BashletError::SandboxExecution(format!("Failed to execute SSH command: {}", e))
+        })?;
+
+        Ok(status.code().unwrap_or(1))
     }
 
     async fn write_file(&self, path: &str, content: &str) -> Result<()> {
+        let path = &resolve_path(&self.workdir, path);
         // Escape content for shell
-        let escaped = content.replace('\\', "\\\\").replace('\'', "'\"'\"'");
-        let cmd = format!("printf '%s' '{}' > '{}'", escaped, path);
+        let cmd = format!(
+            "printf '%s' {} > {}",
+            shell_quote(content),
+            shell_quote(path)
+        );
         let result = self.execute(&cmd).await?;
 
         if result.exit_code != 0 {
@@ -377,7 +511,8 @@ impl SandboxBackend for SshBackend {
     }
 
     async fn read_file(&self, path: &str) -> Result<String> {
-        let result = self.execute(&format!("cat '{}'", path)).await?;
+        let path = &resolve_path(&self.workdir, path);
+        let result = self.execute(&format!("cat {}", shell_quote(path))).await?;
 
         if result.exit_code != 0 {
             return Err(BashletError::SandboxExecution(format!(
@@ -390,7 +525,8 @@ impl SandboxBackend for SshBackend {
     }
 
     async fn list_dir(&self, path: &str) -> Result<String> {
-        let result = self.execute(&format!("ls -la '{}'", path)).await?;
+        let path = &resolve_path(&self.workdir, path);
+        let result = self.execute(&format!("ls -la {}", shell_quote(path))).await?;
 
         if result.exit_code != 0 {
             return Err(BashletError::SandboxExecution(format!(
@@ -402,6 +538,20 @@ impl SandboxBackend for SshBackend {
         Ok(result.stdout)
     }
 
+    async fn copy_in(&self, host: &Path, guest: &str) -> Result<()> {
+        let guest = resolve_path(&self.workdir, guest);
+        let mut cmd = self.scp_command();
+        cmd.arg(host).arg(self.remote_spec(&guest));
+        self.run_scp(cmd).await
+    }
+
+    async fn copy_out(&self, guest: &str, host: &Path) -> Result<()> {
+        let guest = resolve_path(&self.workdir, guest);
+        let mut cmd = self.scp_command();
+        cmd.arg(self.remote_spec(&guest)).arg(host);
+        self.run_scp(cmd).await
+    }
+
     fn info(&self) -> SandboxInfo {
         let connected = self.connected.lock().ok().map(|g| *g).unwrap_or(false);
         let control_path = self.get_control_path();
@@ -410,6 +560,8 @@ impl SandboxBackend for SshBackend {
             backend_type: "ssh".to_string(),
             instance_id: Some(format!("{}@{}:{}", self.user, self.host, self.port)),
             running: connected,
+            last_command_at: self.last_command.get(),
+            last_health_check: None,
             metadata: HashMap::from([
                 ("host".to_string(), self.host.clone()),
                 ("port".to_string(), self.port.to_string()),
@@ -437,9 +589,23 @@ impl SandboxBackend for SshBackend {
     }
 
     async fn health_check(&self) -> Result<bool> {
-        match self.execute("echo ok").await {
+        match self
+            .execute_ssh("echo ok", None, &OutputFiles::default())
+            .await
+        {
             Ok(result) => Ok(result.exit_code == 0 && result.stdout.trim() == "ok"),
             Err(_) => Ok(false),
         }
     }
 }
+
+/// Reject working directories that can't be safely single-quoted in the remote command.
+fn validate_workdir(workdir: &str) -> Result<()> {
+    if workdir.is_empty() || workdir.contains('\'') || workdir.chars().any(char::is_control) {
+        return Err(BashletError::Config(format!(
+            "Invalid SSH working directory {:?}: must be non-empty without quotes or control characters",
+            workdir
+        )));
+    }
+    Ok(())
+}
@@ -1,6 +1,8 @@
 use std::collections::HashMap;
-use std::path::PathBuf;
+use std::fmt;
+use std::path::{Path, PathBuf};
 use std::process::Stdio;
+use std::time::Instant;
 
 use async_trait::async_trait;
 use tokio::process::Command;
@@ -10,16 +12,86 @@ use crate::cli::args::Mount;
 use crate::config::loader::get_cache_dir;
 use crate::config::types::WasmerConfig;
 use crate::error::{BashletError, Result};
-use crate::sandbox::traits::{BackendCapabilities, SandboxBackend, SandboxInfo};
-use crate::sandbox::CommandResult;
+use crate::sandbox::cache;
+use crate::sandbox::output::capture_output;
+use crate::sandbox::traits::{BackendCapabilities, LastCommand, SandboxBackend, SandboxInfo};
+use crate::sandbox::{
+    copy_host_path, resolve_path, shell_quote, CommandResult, CommandTiming, OutputFiles,
+};
 
 /// Wasmer version to download if not installed
-const WASMER_VERSION: &str = "v6.0.0";
+pub(crate) const WASMER_VERSION: &str = "v6.0.0";
 
 /// Wasmer registry WEBC download URL for bash
 const WASMER_BASH_WEBC_URL: &str =
     "https://cdn.wasmer.io/webcimages/6616eee914dd95cb9751a0ef1d17a908055176781bc0b6090e33da5bbc325417.webc";
 
+/// Wasmer registry GraphQL endpoint, used to resolve a package's WEBC URL
+const WASMER_REGISTRY_URL: &str = "https://registry.wasmer.io/graphql";
+
+/// A package in the Wasmer registry, written `namespace/name[@version]`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WebcPackage {
+    pub namespace: String,
+    pub name: String,
+    /// Version to fetch; the registry's latest when unset
+    pub version: Option<String>,
+}
+
+impl WebcPackage {
+    /// Parse a package spec such as `wasmer/python@3.11`.
+    pub fn parse(spec: &str) -> Result<Self> {
+        let valid = |s: &str| {
+            !s.is_empty()
+                && !s.starts_with('.')
+                && s.chars()
+                    .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'))
+        };
+        let (full_name, version) = match spec.split_once('@') {
+            Some((name, version)) => (name, Some(version)),
+            None => (spec, None),
+        };
+
+        match full_name.split_once('/') {
+            Some((namespace, name))
+                if valid(namespace) && valid(name) && version.is_none_or(valid) =>
+            {
+                Ok(Self {
+                    namespace: namespace.to_string(),
+                    name: name.to_string(),
+                    version: version.map(str::to_string),
+                })
+            }
+            _ => Err(BashletError::Config(format!(
+                "Invalid Wasmer package '{}': expected namespace/name[@version]",
+                spec
+            ))),
+        }
+    }
+
+    /// Where the package's WEBC is cached, e.g. `webc/wasmer/python-3.11.webc`.
+    ///
+    /// An unversioned package is cached without a version and is not
+    /// refreshed until the cache is cleared.
+    pub fn cache_path(&self) -> PathBuf {
+        let file_name = match &self.version {
+            Some(version) => format!("{}-{}.webc", self.name, version),
+            None => format!("{}.webc", self.name),
+        };
+        cache::webc_dir().join(&self.namespace).join(file_name)
+    }
+}
+
+impl fmt::Display for WebcPackage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}/{}", self.namespace, self.name)?;
+        if let Some(version) = &self.version {
+            write!(f, "@{}", version)?;
+        }
+        Ok(())
+    }
+}
+
 /// Get the Wasmer binary download URL for the current platform.
 fn get_wasmer_download_url() -> Result<String> {
     let os = std::env::consts::OS;
@@ -64,6 +136,8 @@ pub struct WasmerBackend {
     mounts: Vec<Mount>,
     env_vars: Vec<(String, String)>,
     workdir: String,
+    max_output_bytes: u64,
+    last_command: LastCommand,
 }
 
 impl WasmerBackend {
@@ -73,9 +147,16 @@ impl WasmerBackend {
         mounts: Vec<Mount>,
         env_vars: Vec<(String, String)>,
         workdir: String,
+        max_output_bytes: u64,
+        offline: bool,
+        shell: Option<String>,
     ) -> Result<Self> {
+        if let Some(shell) = shell.filter(|s| Path::new(s).file_name() != Some("bash".as_ref())) {
+            warn!(shell = %shell, "Wasmer always runs commands with bash; ignoring shell");
+        }
+
         // Get or download wasmer binary
-        let wasmer_binary = get_or_download_wasmer().await?;
+        let wasmer_binary = get_or_download_wasmer(offline).await?;
 
         // Get or download the WEBC package
         let webc_path = match &config.wasm_binary {
@@ -87,7 +168,14 @@ impl WasmerBackend {
                 }
                 path.clone()
             }
-            None => get_or_download_webc().await?,
+            None => {
+                let package = config
+                    .package
+                    .as_deref()
+                    .map(WebcPackage::parse)
+                    .transpose()?;
+                get_or_download_webc(package.as_ref(), offline).await?
+            }
         };
 
         info!(webc = %webc_path.display(), "Using WEBC package");
@@ -98,6 +186,8 @@ impl WasmerBackend {
             mounts,
             env_vars,
             workdir,
+            max_output_bytes,
+            last_command: LastCommand::default(),
         })
     }
 
@@ -108,25 +198,86 @@ impl WasmerBackend {
         // Wasmer is always "available" since we can download it at runtime
         true
     }
-}
-
-#[async_trait]
-impl SandboxBackend for WasmerBackend {
-    fn name(&self) -> &str {
-        "wasmer"
-    }
 
-    fn capabilities(&self) -> BackendCapabilities {
-        BackendCapabilities {
-            native_linux: false,  // WASM, not native Linux
-            networking: false,    // No network access in WASM sandbox
-            persistent_fs: false, // Each command is stateless
+    /// Host path behind a guest path inside a mount, and whether the mount is read-only.
+    ///
+    /// The sandbox filesystem is otherwise discarded after each command, so
+    /// files can only be copied through mounts.
+    fn mapped_host_path(&self, guest: &str) -> Result<(PathBuf, bool)> {
+        let guest = resolve_path(&self.workdir, guest);
+        // `..` would otherwise let the joined host path escape the mount
+        if guest.split('/').any(|part| part == ".." || part == ".") {
+            return Err(BashletError::SandboxExecution(format!(
+                "Refusing to copy {}: the path must not contain '.' or '..' components",
+                guest
+            )));
         }
+
+        self.mounts
+            .iter()
+            .filter_map(|mount| {
+                let base = mount.guest_path.trim_end_matches('/');
+                let rest = guest.strip_prefix(base)?;
+                (rest.is_empty() || rest.starts_with('/')).then_some((base.len(), mount, rest))
+            })
+            .max_by_key(|(len, _, _)| *len)
+            .map(|(_, mount, rest)| {
+                let rest = rest.trim_start_matches('/');
+                let host = if rest.is_empty() {
+                    mount.host_path.clone()
+                } else {
+                    mount.host_path.join(rest)
+                };
+                (host, mount.readonly)
+            })
+            .ok_or_else(|| BashletError::Unsupported {
+                operation: "copy".to_string(),
+                reason: format!(
+                    "{} is not inside a mount; the wasmer backend can only copy files through mounts",
+                    guest
+                ),
+            })
     }
 
-    async fn execute(&self, command: &str) -> Result<CommandResult> {
+    /// Run `command` under `wasmer run`, piping `stdin` to it when given.
+    async fn run_wasmer(
+        &self,
+        command: &str,
+        stdin: Option<&[u8]>,
+        files: &OutputFiles,
+    ) -> Result<CommandResult> {
         debug!(command = %command, "Executing command in Wasmer sandbox");
+        let started = Instant::now();
+
+        let mut cmd = self.wasmer_command(command)?;
+        let output = capture_output(&mut cmd, self.max_output_bytes, stdin, files)
+            .await
+            .map_err(|e| {
+                BashletError::SandboxExecution(format!("Failed to execute wasmer: {}", e))
+            })?;
+
+        let stdout = output.stdout;
+        let stderr = output.stderr;
+        let exit_code = output.status.code().unwrap_or(1);
+
+        debug!(
+            exit_code = exit_code,
+            stdout_len = stdout.len(),
+            stderr_len = stderr.len(),
+            "Command completed"
+        );
+
+        Ok(CommandResult {
+            stdout,
+            stderr,
+            exit_code,
+            truncated: output.truncated,
+            timing: Some(CommandTiming::since(started, Vec::new())),
+        })
+    }
 
+    /// Build the `wasmer run` invocation for `command`.
+    fn wasmer_command(&self, command: &str) -> Result<Command> {
         let mut cmd = Command::new(&self.wasmer_binary);
         cmd.arg("run");
 
@@ -151,41 +302,60 @@ impl SandboxBackend for WasmerBackend {
             cmd.arg(format!("{}={}", key, value));
         }
 
-        // Set the WEBC package and command
+        // Set the WEBC package and command. The first `--` ends wasmer's
+        // options; the second ends bash's, so a command starting with `-`
+        // isn't parsed as a bash flag.
         cmd.arg(&self.webc_path);
-        cmd.arg("--");
-        cmd.arg("-c");
-        cmd.arg(command);
+        cmd.args(["--", "-c", "--", command]);
 
         cmd.stdin(Stdio::null());
-        cmd.stdout(Stdio::piped());
-        cmd.stderr(Stdio::piped());
+        Ok(cmd)
+    }
+}
 
-        let output = cmd.output().await.map_err(|e| {
-            BashletError::SandboxExecution(format!("Failed to execute wasmer: {}", e))
-        })?;
+#[async_trait]
+impl SandboxBackend for WasmerBackend {
+    fn name(&self) -> &str {
+        "wasmer"
+    }
 
-        let stdout = String::from_utf8_lossy(&output.stdout).to_string();
-        let stderr = String::from_utf8_lossy(&output.stderr).to_string();
-        let exit_code = output.status.code().unwrap_or(1);
+    fn capabilities(&self) -> BackendCapabilities {
+        BackendCapabilities {
+            native_linux: false,  // WASM, not native Linux
+            networking: false,    // No network access in WASM sandbox
+            persistent_fs: false, // Each command is stateless
+        }
+    }
 
-        debug!(
-            exit_code = exit_code,
-            stdout_len = stdout.len(),
-            stderr_len = stderr.len(),
-            "Command completed"
-        );
+    async fn execute(&self, command: &str) -> Result<CommandResult> {
+        self.last_command.record();
+        self.run_wasmer(command, None, &OutputFiles::default())
+            .await
+    }
 
-        Ok(CommandResult {
-            stdout,
-            stderr,
-            exit_code,
-        })
+    async fn execute_with_stdin(&self, command: &str, stdin: &[u8]) -> Result<CommandResult> {
+        self.last_command.record();
+        self.run_wasmer(command, Some(stdin), &OutputFiles::default())
+            .await
+    }
+
+    async fn execute_to_files(
+        &self,
+        command: &str,
+        stdin: Option<&[u8]>,
+        files: &OutputFiles,
+    ) -> Result<CommandResult> {
+        self.last_command.record();
+        self.run_wasmer(command, stdin, files).await
     }
 
     async fn write_file(&self, path: &str, content: &str) -> Result<()> {
-        let escaped = content.replace('\\', "\\\\").replace('\'', "'\"'\"'");
-        let cmd = format!("printf '%s' '{}' > '{}'", escaped, path);
+        let path = &resolve_path(&self.workdir, path);
+        let cmd = format!(
+            "printf '%s' {} > {}",
+            shell_quote(content),
+            shell_quote(path)
+        );
         let result = self.execute(&cmd).await?;
 
         if result.exit_code != 0 {
@@ -199,7 +369,8 @@ impl SandboxBackend for WasmerBackend {
     }
 
     async fn read_file(&self, path: &str) -> Result<String> {
-        let result = self.execute(&format!("cat '{}'", path)).await?;
+        let path = &resolve_path(&self.workdir, path);
+        let result = self.execute(&format!("cat {}", shell_quote(path))).await?;
 
         if result.exit_code != 0 {
             return Err(BashletError::SandboxExecution(format!(
@@ -212,7 +383,10 @@ impl SandboxBackend for WasmerBackend {
     }
 
     async fn list_dir(&self, path: &str) -> Result<String> {
-        let result = self.execute(&format!("ls -la '{}'", path)).await?;
+        let path = &resolve_path(&self.workdir, path);
+        let result = self
+            .execute(&format!("ls -la {}", shell_quote(path)))
+            .await?;
 
         if result.exit_code != 0 {
             return Err(BashletError::SandboxExecution(format!(
@@ -224,11 +398,33 @@ impl SandboxBackend for WasmerBackend {
         Ok(result.stdout)
     }
 
+    async fn copy_in(&self, host: &Path, guest: &str) -> Result<()> {
+        let (dest, readonly) = self.mapped_host_path(guest)?;
+        if readonly {
+            return Err(BashletError::SandboxExecution(format!(
+                "Cannot copy to {}: it is on a read-only mount",
+                guest
+            )));
+        }
+
+        copy_host_path(host, &dest).map_err(|e| {
+            BashletError::SandboxExecution(format!("Failed to copy {}: {}", host.display(), e))
+        })
+    }
+
+    async fn copy_out(&self, guest: &str, host: &Path) -> Result<()> {
+        let (source, _) = self.mapped_host_path(guest)?;
+        copy_host_path(&source, host)
+            .map_err(|e| BashletError::SandboxExecution(format!("Failed to copy {}: {}", guest, e)))
+    }
+
     fn info(&self) -> SandboxInfo {
         SandboxInfo {
             backend_type: "wasmer".to_string(),
             instance_id: None, // Wasmer is stateless
             running: true,
+            last_command_at: self.last_command.get(),
+            last_health_check: None,
             metadata: HashMap::from([
                 (
                     "webc_path".to_string(),
@@ -242,13 +438,24 @@ impl SandboxBackend for WasmerBackend {
     // shutdown() uses default no-op implementation since Wasmer is stateless
 }
 
+/// Path the wasmer binary is downloaded to.
+pub(crate) fn cached_wasmer_path() -> PathBuf {
+    let cache_dir = get_cache_dir().join("wasmer");
+
+    #[cfg(windows)]
+    return cache_dir.join("wasmer.exe");
+
+    #[cfg(not(windows))]
+    cache_dir.join("wasmer")
+}
+
 /// Get or download the Wasmer binary.
 ///
 /// Checks in order:
 /// 1. System PATH
 /// 2. Cached download
-/// 3. Downloads from GitHub releases
-async fn get_or_download_wasmer() -> Result<PathBuf> {
+/// 3. Downloads from GitHub releases (unless offline)
+async fn get_or_download_wasmer(offline: bool) -> Result<PathBuf> {
     // 1. Check if wasmer is in PATH
     if let Ok(output) = std::process::Command::new("wasmer")
         .arg("--version")
@@ -263,10 +470,7 @@ async fn get_or_download_wasmer() -> Result<PathBuf> {
 
     // 2. Check cached binary
     let cache_dir = get_cache_dir().join("wasmer");
-    let binary_path = cache_dir.join("wasmer");
-
-    #[cfg(windows)]
-    let binary_path = cache_dir.join("wasmer.exe");
+    let binary_path = cached_wasmer_path();
 
     if binary_path.exists() {
         // Verify it works
@@ -283,6 +487,16 @@ async fn get_or_download_wasmer() -> Result<PathBuf> {
     }
 
     // 3. Download from GitHub
+    if offline {
+        return Err(BashletError::BackendNotAvailable {
+            backend: "wasmer".to_string(),
+            reason: format!(
+                "offline mode: wasmer not found in PATH or at {}",
+                binary_path.display()
+            ),
+        });
+    }
+
     info!("Downloading Wasmer {}...", WASMER_VERSION);
 
     tokio::fs::create_dir_all(&cache_dir).await?;
@@ -378,29 +592,51 @@ async fn get_or_download_wasmer() -> Result<PathBuf> {
     Ok(binary_path)
 }
 
-/// Get the cached WEBC package or download it from wasmer registry
-async fn get_or_download_webc() -> Result<PathBuf> {
-    let cache_dir = get_cache_dir();
-    let webc_path = cache_dir.join("bash.webc");
+/// Get the cached WEBC package or download it from wasmer registry.
+///
+/// Without a package this is the pinned bash WEBC; a package is resolved
+/// through the registry and cached per name and version.
+async fn get_or_download_webc(package: Option<&WebcPackage>, offline: bool) -> Result<PathBuf> {
+    let (label, webc_path) = match package {
+        Some(package) => (package.to_string(), package.cache_path()),
+        None => ("bash".to_string(), cache::webc_path()),
+    };
 
     // Check cache first
     if webc_path.exists() {
-        info!(path = %webc_path.display(), "Using cached WEBC package");
+        info!(package = %label, path = %webc_path.display(), "WEBC cache hit");
         return Ok(webc_path);
     }
 
-    info!("Downloading bash WEBC from wasmer registry...");
+    if offline {
+        return Err(BashletError::AssetDownload {
+            url: format!(
+                "offline mode: {} WEBC package not cached at {}",
+                label,
+                webc_path.display()
+            ),
+        });
+    }
+
+    info!(package = %label, "WEBC cache miss, downloading from wasmer registry...");
 
     // Create cache directory
-    tokio::fs::create_dir_all(&cache_dir).await?;
+    if let Some(parent) = webc_path.parent() {
+        tokio::fs::create_dir_all(parent).await?;
+    }
 
     // Download the WEBC container from wasmer CDN
     let client = reqwest::Client::builder()
         .build()
         .map_err(|e| BashletError::SandboxInit(format!("Failed to create HTTP client: {}", e)))?;
 
+    let url = match package {
+        Some(package) => resolve_webc_url(&client, package).await?,
+        None => WASMER_BASH_WEBC_URL.to_string(),
+    };
+
     let response = client
-        .get(WASMER_BASH_WEBC_URL)
+        .get(&url)
         .send()
         .await
         .map_err(|e| BashletError::SandboxInit(format!("Failed to download: {}", e)))?;
@@ -432,3 +668,147 @@ async fn get_or_download_webc() -> Result<PathBuf> {
 
     Ok(webc_path)
 }
+
+/// Look up the WEBC download URL of a package version in the Wasmer registry.
+async fn resolve_webc_url(client: &reqwest::Client, package: &WebcPackage) -> Result<String> {
+    const QUERY: &str = "query ($name: String!, $version: String) { \
+        getPackageVersion(name: $name, version: $version) { \
+        distribution { piritaDownloadUrl } } }";
+
+    let body = serde_json::json!({
+        "query": QUERY,
+        "variables": {
+            "name": format!("{}/{}", package.namespace, package.name),
+            "version": package.version.as_deref().unwrap_or("latest"),
+        },
+    });
+
+    let response = client
+        .post(WASMER_REGISTRY_URL)
+        .json(&body)
+        .send()
+        .await
+        .map_err(|e| {
+            BashletError::SandboxInit(format!("Failed to query wasmer registry: {}", e))
+        })?;
+
+    if !response.status().is_success() {
+        return Err(BashletError::SandboxInit(format!(
+            "Failed to query wasmer registry: HTTP {}",
+            response.status()
+        )));
+    }
+
+    let result: serde_json::Value = response.json().await.map_err(|e| {
+        BashletError::SandboxInit(format!("Invalid wasmer registry response: {}", e))
+    })?;
+
+    let url = result
+        .pointer("/data/getPackageVersion/distribution/piritaDownloadUrl")
+        .and_then(|url| url.as_str())
+        .ok_or_else(|| {
+            BashletError::SandboxInit(format!("Wasmer registry has no WEBC for {}", package))
+        })?;
+
+    debug!(package = %package, url = %url, "Resolved WEBC download URL");
+    Ok(url.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_wasmer_command_ends_bash_options() {
+        let backend = WasmerBackend {
+            wasmer_binary: PathBuf::from("wasmer"),
+            webc_path: PathBuf::from("bash.webc"),
+            mounts: Vec::new(),
+            env_vars: Vec::new(),
+            workdir: "/workspace".to_string(),
+            max_output_bytes: 1024,
+            last_command: LastCommand::default(),
+        };
+
+        for command in ["echo -- -n hi", "-n hi"] {
+            let cmd = backend.wasmer_command(command).unwrap();
+            let args: Vec<_> = cmd.as_std().get_args().collect();
+            assert_eq!(args, ["run", "bash.webc", "--", "-c", "--", command]);
+        }
+    }
+
+    #[test]
+    fn test_webc_package_parse() {
+        let package = WebcPackage::parse("wasmer/python@3.11").unwrap();
+        assert_eq!(package.namespace, "wasmer");
+        assert_eq!(package.name, "python");
+        assert_eq!(package.version.as_deref(), Some("3.11"));
+        assert_eq!(package.to_string(), "wasmer/python@3.11");
+        assert!(package
+            .cache_path()
+            .ends_with(Path::new("webc/wasmer/python-3.11.webc")));
+
+        let latest = WebcPackage::parse("syrusakbary/cowsay").unwrap();
+        assert!(latest.cache_path().ends_with("cowsay.webc"));
+
+        for spec in [
+            "python",
+            "wasmer/",
+            "/python",
+            "wasmer/python@",
+            "a/b/c",
+            "../x",
+        ] {
+            assert!(WebcPackage::parse(spec).is_err(), "{}", spec);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_copy_through_mounts() {
+        let mounted = tempfile::tempdir().unwrap();
+        let host = tempfile::tempdir().unwrap();
+        let backend = WasmerBackend {
+            wasmer_binary: PathBuf::from("wasmer"),
+            webc_path: PathBuf::from("bash.webc"),
+            mounts: vec![Mount {
+                host_path: mounted.path().to_path_buf(),
+                guest_path: "/workspace".to_string(),
+                readonly: false,
+                explicit_access: true,
+                options: Vec::new(),
+            }],
+            env_vars: Vec::new(),
+            workdir: "/workspace".to_string(),
+            max_output_bytes: 1024,
+            last_command: LastCommand::default(),
+        };
+
+        let source = host.path().join("data.bin");
+        std::fs::write(&source, [0u8, 159, 146, 150]).unwrap();
+        backend.copy_in(&source, "renamed.bin").await.unwrap();
+        assert!(mounted.path().join("renamed.bin").is_file());
+
+        std::fs::create_dir(mounted.path().join("sub")).unwrap();
+        backend.copy_in(&source, "sub").await.unwrap();
+        assert_eq!(
+            std::fs::read(mounted.path().join("sub/data.bin")).unwrap(),
+            [0u8, 159, 146, 150]
+        );
+
+        backend
+            .copy_out("/workspace/sub", host.path())
+            .await
+            .unwrap();
+        assert!(host.path().join("sub/data.bin").is_file());
+
+        let err = backend.copy_in(&source, "/tmp/data.bin").await.unwrap_err();
+        assert!(matches!(err, BashletError::Unsupported { .. }));
+
+        for escape in ["../outside.bin", "/workspace/../../outside.bin"] {
+            let err = backend.copy_in(&source, escape).await.unwrap_err();
+            assert!(matches!(err, BashletError::SandboxExecution(_)));
+            let err = backend.copy_out(escape, host.path()).await.unwrap_err();
+            assert!(matches!(err, BashletError::SandboxExecution(_)));
+        }
+    }
+}
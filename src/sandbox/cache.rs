@@ -0,0 +1,209 @@
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use crate::config::loader::get_cache_dir;
+use crate::error::Result;
+
+/// Rootfs copies younger than this are never treated as orphaned, since
+/// their VM may still be booting and not yet serving its socket.
+const ORPHAN_GRACE_PERIOD: Duration = Duration::from_secs(300);
+
+/// Directory holding the downloaded wasmer binary.
+pub fn wasmer_dir() -> PathBuf {
+    get_cache_dir().join("wasmer")
+}
+
+/// Path of the cached bash WEBC package.
+pub fn webc_path() -> PathBuf {
+    get_cache_dir().join("bash.webc")
+}
+
+/// Directory holding WEBC packages fetched from the Wasmer registry, as
+/// `NAMESPACE/NAME-VERSION.webc`.
+pub fn webc_dir() -> PathBuf {
+    get_cache_dir().join("webc")
+}
+
+/// Directory holding Firecracker binaries, kernels, and rootfs images.
+pub fn firecracker_dir() -> PathBuf {
+    get_cache_dir().join("firecracker")
+}
+
+/// Directory holding per-VM writable rootfs copies.
+pub fn instances_dir() -> PathBuf {
+    firecracker_dir().join("instances")
+}
+
+/// API socket path of a Firecracker VM instance.
+pub fn instance_socket_path(instance_id: &str) -> PathBuf {
+    std::env::temp_dir().join(format!("firecracker-{}.sock", instance_id))
+}
+
+/// Console log of a Firecracker VM kept running for a session.
+pub fn instance_log_path(instance_id: &str) -> PathBuf {
+    instance_socket_path(instance_id).with_extension("log")
+}
+
+/// Prefix of Firecracker VM instance IDs, which sets them apart from Docker
+/// container IDs in a session's `instance_id`.
+pub const VM_INSTANCE_PREFIX: &str = "fc-";
+
+/// Whether a session's `instance_id` names a Firecracker VM.
+pub fn is_vm_instance(instance_id: &str) -> bool {
+    instance_id.starts_with(VM_INSTANCE_PREFIX)
+}
+
+/// Stop a Firecracker VM kept running for a session, and remove its sockets
+/// and rootfs copy. Does nothing for files that are already gone.
+pub fn stop_instance(instance_id: &str) -> Result<()> {
+    let socket_path = instance_socket_path(instance_id);
+
+    // Only signal the recorded PID while the VM answers, in case it was reused
+    if instance_running(instance_id) {
+        if let Ok(pid) = std::fs::read_to_string(socket_path.with_extension("pid")) {
+            let _ = std::process::Command::new("kill")
+                .arg(pid.trim())
+                .stderr(std::process::Stdio::null())
+                .status();
+        }
+    }
+
+    for extension in ["sock", "vsock", "pid", "log"] {
+        let _ = std::fs::remove_file(socket_path.with_extension(extension));
+    }
+    for suffix in ["rootfs.ext4", "overlay.ext4"] {
+        remove_path(&instances_dir().join(format!("{}.{}", instance_id, suffix)))?;
+    }
+    Ok(())
+}
+
+/// Whether a Firecracker VM is still serving its API socket.
+///
+/// A leftover socket file from a killed process refuses connections, so
+/// connecting is a better liveness check than the file existing.
+pub fn instance_running(instance_id: &str) -> bool {
+    let socket_path = instance_socket_path(instance_id);
+
+    #[cfg(unix)]
+    {
+        std::os::unix::net::UnixStream::connect(&socket_path).is_ok()
+    }
+
+    #[cfg(not(unix))]
+    {
+        socket_path.exists()
+    }
+}
+
+/// Disk usage of one cache category.
+#[derive(Debug)]
+pub struct CacheUsage {
+    pub category: &'static str,
+    pub bytes: u64,
+}
+
+/// Report disk usage per category, plus anything else found in the cache directory.
+pub fn cache_usage() -> Vec<CacheUsage> {
+    let wasmer = dir_size(&wasmer_dir()) + dir_size(&webc_path()) + dir_size(&webc_dir());
+    let instances = dir_size(&instances_dir());
+    let firecracker = dir_size(&firecracker_dir()).saturating_sub(instances);
+    let total = dir_size(&get_cache_dir());
+
+    vec![
+        CacheUsage {
+            category: "wasmer",
+            bytes: wasmer,
+        },
+        CacheUsage {
+            category: "firecracker assets",
+            bytes: firecracker,
+        },
+        CacheUsage {
+            category: "instance rootfs copies",
+            bytes: instances,
+        },
+        CacheUsage {
+            category: "other",
+            bytes: total.saturating_sub(wasmer + firecracker + instances),
+        },
+    ]
+}
+
+/// Remove downloaded runtimes, kernels, and images. Returns bytes freed.
+pub fn clear_assets() -> Result<u64> {
+    let mut freed =
+        remove_path(&wasmer_dir())? + remove_path(&webc_path())? + remove_path(&webc_dir())?;
+
+    if let Ok(entries) = std::fs::read_dir(firecracker_dir()) {
+        for entry in entries.flatten() {
+            if entry.path() != instances_dir() {
+                freed += remove_path(&entry.path())?;
+            }
+        }
+    }
+
+    Ok(freed)
+}
+
+/// Remove rootfs copies and overlays left behind by VMs that are no longer running.
+///
+/// A copy is orphaned when its VM is no longer running, which happens when
+/// bashlet was killed before it could clean up.
+/// Returns the number of files removed and bytes freed.
+pub fn remove_orphaned_instances() -> Result<(usize, u64)> {
+    let mut removed = 0;
+    let mut freed = 0;
+
+    let Ok(entries) = std::fs::read_dir(instances_dir()) else {
+        return Ok((removed, freed));
+    };
+
+    for entry in entries.flatten() {
+        let file_name = entry.file_name();
+        let Some(instance_id) = file_name.to_str().and_then(|name| {
+            name.strip_suffix(".rootfs.ext4")
+                .or_else(|| name.strip_suffix(".overlay.ext4"))
+        }) else {
+            continue;
+        };
+
+        let recently_modified = entry
+            .metadata()
+            .and_then(|m| m.modified())
+            .ok()
+            .and_then(|modified| modified.elapsed().ok())
+            .is_some_and(|age| age < ORPHAN_GRACE_PERIOD);
+
+        if !recently_modified && !instance_running(instance_id) {
+            freed += remove_path(&entry.path())?;
+            removed += 1;
+        }
+    }
+
+    Ok((removed, freed))
+}
+
+/// Total size of a file or directory tree, 0 if it doesn't exist.
+fn dir_size(path: &Path) -> u64 {
+    let Ok(metadata) = std::fs::symlink_metadata(path) else {
+        return 0;
+    };
+    if !metadata.is_dir() {
+        return metadata.len();
+    }
+
+    std::fs::read_dir(path)
+        .map(|entries| entries.flatten().map(|e| dir_size(&e.path())).sum())
+        .unwrap_or(0)
+}
+
+/// Remove a file or directory tree, returning the bytes it occupied.
+fn remove_path(path: &Path) -> Result<u64> {
+    let size = dir_size(path);
+    match std::fs::symlink_metadata(path) {
+        Ok(metadata) if metadata.is_dir() => std::fs::remove_dir_all(path)?,
+        Ok(_) => std::fs::remove_file(path)?,
+        Err(_) => return Ok(0),
+    }
+    Ok(size)
+}
@@ -1,7 +1,9 @@
+use tracing::{debug, warn};
+
 use crate::cli::args::Mount;
 use crate::config::types::{BackendType, SandboxConfig as ConfigSandboxConfig};
 use crate::error::{BashletError, Result};
-use crate::sandbox::traits::SandboxBackend;
+use crate::sandbox::traits::{BackendCapabilities, SandboxBackend};
 
 #[cfg(feature = "wasmer")]
 use crate::sandbox::backends::WasmerBackend;
@@ -16,13 +18,38 @@ use crate::sandbox::backends::SshBackend;
 ///
 /// This combines the persistent configuration from the config file
 /// with the runtime parameters (mounts, env vars, etc.) from the CLI.
+#[derive(Clone)]
 pub struct RuntimeConfig {
     pub mounts: Vec<Mount>,
     pub env_vars: Vec<(String, String)>,
+    /// Environment variables that must not be logged or persisted
+    pub secret_env_vars: Vec<(String, String)>,
     pub workdir: String,
     pub memory_limit_mb: u64,
     #[allow(dead_code)]
     pub timeout_seconds: u64,
+    pub max_output_bytes: u64,
+    /// Existing instance to reattach to instead of starting a new one
+    pub instance_id: Option<String>,
+    /// DNS and host overrides, applied when networking is enabled
+    pub network: NetworkSettings,
+    /// Capabilities the backend must provide (constrains Auto selection)
+    pub required: BackendCapabilities,
+}
+
+/// Name resolution overrides for backends with networking.
+#[derive(Debug, Clone, Default)]
+pub struct NetworkSettings {
+    /// DNS server IP addresses
+    pub dns: Vec<String>,
+    /// Extra host entries as (hostname, ip)
+    pub extra_hosts: Vec<(String, String)>,
+}
+
+impl NetworkSettings {
+    pub fn is_empty(&self) -> bool {
+        self.dns.is_empty() && self.extra_hosts.is_empty()
+    }
 }
 
 /// Create a sandbox backend based on the configuration.
@@ -33,7 +60,80 @@ pub async fn create_backend(
     config: &ConfigSandboxConfig,
     runtime: RuntimeConfig,
 ) -> Result<Box<dyn SandboxBackend>> {
-    let backend_type = resolve_backend_type(&config.backend)?;
+    let mut backend_type = resolve_backend_type(config, &runtime.required)?;
+    if config.backend != BackendType::Auto {
+        return instantiate_backend(&backend_type, config, runtime).await;
+    }
+
+    let mut excluded = Vec::new();
+    loop {
+        let result = instantiate_backend(&backend_type, config, runtime.clone()).await;
+        let reason = match result {
+            Err(ref e) => match auto_fallback_reason(&backend_type, config, e) {
+                Some(reason) => reason,
+                None => return result,
+            },
+            Ok(_) => return result,
+        };
+
+        excluded.push(backend_type.clone());
+        let fallback = resolve_auto(config, &runtime.required, &excluded)?;
+        warn!(
+            backend = ?backend_type,
+            reason = %reason,
+            fallback = ?fallback,
+            "Skipping backend, falling back to another"
+        );
+        backend_type = fallback;
+    }
+}
+
+/// Why Auto should move on to the next backend after `error`, or None if
+/// the error should be reported as is.
+fn auto_fallback_reason(
+    backend_type: &BackendType,
+    config: &ConfigSandboxConfig,
+    error: &BashletError,
+) -> Option<String> {
+    match (backend_type, error) {
+        // /dev/kvm can be present on hosts where VMs still can't boot (e.g.
+        // broken nested virtualization)
+        (BackendType::Firecracker, BashletError::VMBootFailed(reason))
+            if config.firecracker.fallback_on_boot_failure =>
+        {
+            Some(format!("Firecracker failed to boot: {}", reason))
+        }
+        // Docker is installed but the sandbox image is missing and can't be built
+        (BackendType::Docker, BashletError::DockerImageUnavailable { .. }) => {
+            Some(error.to_string())
+        }
+        _ => None,
+    }
+}
+
+/// Construct a backend of an already resolved type.
+async fn instantiate_backend(
+    backend_type: &BackendType,
+    config: &ConfigSandboxConfig,
+    runtime: RuntimeConfig,
+) -> Result<Box<dyn SandboxBackend>> {
+    if *backend_type != BackendType::Docker
+        && (config.docker.seccomp_profile.is_some() || config.docker.apparmor_profile.is_some())
+    {
+        debug!(
+            backend = ?backend_type,
+            "Ignoring Docker seccomp/AppArmor profiles for a non-Docker backend"
+        );
+    }
+
+    // Only Docker keeps secrets out of process arguments; elsewhere they are plain env vars
+    let (env_vars, secret_env_vars) = match backend_type {
+        BackendType::Docker => (runtime.env_vars, runtime.secret_env_vars),
+        _ => (
+            [runtime.env_vars, runtime.secret_env_vars].concat(),
+            Vec::new(),
+        ),
+    };
 
     match backend_type {
         #[cfg(feature = "wasmer")]
@@ -41,8 +141,11 @@ pub async fn create_backend(
             let backend = WasmerBackend::new(
                 config.wasmer.clone(),
                 runtime.mounts,
-                runtime.env_vars,
+                env_vars,
                 runtime.workdir,
+                runtime.max_output_bytes,
+                config.offline,
+                config.shell.clone(),
             )
             .await?;
             Ok(Box::new(backend))
@@ -58,9 +161,14 @@ pub async fn create_backend(
             let backend = FirecrackerBackend::new(
                 config.firecracker.clone(),
                 runtime.mounts,
-                runtime.env_vars,
+                env_vars,
                 runtime.workdir,
                 runtime.memory_limit_mb,
+                runtime.max_output_bytes,
+                runtime.instance_id,
+                runtime.network,
+                config.offline,
+                config.shell.clone(),
             )
             .await?;
             Ok(Box::new(backend))
@@ -80,9 +188,15 @@ pub async fn create_backend(
             let backend = DockerBackend::new(
                 config.docker.clone(),
                 runtime.mounts,
-                runtime.env_vars,
+                env_vars,
+                secret_env_vars,
                 runtime.workdir,
                 runtime.memory_limit_mb,
+                runtime.max_output_bytes,
+                runtime.instance_id,
+                runtime.network,
+                config.offline,
+                config.shell.clone(),
             )
             .await?;
             Ok(Box::new(backend))
@@ -91,49 +205,62 @@ pub async fn create_backend(
         BackendType::Ssh => {
             let backend = SshBackend::new(
                 config.ssh.clone(),
-                runtime.env_vars,
+                env_vars,
                 runtime.workdir,
+                runtime.max_output_bytes,
+                config.shell.clone(),
             )
             .await?;
             Ok(Box::new(backend))
         }
 
-        BackendType::Auto => {
-            // Already resolved by resolve_backend_type
-            unreachable!()
-        }
+        // resolve_backend_type always picks a concrete backend, but don't
+        // panic if a caller skips it
+        BackendType::Auto => Err(BashletError::BackendNotAvailable {
+            backend: "auto".to_string(),
+            reason: "auto must be resolved to a concrete backend before one is created".to_string(),
+        }),
     }
 }
 
-/// Resolve the backend type, handling Auto selection.
-fn resolve_backend_type(requested: &BackendType) -> Result<BackendType> {
-    match requested {
-        BackendType::Auto => {
-            // Priority: Firecracker (Linux + KVM) > Docker > Wasmer
-            #[cfg(all(feature = "firecracker", target_os = "linux"))]
-            {
-                if FirecrackerBackend::is_available() {
-                    return Ok(BackendType::Firecracker);
-                }
-            }
-
-            if DockerBackend::is_available() {
-                return Ok(BackendType::Docker);
-            }
-
-            #[cfg(feature = "wasmer")]
-            {
-                return Ok(BackendType::Wasmer);
-            }
+/// Capabilities a backend type provides with the given configuration,
+/// known without constructing it.
+pub fn capabilities_for(
+    backend: &BackendType,
+    config: &ConfigSandboxConfig,
+) -> BackendCapabilities {
+    match backend {
+        BackendType::Wasmer => BackendCapabilities::default(),
+        BackendType::Firecracker => BackendCapabilities {
+            native_linux: true,
+            networking: config.firecracker.enable_networking,
+            persistent_fs: true,
+        },
+        BackendType::Docker => BackendCapabilities {
+            native_linux: true,
+            networking: config.docker.enable_networking,
+            persistent_fs: config.docker.session_mode,
+        },
+        BackendType::Ssh => BackendCapabilities {
+            native_linux: true,
+            networking: true,
+            persistent_fs: true,
+        },
+        // Auto guarantees nothing until it is resolved
+        BackendType::Auto => BackendCapabilities::default(),
+    }
+}
 
-            #[cfg(not(feature = "wasmer"))]
-            {
-                return Err(BashletError::BackendNotAvailable {
-                    backend: "auto".to_string(),
-                    reason: "No sandbox backends available".to_string(),
-                });
-            }
-        }
+/// Resolve the backend type, handling Auto selection.
+///
+/// Auto picks the first available backend that provides every `required`
+/// capability; an explicit backend that lacks one is an error.
+fn resolve_backend_type(
+    config: &ConfigSandboxConfig,
+    required: &BackendCapabilities,
+) -> Result<BackendType> {
+    let resolved = match &config.backend {
+        BackendType::Auto => return resolve_auto(config, required, &[]),
         BackendType::Firecracker => {
             #[cfg(all(feature = "firecracker", target_os = "linux"))]
             {
@@ -145,7 +272,7 @@ fn resolve_backend_type(requested: &BackendType) -> Result<BackendType> {
                     });
                 }
             }
-            Ok(BackendType::Firecracker)
+            BackendType::Firecracker
         }
         BackendType::Docker => {
             if !DockerBackend::is_available() {
@@ -155,7 +282,7 @@ fn resolve_backend_type(requested: &BackendType) -> Result<BackendType> {
                         .to_string(),
                 });
             }
-            Ok(BackendType::Docker)
+            BackendType::Docker
         }
         BackendType::Ssh => {
             if !SshBackend::is_available() {
@@ -164,10 +291,90 @@ fn resolve_backend_type(requested: &BackendType) -> Result<BackendType> {
                     reason: "SSH client is not installed or not accessible.".to_string(),
                 });
             }
-            Ok(BackendType::Ssh)
+            BackendType::Ssh
+        }
+        other => other.clone(),
+    };
+
+    let missing = capabilities_for(&resolved, config).missing(required);
+    if !missing.is_empty() {
+        return Err(BashletError::BackendNotAvailable {
+            backend: format!("{:?}", resolved).to_lowercase(),
+            reason: format!(
+                "it does not provide the required capabilities ({}) with the current configuration",
+                missing.join(", ")
+            ),
+        });
+    }
+
+    Ok(resolved)
+}
+
+/// Pick the first available backend, in priority order, that provides every
+/// `required` capability and isn't in `excluded`.
+fn resolve_auto(
+    config: &ConfigSandboxConfig,
+    required: &BackendCapabilities,
+    excluded: &[BackendType],
+) -> Result<BackendType> {
+    validate_auto_priority(&config.auto_priority)?;
+
+    let candidates: Vec<BackendType> = config
+        .auto_priority
+        .iter()
+        .filter(|backend| !excluded.contains(backend) && auto_can_use(backend, config))
+        .cloned()
+        .collect();
+
+    let reason = if candidates.is_empty() {
+        "No sandbox backends available".to_string()
+    } else {
+        format!(
+            "No available backend provides the required capabilities ({})",
+            BackendCapabilities::default().missing(required).join(", ")
+        )
+    };
+
+    candidates
+        .into_iter()
+        .find(|backend| capabilities_for(backend, config).missing(required).is_empty())
+        .ok_or(BashletError::BackendNotAvailable {
+            backend: "auto".to_string(),
+            reason,
+        })
+}
+
+/// Whether `auto` can pick `backend` on this host.
+fn auto_can_use(backend: &BackendType, config: &ConfigSandboxConfig) -> bool {
+    match backend {
+        #[cfg(all(feature = "firecracker", target_os = "linux"))]
+        BackendType::Firecracker => FirecrackerBackend::is_available(),
+        BackendType::Docker => DockerBackend::is_available(),
+        #[cfg(feature = "wasmer")]
+        BackendType::Wasmer => true,
+        // Only when a host is configured
+        BackendType::Ssh => !config.ssh.host.is_empty() && SshBackend::is_available(),
+        _ => false,
+    }
+}
+
+/// Check `sandbox.auto_priority` names each concrete backend at most once.
+pub fn validate_auto_priority(priority: &[BackendType]) -> Result<()> {
+    for (i, backend) in priority.iter().enumerate() {
+        if *backend == BackendType::Auto {
+            return Err(BashletError::Config(
+                "sandbox.auto_priority can't include auto".to_string(),
+            ));
+        }
+        if priority[..i].contains(backend) {
+            let name = format!("{:?}", backend).to_lowercase();
+            return Err(BashletError::Config(format!(
+                "sandbox.auto_priority lists {} more than once",
+                name
+            )));
         }
-        other => Ok(other.clone()),
     }
+    Ok(())
 }
 
 /// Get information about available backends on this system.
@@ -258,3 +465,39 @@ pub struct BackendInfo {
     pub description: &'static str,
     pub unavailable_reason: Option<&'static str>,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_validate_auto_priority() {
+        assert!(validate_auto_priority(&ConfigSandboxConfig::default().auto_priority).is_ok());
+        assert!(validate_auto_priority(&[BackendType::Docker, BackendType::Wasmer]).is_ok());
+        assert!(validate_auto_priority(&[BackendType::Docker, BackendType::Docker]).is_err());
+        assert!(validate_auto_priority(&[BackendType::Auto]).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_instantiate_unresolved_auto() {
+        let runtime = RuntimeConfig {
+            mounts: Vec::new(),
+            env_vars: Vec::new(),
+            secret_env_vars: Vec::new(),
+            workdir: "/workspace".to_string(),
+            memory_limit_mb: 256,
+            timeout_seconds: 300,
+            max_output_bytes: 1024,
+            instance_id: None,
+            network: NetworkSettings::default(),
+            required: BackendCapabilities::default(),
+        };
+
+        let result =
+            instantiate_backend(&BackendType::Auto, &ConfigSandboxConfig::default(), runtime).await;
+        assert!(matches!(
+            result,
+            Err(BashletError::BackendNotAvailable { ref backend, .. }) if backend == "auto"
+        ));
+    }
+}
@@ -0,0 +1,94 @@
+//! Allow/deny filtering of commands before they reach a backend.
+//!
+//! This is a guardrail against mistakes, not a security boundary: patterns
+//! only see the command text, so anything that builds a command at run time
+//! (variables, `eval`, scripts on a mount, `base64 -d | sh`) gets past them.
+
+use regex::Regex;
+
+use crate::config::types::SandboxConfig;
+use crate::error::{BashletError, Result};
+
+/// Compiled `command_allowlist` and `command_denylist` patterns.
+#[derive(Debug, Default)]
+pub struct CommandFilter {
+    allow: Vec<Regex>,
+    deny: Vec<Regex>,
+}
+
+impl CommandFilter {
+    /// Compile the filter lists from the sandbox configuration.
+    pub fn from_config(config: &SandboxConfig) -> Result<Self> {
+        Ok(Self {
+            allow: compile("command_allowlist", &config.command_allowlist)?,
+            deny: compile("command_denylist", &config.command_denylist)?,
+        })
+    }
+
+    /// Reject a command that matches the denylist, or misses a non-empty allowlist.
+    pub fn check(&self, command: &str) -> Result<()> {
+        if let Some(pattern) = self.deny.iter().find(|re| re.is_match(command)) {
+            return Err(BashletError::CommandRejected {
+                command: command.to_string(),
+                reason: format!("matches denied pattern '{}'", pattern),
+            });
+        }
+        if !self.allow.is_empty() && !self.allow.iter().any(|re| re.is_match(command)) {
+            return Err(BashletError::CommandRejected {
+                command: command.to_string(),
+                reason: "matches no allowed pattern".to_string(),
+            });
+        }
+        Ok(())
+    }
+}
+
+fn compile(setting: &str, patterns: &[String]) -> Result<Vec<Regex>> {
+    patterns
+        .iter()
+        .map(|p| {
+            Regex::new(p).map_err(|e| {
+                BashletError::Config(format!("Invalid pattern in sandbox.{}: {}", setting, e))
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn filter(allow: &[&str], deny: &[&str]) -> CommandFilter {
+        let config = SandboxConfig {
+            command_allowlist: allow.iter().map(|s| s.to_string()).collect(),
+            command_denylist: deny.iter().map(|s| s.to_string()).collect(),
+            ..SandboxConfig::default()
+        };
+        CommandFilter::from_config(&config).unwrap()
+    }
+
+    #[test]
+    fn test_command_filter() {
+        let open = filter(&[], &[]);
+        assert!(open.check("rm -rf /").is_ok());
+
+        let deny = filter(&[], &[r"rm\s+-rf\s+/(\s|$)"]);
+        assert!(deny.check("rm -rf /").is_err());
+        assert!(deny.check("rm -rf /tmp/build").is_ok());
+
+        // The denylist wins over the allowlist
+        let both = filter(&[r"^(ls|cat|rm)\b"], &[r"\brm\b"]);
+        assert!(both.check("ls -la").is_ok());
+        assert!(both.check("rm file").is_err());
+        assert!(both.check("curl example.com").is_err());
+    }
+
+    #[test]
+    fn test_invalid_pattern() {
+        let config = SandboxConfig {
+            command_denylist: vec!["(".to_string()],
+            ..SandboxConfig::default()
+        };
+        assert!(CommandFilter::from_config(&config).is_err());
+    }
+}
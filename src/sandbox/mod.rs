@@ -1,15 +1,38 @@
 mod backends;
+pub mod cache;
 mod factory;
+mod filter;
+mod output;
 mod traits;
 
 #[cfg(feature = "wasmer")]
 pub use backends::WasmerBackend;
 
+#[cfg(feature = "wasmer")]
+pub(crate) use backends::{cached_wasmer_path, WASMER_VERSION};
+
 #[cfg(all(feature = "firecracker", target_os = "linux"))]
 pub use backends::FirecrackerBackend;
 
-pub use factory::{available_backends, create_backend, BackendInfo, RuntimeConfig};
-pub use traits::{BackendCapabilities, SandboxBackend, SandboxInfo};
+#[cfg(all(feature = "firecracker", target_os = "linux"))]
+pub(crate) use backends::FIRECRACKER_VERSION;
+
+pub use backends::{DockerBackend, SshBackend};
+
+pub use factory::{
+    available_backends, capabilities_for, create_backend, validate_auto_priority, BackendInfo,
+    NetworkSettings, RuntimeConfig,
+};
+pub use filter::CommandFilter;
+pub use output::DEFAULT_MAX_OUTPUT_BYTES;
+pub use traits::{
+    BackendCapabilities, DirEntry, EntryType, FileStat, SandboxBackend, SandboxInfo,
+};
+
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+
+use serde::Serialize;
 
 /// Result of executing a command in the sandbox.
 #[derive(Debug)]
@@ -17,4 +40,190 @@ pub struct CommandResult {
     pub stdout: String,
     pub stderr: String,
     pub exit_code: i32,
+    /// Whether stdout or stderr exceeded the output limit and was truncated
+    pub truncated: bool,
+    /// How long the command took, if the backend measured it
+    pub timing: Option<CommandTiming>,
+}
+
+/// Host files that receive a command's stdout/stderr in full, in place of
+/// the capped strings in its [`CommandResult`].
+#[derive(Debug, Clone, Default)]
+pub struct OutputFiles {
+    pub stdout: Option<PathBuf>,
+    pub stderr: Option<PathBuf>,
+}
+
+impl OutputFiles {
+    pub fn is_empty(&self) -> bool {
+        self.stdout.is_none() && self.stderr.is_none()
+    }
+
+    /// Move captured output into the files, for backends that can't stream it.
+    pub(crate) fn write_result(&self, result: &mut CommandResult) -> std::io::Result<()> {
+        if let Some(ref path) = self.stdout {
+            std::fs::write(path, std::mem::take(&mut result.stdout))?;
+        }
+        if let Some(ref path) = self.stderr {
+            std::fs::write(path, std::mem::take(&mut result.stderr))?;
+        }
+        Ok(())
+    }
+}
+
+/// Wall-clock timing of a command.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct CommandTiming {
+    /// Time spent running the command, in milliseconds
+    pub total_ms: u64,
+    /// Backend-specific phases, such as container start or VM boot.
+    ///
+    /// Startup phases happen once, before the first command, and are only
+    /// reported with that command.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub phases: Vec<TimingPhase>,
+}
+
+impl CommandTiming {
+    /// Timing of a command that started at `started` and just finished.
+    pub(crate) fn since(started: Instant, phases: Vec<TimingPhase>) -> Self {
+        Self {
+            total_ms: elapsed_ms(started),
+            phases,
+        }
+    }
+}
+
+/// A named phase of backend work.
+#[derive(Debug, Clone, Serialize)]
+pub struct TimingPhase {
+    pub name: &'static str,
+    pub ms: u64,
+}
+
+impl TimingPhase {
+    /// A phase that started at `started` and just finished.
+    pub(crate) fn since(name: &'static str, started: Instant) -> Self {
+        Self {
+            name,
+            ms: elapsed_ms(started),
+        }
+    }
+}
+
+fn elapsed_ms(started: Instant) -> u64 {
+    started.elapsed().as_millis().try_into().unwrap_or(u64::MAX)
+}
+
+/// Quote a string as a single shell word.
+///
+/// Wraps it in single quotes, where nothing is special except the quote
+/// itself, which is written as `'\''` (close, escaped quote, reopen).
+pub(crate) fn shell_quote(s: &str) -> String {
+    format!("'{}'", s.replace('\'', "'\\''"))
+}
+
+/// Join an argument vector into a shell command that runs it verbatim.
+pub(crate) fn shell_join(argv: &[String]) -> String {
+    argv.iter()
+        .map(|arg| shell_quote(arg))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Wrap `command` so whatever shell receives it hands it to `shell` instead.
+pub(crate) fn shell_command(shell: &str, command: &str) -> String {
+    format!("exec {} -c {}", shell_quote(shell), shell_quote(command))
+}
+
+/// Resolve a file tool path against the sandbox working directory.
+///
+/// Absolute paths are returned unchanged.
+pub(crate) fn resolve_path(workdir: &str, path: &str) -> String {
+    if path.starts_with('/') {
+        return path.to_string();
+    }
+    let path = path.trim_start_matches("./");
+    if path.is_empty() || path == "." {
+        return workdir.to_string();
+    }
+    format!("{}/{}", workdir.trim_end_matches('/'), path)
+}
+
+/// Copy a host file or directory tree like `cp -r`: when `dest` is an
+/// existing directory, `source` is copied into it.
+pub(crate) fn copy_host_path(source: &Path, dest: &Path) -> std::io::Result<()> {
+    let dest = match source.file_name() {
+        Some(name) if dest.is_dir() => dest.join(name),
+        _ => dest.to_path_buf(),
+    };
+    copy_tree(source, &dest)
+}
+
+fn copy_tree(source: &Path, dest: &Path) -> std::io::Result<()> {
+    if !source.is_dir() {
+        return std::fs::copy(source, dest).map(|_| ());
+    }
+
+    std::fs::create_dir_all(dest)?;
+    for entry in std::fs::read_dir(source)? {
+        let entry = entry?;
+        copy_tree(&entry.path(), &dest.join(entry.file_name()))?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    #[test]
+    fn test_shell_quote() {
+        assert_eq!(shell_quote("plain"), "'plain'");
+        assert_eq!(shell_quote("it's"), "'it'\\''s'");
+        assert_eq!(shell_quote(r"C:\dir"), r"'C:\dir'");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_shell_command() {
+        let wrapped = shell_command("/bin/sh", r#"printf '%s' "$0 it's""#);
+        let output = std::process::Command::new("sh")
+            .args(["-c", &wrapped])
+            .output()
+            .unwrap();
+        assert_eq!(String::from_utf8_lossy(&output.stdout), "/bin/sh it's");
+    }
+
+    #[test]
+    fn test_resolve_path() {
+        assert_eq!(resolve_path("/workspace", "/etc/hosts"), "/etc/hosts");
+        assert_eq!(resolve_path("/workspace", "out.txt"), "/workspace/out.txt");
+        assert_eq!(
+            resolve_path("/workspace/", "./src/a.rs"),
+            "/workspace/src/a.rs"
+        );
+        assert_eq!(resolve_path("/workspace", "."), "/workspace");
+        assert_eq!(resolve_path("/", "tmp"), "/tmp");
+    }
+
+    #[test]
+    fn test_shell_join() {
+        let argv = ["echo".to_string(), "-n".to_string(), "a b; rm".to_string()];
+        assert_eq!(shell_join(&argv), "'echo' '-n' 'a b; rm'");
+    }
+
+    proptest! {
+        #[cfg(unix)]
+        #[test]
+        fn test_shell_quote_round_trips(content in "[^\\x00]*") {
+            let output = std::process::Command::new("sh")
+                .arg("-c")
+                .arg(format!("printf '%s' {}", shell_quote(&content)))
+                .output()
+                .unwrap();
+            prop_assert_eq!(String::from_utf8_lossy(&output.stdout), content);
+        }
+    }
 }
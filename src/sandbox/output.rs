@@ -0,0 +1,267 @@
+use std::path::Path;
+use std::process::{ExitStatus, Stdio};
+
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWriteExt};
+use tokio::process::Command;
+
+use crate::sandbox::OutputFiles;
+
+/// Default cap on captured stdout/stderr per stream (10 MiB).
+pub const DEFAULT_MAX_OUTPUT_BYTES: u64 = 10 * 1024 * 1024;
+
+/// Output of a child process captured with bounded buffering.
+pub(crate) struct CapturedOutput {
+    pub status: ExitStatus,
+    pub stdout: String,
+    pub stderr: String,
+    /// Whether either stream exceeded the limit and was truncated
+    pub truncated: bool,
+}
+
+/// Spawn `cmd` and capture its output, keeping at most `max_bytes` per stream.
+///
+/// Both streams are drained to completion so the child never blocks on a full
+/// pipe; bytes past the limit are discarded and replaced with a marker.
+/// When `stdin` is given it is written to the child concurrently with
+/// draining the output, so a child that fills its output pipe before reading
+/// all of its input can't deadlock.
+///
+/// Streams with a file in `files` are copied to it uncapped and come back empty.
+///
+/// The child is killed if the returned future is dropped, so cancelling an
+/// `execute` (Ctrl-C, a timeout, a `select!`) doesn't leave it running.
+pub(crate) async fn capture_output(
+    cmd: &mut Command,
+    max_bytes: u64,
+    stdin: Option<&[u8]>,
+    files: &OutputFiles,
+) -> std::io::Result<CapturedOutput> {
+    cmd.stdout(Stdio::piped());
+    cmd.stderr(Stdio::piped());
+    if stdin.is_some() {
+        cmd.stdin(Stdio::piped());
+    }
+    cmd.kill_on_drop(true);
+
+    let mut child = cmd.spawn()?;
+    let stdout = child.stdout.take();
+    let stderr = child.stderr.take();
+    let pipe = child.stdin.take();
+
+    let write_stdin = async {
+        if let (Some(mut pipe), Some(input)) = (pipe, stdin) {
+            match pipe.write_all(input).await {
+                // The command may exit without reading all of its input
+                Err(e) if e.kind() == std::io::ErrorKind::BrokenPipe => {}
+                result => result?,
+            }
+            // Dropping the pipe closes it, signalling EOF
+        }
+        Ok(())
+    };
+
+    let ((), (stdout, stdout_omitted), (stderr, stderr_omitted)) = tokio::try_join!(
+        write_stdin,
+        drain(stdout, max_bytes, files.stdout.as_deref()),
+        drain(stderr, max_bytes, files.stderr.as_deref())
+    )?;
+    let status = child.wait().await?;
+
+    Ok(CapturedOutput {
+        status,
+        stdout: with_marker(
+            String::from_utf8_lossy(&stdout).into_owned(),
+            stdout_omitted,
+        ),
+        stderr: with_marker(
+            String::from_utf8_lossy(&stderr).into_owned(),
+            stderr_omitted,
+        ),
+        truncated: stdout_omitted > 0 || stderr_omitted > 0,
+    })
+}
+
+/// Truncate output that was already received in full (e.g. from the guest agent).
+///
+/// Returns the possibly truncated string and whether truncation happened.
+#[cfg(any(test, all(feature = "firecracker", target_os = "linux")))]
+pub(crate) fn truncate_output(mut output: String, max_bytes: u64) -> (String, bool) {
+    let max_bytes = usize::try_from(max_bytes).unwrap_or(usize::MAX);
+    if output.len() <= max_bytes {
+        return (output, false);
+    }
+
+    let mut cut = max_bytes;
+    while !output.is_char_boundary(cut) {
+        cut -= 1;
+    }
+    let omitted = (output.len() - cut) as u64;
+    output.truncate(cut);
+
+    (with_marker(output, omitted), true)
+}
+
+/// Copy a stream to `file` if given, otherwise read it with [`read_capped`].
+async fn drain<R: AsyncRead + Unpin>(
+    stream: Option<R>,
+    max_bytes: u64,
+    file: Option<&Path>,
+) -> std::io::Result<(Vec<u8>, u64)> {
+    let Some(path) = file else {
+        return read_capped(stream, max_bytes).await;
+    };
+
+    let mut file = tokio::fs::File::create(path).await?;
+    if let Some(mut stream) = stream {
+        tokio::io::copy(&mut stream, &mut file).await?;
+    }
+    file.flush().await?;
+
+    Ok((Vec::new(), 0))
+}
+
+/// Read a stream to EOF, keeping the first `max_bytes` and counting the rest.
+async fn read_capped<R: AsyncRead + Unpin>(
+    stream: Option<R>,
+    max_bytes: u64,
+) -> std::io::Result<(Vec<u8>, u64)> {
+    let mut kept = Vec::new();
+    let mut omitted = 0u64;
+
+    let Some(mut stream) = stream else {
+        return Ok((kept, omitted));
+    };
+
+    let max_bytes = usize::try_from(max_bytes).unwrap_or(usize::MAX);
+    let mut buf = [0u8; 8192];
+    loop {
+        let n = stream.read(&mut buf).await?;
+        if n == 0 {
+            break;
+        }
+        let take = max_bytes.saturating_sub(kept.len()).min(n);
+        kept.extend_from_slice(&buf[..take]);
+        omitted += (n - take) as u64;
+    }
+
+    Ok((kept, omitted))
+}
+
+/// Append the truncation marker when bytes were omitted.
+fn with_marker(mut output: String, omitted: u64) -> String {
+    if omitted > 0 {
+        output.push_str(&format!(
+            "\n[output truncated: {} bytes omitted]\n",
+            omitted
+        ));
+    }
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_truncate_output() {
+        let (out, truncated) = truncate_output("hello".to_string(), 10);
+        assert_eq!(out, "hello");
+        assert!(!truncated);
+
+        let (out, truncated) = truncate_output("hello world".to_string(), 5);
+        assert_eq!(out, "hello\n[output truncated: 6 bytes omitted]\n");
+        assert!(truncated);
+
+        // Never split a multi-byte character
+        let (out, truncated) = truncate_output("aé".to_string(), 2);
+        assert_eq!(out, "a\n[output truncated: 2 bytes omitted]\n");
+        assert!(truncated);
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_capture_output_caps_streams() {
+        let mut cmd = Command::new("sh");
+        cmd.args(["-c", "printf 'abcdefghij'; printf 'xy' >&2"]);
+
+        let output = capture_output(&mut cmd, 4, None, &OutputFiles::default())
+            .await
+            .unwrap();
+        assert!(output.status.success());
+        assert_eq!(output.stdout, "abcd\n[output truncated: 6 bytes omitted]\n");
+        assert_eq!(output.stderr, "xy");
+        assert!(output.truncated);
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_capture_output() {
+        // Larger than a pipe buffer, so writing and reading must overlap
+        let input = "x".repeat(1024 * 1024);
+        let mut cmd = Command::new("cat");
+
+        let output = capture_output(
+            &mut cmd,
+            u64::MAX,
+            Some(input.as_bytes()),
+            &OutputFiles::default(),
+        )
+        .await
+        .unwrap();
+        assert!(output.status.success());
+        assert_eq!(output.stdout.len(), input.len());
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_capture_output_to_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let files = OutputFiles {
+            stdout: Some(dir.path().join("out")),
+            stderr: None,
+        };
+        let mut cmd = Command::new("sh");
+        cmd.args(["-c", "printf 'abcdefghij'; printf 'xy' >&2"]);
+
+        // The file is not subject to the cap
+        let output = capture_output(&mut cmd, 4, None, &files).await.unwrap();
+        assert_eq!(output.stdout, "");
+        assert_eq!(output.stderr, "xy");
+        assert!(!output.truncated);
+        assert_eq!(
+            std::fs::read_to_string(dir.path().join("out")).unwrap(),
+            "abcdefghij"
+        );
+    }
+
+    #[cfg(target_os = "linux")]
+    #[tokio::test]
+    async fn test_capture_output_kills_child_when_dropped() {
+        let dir = tempfile::tempdir().unwrap();
+        let pid_file = dir.path().join("pid");
+        let mut cmd = Command::new("sh");
+        cmd.arg("-c")
+            .arg(format!("echo $$ > {}; exec sleep 30", pid_file.display()));
+
+        let files = OutputFiles::default();
+        let capture = capture_output(&mut cmd, u64::MAX, None, &files);
+        let result = tokio::time::timeout(std::time::Duration::from_millis(500), capture).await;
+        assert!(result.is_err());
+
+        // Killed children may linger briefly as zombies until reaped
+        let pid = std::fs::read_to_string(&pid_file).unwrap();
+        let stat = format!("/proc/{}/stat", pid.trim());
+        let running = || {
+            std::fs::read_to_string(&stat)
+                .map(|s| !s.contains(") Z "))
+                .unwrap_or(false)
+        };
+        for _ in 0..50 {
+            if !running() {
+                break;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+        }
+        assert!(!running());
+    }
+}
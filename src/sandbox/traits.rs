@@ -1,12 +1,20 @@
 use std::collections::HashMap;
+use std::path::Path;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
 
 use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
 
-use crate::error::Result;
-use crate::sandbox::CommandResult;
+use crate::error::{BashletError, Result};
+use crate::sandbox::{shell_join, shell_quote, CommandResult, OutputFiles, TimingPhase};
 
 /// Capability flags indicating what the backend supports
-#[derive(Debug, Clone, Default)]
+///
+/// Also used to express what a caller requires of a backend.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
 pub struct BackendCapabilities {
     /// Supports full Linux environment (native binaries, not WASM)
     pub native_linux: bool,
@@ -16,6 +24,91 @@ pub struct BackendCapabilities {
     pub persistent_fs: bool,
 }
 
+impl BackendCapabilities {
+    /// Names of the capabilities in `required` that this set lacks.
+    pub fn missing(&self, required: &BackendCapabilities) -> Vec<&'static str> {
+        [
+            ("native", required.native_linux && !self.native_linux),
+            ("network", required.networking && !self.networking),
+            (
+                "persistent-fs",
+                required.persistent_fs && !self.persistent_fs,
+            ),
+        ]
+        .into_iter()
+        .filter_map(|(name, lacking)| lacking.then_some(name))
+        .collect()
+    }
+}
+
+/// Metadata about a path inside the sandbox
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FileStat {
+    /// Size in bytes
+    pub size: u64,
+    /// Whether the path is a directory
+    pub is_dir: bool,
+    /// Permission bits (e.g. 0o644)
+    pub mode: u32,
+}
+
+impl FileStat {
+    /// Parse the output of `stat -c '%s %a %F'`, e.g. `4096 755 directory`.
+    fn parse(output: &str) -> Option<Self> {
+        let mut fields = output.trim().splitn(3, ' ');
+        let size = fields.next()?.parse().ok()?;
+        let mode = u32::from_str_radix(fields.next()?, 8).ok()?;
+        let is_dir = fields.next()? == "directory";
+        Some(Self { size, is_dir, mode })
+    }
+}
+
+/// Kind of a directory entry
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum EntryType {
+    File,
+    Dir,
+    Symlink,
+    /// Device, socket, or FIFO
+    Other,
+}
+
+/// One entry of a directory listing, from [`SandboxBackend::list_dir_entries`]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DirEntry {
+    /// File name, without the directory
+    pub name: String,
+    /// Size in bytes (of the link itself for symlinks)
+    pub size: u64,
+    pub entry_type: EntryType,
+    /// Permission bits (e.g. 0o644)
+    pub mode: u32,
+}
+
+impl DirEntry {
+    /// Parse a line of `stat -c '%s %f %n'` run on `./NAME`, e.g. `4096 41ed ./src`.
+    fn parse(line: &str) -> Option<Self> {
+        let mut fields = line.splitn(3, ' ');
+        let size = fields.next()?.parse().ok()?;
+        let raw_mode = u32::from_str_radix(fields.next()?, 16).ok()?;
+        let name = fields.next()?.strip_prefix("./")?.to_string();
+
+        let entry_type = match raw_mode & 0o170000 {
+            0o100000 => EntryType::File,
+            0o040000 => EntryType::Dir,
+            0o120000 => EntryType::Symlink,
+            _ => EntryType::Other,
+        };
+        Some(Self {
+            name,
+            size,
+            entry_type,
+            mode: raw_mode & 0o7777,
+        })
+    }
+}
+
 /// Information about the running sandbox instance
 #[derive(Debug, Clone)]
 pub struct SandboxInfo {
@@ -25,10 +118,60 @@ pub struct SandboxInfo {
     pub instance_id: Option<String>,
     /// Whether the sandbox is currently running
     pub running: bool,
+    /// When the last command was run (Unix epoch seconds), if any
+    pub last_command_at: Option<u64>,
+    /// Result of a liveness probe; only set by [`SandboxBackend::status`]
+    pub last_health_check: Option<bool>,
     /// Additional backend-specific metadata
     pub metadata: HashMap<String, String>,
 }
 
+/// Records when a backend last ran a command, for [`SandboxInfo::last_command_at`].
+#[derive(Debug, Default)]
+pub(crate) struct LastCommand(AtomicU64);
+
+impl LastCommand {
+    /// Record that a command is being run now.
+    pub fn record(&self) {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+        self.0.store(now, Ordering::Relaxed);
+    }
+
+    /// Time of the last recorded command, or None if none was run.
+    pub fn get(&self) -> Option<u64> {
+        match self.0.load(Ordering::Relaxed) {
+            0 => None,
+            at => Some(at),
+        }
+    }
+}
+
+/// Holds a startup phase until it is reported with the backend's first command.
+#[derive(Debug, Default)]
+pub(crate) struct StartupTiming(Mutex<Option<TimingPhase>>);
+
+impl StartupTiming {
+    /// Record a startup phase, replacing any that was not yet reported.
+    pub fn record(&self, phase: TimingPhase) {
+        if let Ok(mut lock) = self.0.lock() {
+            *lock = Some(phase);
+        }
+    }
+
+    /// Take the unreported startup phase, if any.
+    pub fn take(&self) -> Vec<TimingPhase> {
+        self.0
+            .lock()
+            .ok()
+            .and_then(|mut lock| lock.take())
+            .into_iter()
+            .collect()
+    }
+}
+
 /// Trait for sandbox execution backends.
 ///
 /// This trait abstracts the execution environment, allowing different backends
@@ -44,6 +187,53 @@ pub trait SandboxBackend: Send + Sync {
     /// Execute a shell command inside the sandbox
     async fn execute(&self, command: &str) -> Result<CommandResult>;
 
+    /// Execute a command attached to the host terminal, with a TTY allocated.
+    ///
+    /// Output goes straight to the terminal, so only the exit code is returned.
+    /// Default implementation reports TTY mode as unsupported.
+    async fn execute_interactive(&self, command: &str) -> Result<i32> {
+        let _ = command;
+        Err(BashletError::Unsupported {
+            operation: "tty".to_string(),
+            reason: format!("the {} backend cannot allocate a terminal", self.name()),
+        })
+    }
+
+    /// Execute a program with explicit arguments, without shell interpretation.
+    /// Default implementation quotes every argument and runs the result with `execute`.
+    async fn execute_argv(&self, argv: &[String]) -> Result<CommandResult> {
+        self.execute(&shell_join(argv)).await
+    }
+
+    /// Execute a shell command with `stdin` piped to it.
+    /// Default implementation reports piped input as unsupported.
+    async fn execute_with_stdin(&self, command: &str, stdin: &[u8]) -> Result<CommandResult> {
+        let _ = (command, stdin);
+        Err(BashletError::Unsupported {
+            operation: "stdin".to_string(),
+            reason: format!("the {} backend cannot pipe input to commands", self.name()),
+        })
+    }
+
+    /// Execute a shell command, sending stdout/stderr to the host files in
+    /// `files` instead of the returned result.
+    ///
+    /// Default implementation runs the command with output captured, then
+    /// writes it out, so it is still subject to the output limit.
+    async fn execute_to_files(
+        &self,
+        command: &str,
+        stdin: Option<&[u8]>,
+        files: &OutputFiles,
+    ) -> Result<CommandResult> {
+        let mut result = match stdin {
+            Some(stdin) => self.execute_with_stdin(command, stdin).await?,
+            None => self.execute(command).await?,
+        };
+        files.write_result(&mut result)?;
+        Ok(result)
+    }
+
     /// Write a file inside the sandbox
     async fn write_file(&self, path: &str, content: &str) -> Result<()>;
 
@@ -53,9 +243,120 @@ pub trait SandboxBackend: Send + Sync {
     /// List directory contents
     async fn list_dir(&self, path: &str) -> Result<String>;
 
+    /// List directory contents as typed entries, sorted by name, for callers
+    /// that would otherwise parse [`list_dir`](Self::list_dir) output.
+    ///
+    /// Default implementation runs `find` and `stat` inside the sandbox.
+    /// Names containing newlines are not supported.
+    async fn list_dir_entries(&self, path: &str) -> Result<Vec<DirEntry>> {
+        let result = self
+            .execute(&format!(
+                "cd {} && find . -mindepth 1 -maxdepth 1 -exec stat -c '%s %f %n' {{}} +",
+                shell_quote(path)
+            ))
+            .await?;
+
+        if result.exit_code != 0 {
+            return Err(BashletError::SandboxExecution(format!(
+                "Failed to list {}: {}",
+                path, result.stderr
+            )));
+        }
+
+        let mut entries: Vec<DirEntry> =
+            result.stdout.lines().filter_map(DirEntry::parse).collect();
+        entries.sort_by(|a, b| a.name.cmp(&b.name));
+        Ok(entries)
+    }
+
+    /// Copy a host file or directory tree into the sandbox, binary-safe.
+    ///
+    /// Like `docker cp`, the source is copied into `guest` when that is an
+    /// existing directory. Default implementation reports copying as unsupported.
+    async fn copy_in(&self, host: &Path, guest: &str) -> Result<()> {
+        let _ = (host, guest);
+        Err(BashletError::Unsupported {
+            operation: "copy".to_string(),
+            reason: format!("the {} backend cannot copy files", self.name()),
+        })
+    }
+
+    /// Copy a file or directory tree out of the sandbox to the host.
+    ///
+    /// Like `docker cp`, the source is copied into `host` when that is an
+    /// existing directory. Default implementation reports copying as unsupported.
+    async fn copy_out(&self, guest: &str, host: &Path) -> Result<()> {
+        let _ = (guest, host);
+        Err(BashletError::Unsupported {
+            operation: "copy".to_string(),
+            reason: format!("the {} backend cannot copy files", self.name()),
+        })
+    }
+
+    /// Remove a file, or a directory tree when `recursive` is set.
+    /// Default implementation runs `rm` inside the sandbox.
+    async fn remove(&self, path: &str, recursive: bool) -> Result<()> {
+        let flags = if recursive { "-rf " } else { "" };
+        let quoted = shell_quote(path);
+        let result = self.execute(&format!("rm {}-- {}", flags, quoted)).await?;
+
+        if result.exit_code != 0 {
+            return Err(BashletError::SandboxExecution(format!(
+                "Failed to remove {}: {}",
+                path, result.stderr
+            )));
+        }
+
+        Ok(())
+    }
+
+    /// Get size, type, and mode of a path, or None if it doesn't exist.
+    /// Default implementation runs `stat` inside the sandbox.
+    async fn stat(&self, path: &str) -> Result<Option<FileStat>> {
+        let quoted = shell_quote(path);
+        let result = self
+            .execute(&format!(
+                "if [ -e {0} ]; then stat -c '%s %a %F' {0}; else echo missing; fi",
+                quoted
+            ))
+            .await?;
+
+        if result.exit_code != 0 {
+            return Err(BashletError::SandboxExecution(format!(
+                "Failed to stat {}: {}",
+                path, result.stderr
+            )));
+        }
+        if result.stdout.trim() == "missing" {
+            return Ok(None);
+        }
+
+        FileStat::parse(&result.stdout).map(Some).ok_or_else(|| {
+            BashletError::SandboxExecution(format!(
+                "Unexpected stat output for {}: {}",
+                path,
+                result.stdout.trim()
+            ))
+        })
+    }
+
     /// Get information about the sandbox instance
     fn info(&self) -> SandboxInfo;
 
+    /// [`info`](Self::info) plus the result of a [`health_check`](Self::health_check).
+    async fn status(&self) -> SandboxInfo {
+        let mut info = self.info();
+        info.last_health_check = Some(self.health_check().await.unwrap_or(false));
+        info
+    }
+
+    /// Identifier of a runtime instance that outlives this process
+    /// (e.g. a Docker session container), so later commands can reattach to it.
+    /// Default implementation returns None for backends without one.
+    fn persistent_instance_id(&self) -> Option<String> {
+        None
+    }
+
     /// Gracefully shutdown the sandbox.
     /// Default implementation is a no-op for stateless backends.
     async fn shutdown(&self) -> Result<()> {
@@ -71,3 +372,29 @@ pub trait SandboxBackend: Send + Sync {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_dir_entry_parse() {
+        let entry = DirEntry::parse("4096 41ed ./src").unwrap();
+        assert_eq!(entry.name, "src");
+        assert_eq!(entry.entry_type, EntryType::Dir);
+        assert_eq!(entry.mode, 0o755);
+
+        let entry = DirEntry::parse("12 81a4 ./my notes.txt").unwrap();
+        assert_eq!(entry.name, "my notes.txt");
+        assert_eq!(entry.size, 12);
+        assert_eq!(entry.entry_type, EntryType::File);
+        assert_eq!(entry.mode, 0o644);
+
+        assert_eq!(
+            DirEntry::parse("7 a1ff ./link").unwrap().entry_type,
+            EntryType::Symlink
+        );
+        // The tail of a name containing a newline
+        assert!(DirEntry::parse("rest of name").is_none());
+    }
+}
@@ -1,38 +1,94 @@
-use std::path::PathBuf;
-use std::time::{SystemTime, UNIX_EPOCH};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 
+use fs2::FileExt;
 use serde::{Deserialize, Serialize};
 use tokio::fs;
-use tracing::{debug, info};
+use tracing::{debug, info, warn};
 
 use crate::cli::args::Mount;
 use crate::config::loader::get_data_dir;
 use crate::error::{BashletError, Result};
+use crate::sandbox::BackendCapabilities;
+
+/// Current version of the session file format.
+///
+/// Fields added since version 1 must have `#[serde(default)]` so older
+/// files still load; bump this when a record needs rewriting in [`migrate`].
+pub const SESSION_SCHEMA_VERSION: u32 = 1;
 
 /// Session state that persists between commands
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Session {
+    /// Version of the format this record was written with (0 = before versioning)
+    #[serde(default)]
+    pub schema_version: u32,
     /// Unique session identifier
     pub id: String,
     /// Optional human-readable name
+    #[serde(default)]
     pub name: Option<String>,
     /// Mount configurations
+    #[serde(default)]
     pub mounts: Vec<SerializableMount>,
     /// Environment variables
+    #[serde(default)]
     pub env_vars: Vec<(String, String)>,
     /// Working directory inside sandbox
     pub workdir: String,
     /// Custom WASM binary path
+    #[serde(default)]
     pub wasm_binary: Option<PathBuf>,
     /// Creation timestamp (Unix epoch seconds)
     pub created_at: u64,
     /// Last activity timestamp (Unix epoch seconds)
     pub last_activity: u64,
     /// Time-to-live in seconds (None = no expiration)
+    #[serde(default)]
     pub ttl_seconds: Option<u64>,
     /// Preset used to create this session
     #[serde(default)]
     pub preset: Option<String>,
+    /// Backend instance kept alive between commands (a Docker session container
+    /// ID, or a Firecracker VM ID starting with `fc-`)
+    #[serde(default)]
+    pub instance_id: Option<String>,
+    /// DNS servers given at creation time
+    #[serde(default)]
+    pub dns: Vec<String>,
+    /// Extra host entries given at creation time [(hostname, ip), ...]
+    #[serde(default)]
+    pub extra_hosts: Vec<(String, String)>,
+    /// Capabilities required of the backend (--require-* flags)
+    #[serde(default)]
+    pub required_capabilities: BackendCapabilities,
+    /// Firecracker root filesystem override (--rootfs)
+    #[serde(default)]
+    pub rootfs_path: Option<PathBuf>,
+    /// Firecracker kernel override (--kernel)
+    #[serde(default)]
+    pub kernel_path: Option<PathBuf>,
+    /// Host directory holding the --git clone, removed with the session
+    #[serde(default)]
+    pub git_checkout: Option<PathBuf>,
+    /// Interpreter override (--shell)
+    #[serde(default)]
+    pub shell: Option<String>,
+    /// Whether the container was stopped by `sandbox.idle_shutdown`; the
+    /// next command starts it again
+    #[serde(default)]
+    pub idle_stopped: bool,
+}
+
+/// How long to wait for another process to finish starting a session
+const SESSION_LOCK_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Exclusive per-session lock, released when dropped.
+///
+/// Held while a session's sandbox is created or started, so concurrent
+/// commands don't start duplicate containers or create the session twice.
+pub struct SessionLock {
+    _file: std::fs::File,
 }
 
 /// Serializable version of Mount (PathBuf needs special handling)
@@ -41,6 +97,8 @@ pub struct SerializableMount {
     pub host_path: String,
     pub guest_path: String,
     pub readonly: bool,
+    #[serde(default)]
+    pub options: Vec<String>,
 }
 
 impl From<&Mount> for SerializableMount {
@@ -49,6 +107,7 @@ impl From<&Mount> for SerializableMount {
             host_path: mount.host_path.display().to_string(),
             guest_path: mount.guest_path.clone(),
             readonly: mount.readonly,
+            options: mount.options.clone(),
         }
     }
 }
@@ -59,6 +118,8 @@ impl From<&SerializableMount> for Mount {
             host_path: PathBuf::from(&mount.host_path),
             guest_path: mount.guest_path.clone(),
             readonly: mount.readonly,
+            explicit_access: true,
+            options: mount.options.clone(),
         }
     }
 }
@@ -83,6 +144,7 @@ impl Session {
         let id = generate_session_id();
 
         Self {
+            schema_version: SESSION_SCHEMA_VERSION,
             id,
             name,
             mounts: mounts.iter().map(SerializableMount::from).collect(),
@@ -93,6 +155,15 @@ impl Session {
             last_activity: now,
             ttl_seconds,
             preset,
+            instance_id: None,
+            dns: Vec::new(),
+            extra_hosts: Vec::new(),
+            required_capabilities: BackendCapabilities::default(),
+            rootfs_path: None,
+            kernel_path: None,
+            git_checkout: None,
+            shell: None,
+            idle_stopped: false,
         }
     }
 
@@ -151,12 +222,81 @@ impl SessionManager {
         self.sessions_dir.join(format!("{}.json", id))
     }
 
+    /// Lock a session by its ID, or by name while creating a session that
+    /// has no ID yet. Use [`SessionManager::lock_existing`] for a session
+    /// requested by either, so both forms take the same lock.
+    ///
+    /// Waits up to [`SESSION_LOCK_TIMEOUT`] for another holder to finish.
+    pub async fn lock(&self, id_or_name: &str) -> Result<SessionLock> {
+        let file = self.lock_file(id_or_name).await?;
+
+        let deadline = Instant::now() + SESSION_LOCK_TIMEOUT;
+        loop {
+            match file.try_lock_exclusive() {
+                Ok(()) => return Ok(SessionLock { _file: file }),
+                Err(_) if Instant::now() < deadline => {
+                    debug!(session = %id_or_name, "Waiting for session lock");
+                    tokio::time::sleep(Duration::from_millis(100)).await;
+                }
+                Err(_) => {
+                    return Err(BashletError::SessionLocked {
+                        id: id_or_name.to_string(),
+                    })
+                }
+            }
+        }
+    }
+
+    /// Resolve an existing session's ID or name, lock it by ID, and return
+    /// it re-read under the lock.
+    pub async fn lock_existing(&self, id_or_name: &str) -> Result<(Session, SessionLock)> {
+        let id = self.get(id_or_name).await?.id;
+        let lock = self.lock(&id).await?;
+        let session = self.get(&id).await?;
+        Ok((session, lock))
+    }
+
+    /// Lock a session like [`SessionManager::lock`], or return None at once
+    /// if another command holds it.
+    pub async fn try_lock(&self, id_or_name: &str) -> Result<Option<SessionLock>> {
+        let file = self.lock_file(id_or_name).await?;
+        Ok(file
+            .try_lock_exclusive()
+            .ok()
+            .map(|()| SessionLock { _file: file }))
+    }
+
+    fn lock_path(&self, id_or_name: &str) -> PathBuf {
+        self.sessions_dir
+            .join(format!("{}.lock", id_or_name.replace('/', "_")))
+    }
+
+    async fn lock_file(&self, id_or_name: &str) -> Result<std::fs::File> {
+        self.ensure_dir().await?;
+        Ok(std::fs::OpenOptions::new()
+            .create(true)
+            .truncate(false)
+            .write(true)
+            .open(self.lock_path(id_or_name))?)
+    }
+
+    /// Remove the ID and name lock files of a deleted session.
+    async fn remove_lock_files(&self, id: &str, name: Option<&str>) {
+        for key in std::iter::once(id).chain(name) {
+            let _ = fs::remove_file(self.lock_path(key)).await;
+        }
+    }
+
     /// Save a session to disk
+    ///
+    /// Writes to a temporary file first so readers never see a partial session.
     pub async fn save(&self, session: &Session) -> Result<()> {
         self.ensure_dir().await?;
         let path = self.session_path(&session.id);
         let json = serde_json::to_string_pretty(session)?;
-        fs::write(&path, json).await?;
+        let tmp_path = path.with_extension("json.tmp");
+        fs::write(&tmp_path, json).await?;
+        fs::rename(&tmp_path, &path).await?;
         debug!(id = %session.id, path = %path.display(), "Saved session");
         Ok(())
     }
@@ -167,7 +307,11 @@ impl SessionManager {
         let path = self.session_path(id_or_name);
         if path.exists() {
             let json = fs::read_to_string(&path).await?;
-            let session: Session = serde_json::from_str(&json)?;
+            let mut session: Session = match serde_json::from_str(&json) {
+                Ok(session) => session,
+                Err(e) => return Err(self.quarantine(id_or_name, &path, e).await),
+            };
+            self.upgrade(&mut session).await;
 
             if session.is_expired() {
                 // Clean up expired session
@@ -199,12 +343,49 @@ impl SessionManager {
         })
     }
 
+    /// Migrate a session loaded from an older format and rewrite its file.
+    async fn upgrade(&self, session: &mut Session) {
+        if !migrate(session) {
+            return;
+        }
+        info!(id = %session.id, version = session.schema_version, "Upgraded session file");
+        if let Err(e) = self.save(session).await {
+            warn!(id = %session.id, error = %e, "Failed to save upgraded session");
+        }
+    }
+
+    /// Move an unparseable session file aside so it stops breaking lookups.
+    async fn quarantine(&self, id: &str, path: &Path, error: serde_json::Error) -> BashletError {
+        let corrupt_path = path.with_extension("json.corrupt");
+        warn!(path = %path.display(), error = %error, "Session file is corrupt");
+
+        match fs::rename(path, &corrupt_path).await {
+            Ok(()) => BashletError::SessionCorrupt {
+                id: id.to_string(),
+                path: corrupt_path.display().to_string(),
+            },
+            Err(e) => {
+                warn!(path = %path.display(), error = %e, "Failed to move corrupt session file");
+                error.into()
+            }
+        }
+    }
+
     /// Delete a session
     pub async fn delete(&self, id_or_name: &str) -> Result<()> {
         // Try direct ID first
         let path = self.session_path(id_or_name);
         if path.exists() {
+            let session = fs::read_to_string(&path)
+                .await
+                .ok()
+                .and_then(|json| serde_json::from_str::<Session>(&json).ok());
             fs::remove_file(&path).await?;
+            let name = session.as_ref().and_then(|s| s.name.as_deref());
+            self.remove_lock_files(id_or_name, name).await;
+            if let Some(ref session) = session {
+                remove_git_checkout(session).await;
+            }
             info!(id = %id_or_name, "Deleted session");
             return Ok(());
         }
@@ -215,6 +396,9 @@ impl SessionManager {
             if session.name.as_deref() == Some(id_or_name) {
                 let path = self.session_path(&session.id);
                 fs::remove_file(&path).await?;
+                self.remove_lock_files(&session.id, session.name.as_deref())
+                    .await;
+                remove_git_checkout(&session).await;
                 info!(id = %session.id, name = %id_or_name, "Deleted session");
                 return Ok(());
             }
@@ -237,7 +421,8 @@ impl SessionManager {
             if path.extension().and_then(|s| s.to_str()) == Some("json") {
                 match fs::read_to_string(&path).await {
                     Ok(json) => {
-                        if let Ok(session) = serde_json::from_str::<Session>(&json) {
+                        if let Ok(mut session) = serde_json::from_str::<Session>(&json) {
+                            self.upgrade(&mut session).await;
                             sessions.push(session);
                         }
                     }
@@ -249,7 +434,7 @@ impl SessionManager {
         }
 
         // Sort by creation time (newest first)
-        sessions.sort_by(|a, b| b.created_at.cmp(&a.created_at));
+        sessions.sort_by_key(|s| std::cmp::Reverse(s.created_at));
 
         Ok(sessions)
     }
@@ -288,6 +473,31 @@ impl Default for SessionManager {
     }
 }
 
+/// Create a fresh, unused directory path for a `--git` clone.
+pub fn new_checkout_dir() -> PathBuf {
+    get_data_dir().join("checkouts").join(generate_session_id())
+}
+
+/// Remove the host directory of a session's `--git` clone, if it has one.
+async fn remove_git_checkout(session: &Session) {
+    if let Some(ref dir) = session.git_checkout {
+        if let Err(e) = fs::remove_dir_all(dir).await {
+            warn!(path = %dir.display(), error = %e, "Failed to remove git checkout");
+        }
+    }
+}
+
+/// Bring a session up to [`SESSION_SCHEMA_VERSION`]. Returns whether it changed.
+pub fn migrate(session: &mut Session) -> bool {
+    if session.schema_version >= SESSION_SCHEMA_VERSION {
+        return false;
+    }
+
+    // 0 -> 1: fields missing from unversioned files are filled by serde defaults
+    session.schema_version = SESSION_SCHEMA_VERSION;
+    true
+}
+
 /// Generate a short, unique session ID
 fn generate_session_id() -> String {
     use std::sync::atomic::{AtomicU32, Ordering};
@@ -364,6 +574,25 @@ mod tests {
         assert_eq!(parse_ttl("60").unwrap(), 60);
     }
 
+    #[test]
+    fn test_migrate_legacy_session() {
+        let legacy = r#"{
+            "id": "abc",
+            "workdir": "/workspace",
+            "created_at": 1700000000,
+            "last_activity": 1700000000
+        }"#;
+
+        let mut session: Session = serde_json::from_str(legacy).unwrap();
+        assert_eq!(session.schema_version, 0);
+        assert!(session.mounts.is_empty());
+        assert!(session.preset.is_none());
+
+        assert!(migrate(&mut session));
+        assert_eq!(session.schema_version, SESSION_SCHEMA_VERSION);
+        assert!(!migrate(&mut session));
+    }
+
     #[test]
     fn test_format_base36() {
         assert_eq!(format_base36(0), "0");
@@ -371,4 +600,51 @@ mod tests {
         assert_eq!(format_base36(35), "z");
         assert_eq!(format_base36(36), "10");
     }
+
+    #[tokio::test]
+    async fn test_lock_existing_by_name_locks_id() {
+        let dir = tempfile::tempdir().unwrap();
+        let manager = SessionManager {
+            sessions_dir: dir.path().to_path_buf(),
+        };
+        let session = Session::new(
+            Some("dev".to_string()),
+            Vec::new(),
+            Vec::new(),
+            "/workspace".to_string(),
+            None,
+            None,
+            None,
+        );
+        manager.save(&session).await.unwrap();
+
+        let (locked, _lock) = manager.lock_existing("dev").await.unwrap();
+        assert_eq!(locked.id, session.id);
+        assert!(manager.try_lock(&session.id).await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_delete_removes_lock_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let manager = SessionManager {
+            sessions_dir: dir.path().to_path_buf(),
+        };
+        let session = Session::new(
+            Some("dev".to_string()),
+            Vec::new(),
+            Vec::new(),
+            "/workspace".to_string(),
+            None,
+            None,
+            None,
+        );
+        let name_lock = manager.lock("dev").await.unwrap();
+        manager.save(&session).await.unwrap();
+        drop(name_lock);
+
+        let (_, _lock) = manager.lock_existing("dev").await.unwrap();
+        manager.delete("dev").await.unwrap();
+        assert!(!manager.lock_path(&session.id).exists());
+        assert!(!manager.lock_path("dev").exists());
+    }
 }